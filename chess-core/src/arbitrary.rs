@@ -0,0 +1,116 @@
+//! Random legal positions and move sequences for property tests, behind the
+//! `arbitrary` feature. Generation is seeded, so a failing case can be replayed
+//! from the seed it was built with.
+
+use crate::{moves::Move, position::Position};
+
+/// Positions random games start from: the initial position and the perft positions
+/// rich in castling, en passant, promotions and pins
+const STARTS: [&str; 5] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+];
+
+/// A small xorshift generator, enough to pick moves without a dependency
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..bound`; `bound` must not be zero
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Up to `length` random legal moves from `position`, stopping early if the game
+/// ends. The position is left after the last of them.
+pub fn legal_move_sequence(position: &mut Position, rng: &mut Rng, length: usize) -> Vec<Move> {
+    let mut played = Vec::with_capacity(length);
+    for _ in 0..length {
+        let moves = position.generate_moves();
+        if moves.is_empty() {
+            break;
+        }
+        let mv = moves[rng.below(moves.len())];
+        position.make_move(&mv).expect("Generated moves are legal");
+        played.push(mv);
+    }
+    played
+}
+
+/// A legal position reached by up to `max_plies` random moves from one of the
+/// start positions, with the moves kept in its history
+pub fn legal_position(rng: &mut Rng, max_plies: usize) -> Position {
+    let start = STARTS[rng.below(STARTS.len())];
+    let mut position = Position::from_fen(start).expect("Start positions are valid");
+    let plies = rng.below(max_plies + 1);
+    legal_move_sequence(&mut position, rng, plies);
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{endgame::MaterialKey, zobrist};
+
+    const CASES: u64 = 64;
+
+    #[test]
+    fn test_make_undo_restores_position() {
+        for seed in 0..CASES {
+            let mut rng = Rng::new(seed);
+            let mut position = legal_position(&mut rng, 40);
+            let (fen, key) = (position.to_fen(), position.key());
+            let played = legal_move_sequence(&mut position, &mut rng, 12);
+            for _ in &played {
+                position.undo_move().unwrap();
+            }
+            assert_eq!(position.to_fen(), fen, "seed {seed}");
+            assert_eq!(position.key(), key, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_fen_roundtrip() {
+        for seed in 0..CASES {
+            let position = legal_position(&mut Rng::new(seed), 60);
+            let fen = position.to_fen();
+            let parsed = Position::from_fen(&fen).unwrap();
+            assert_eq!(parsed.to_fen(), fen, "seed {seed}");
+            assert_eq!(parsed.key(), position.key(), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_incremental_keys_match_hashes() {
+        for seed in 0..CASES {
+            let position = legal_position(&mut Rng::new(seed), 60);
+            assert_eq!(position.key(), zobrist::hash(&position), "seed {seed}");
+            assert_eq!(
+                position.pawn_key(),
+                zobrist::pawn_hash(&position.board),
+                "seed {seed}"
+            );
+            assert_eq!(
+                position.material_key(),
+                MaterialKey::of(&position.board),
+                "seed {seed}"
+            );
+        }
+    }
+}
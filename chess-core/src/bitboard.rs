@@ -210,9 +210,98 @@ pub const RANK_6: Bitboard = Bitboard(0x0000FF0000000000);
 pub const RANK_7: Bitboard = Bitboard(0x00FF000000000000);
 pub const RANK_8: Bitboard = Bitboard(0xFF00000000000000);
 
+pub const FILES: [Bitboard; 8] = [
+    FILE_A, FILE_B, FILE_C, FILE_D, FILE_E, FILE_F, FILE_G, FILE_H,
+];
+
+pub const RANKS: [Bitboard; 8] = [
+    RANK_1, RANK_2, RANK_3, RANK_4, RANK_5, RANK_6, RANK_7, RANK_8,
+];
+
+/// Files directly left and right of `file`
+pub fn adjacent_files(file: u8) -> Bitboard {
+    let file = FILES[file as usize];
+    file.east() | file.west()
+}
+
 pub const LIGHT_SQUARES: Bitboard = Bitboard(0x55AA55AA55AA55AA);
 pub const DARK_SQUARES: Bitboard = Bitboard(0xAA55AA55AA55AA55);
 
+/// Rook and bishop directions as (file step, rank step)
+const DIRECTIONS: [(i8, i8); 8] = [
+    (0, 1),
+    (0, -1),
+    (1, 0),
+    (-1, 0),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// Squares from `square` towards the edge in one direction, excluding `square`
+fn ray(square: u8, (file_step, rank_step): (i8, i8)) -> Vec<u8> {
+    let mut squares = Vec::new();
+    let (mut file, mut rank) = ((square % 8) as i8, (square / 8) as i8);
+    loop {
+        file += file_step;
+        rank += rank_step;
+        if !(0..8).contains(&file) || !(0..8).contains(&rank) {
+            return squares;
+        }
+        squares.push((rank * 8 + file) as u8);
+    }
+}
+
+lazy_static::lazy_static! {
+    /// `BETWEEN[a][b]`: squares strictly between two aligned squares
+    static ref BETWEEN: Vec<[Bitboard; 64]> = {
+        let mut table = vec![[Bitboard::empty(); 64]; 64];
+        for from in 0..64u8 {
+            for direction in DIRECTIONS {
+                let mut passed = Bitboard::empty();
+                for to in ray(from, direction) {
+                    table[from as usize][to as usize] = passed;
+                    passed.set_square(to);
+                }
+            }
+        }
+        table
+    };
+
+    /// `LINE[a][b]`: the whole rank, file or diagonal through two aligned squares
+    static ref LINE: Vec<[Bitboard; 64]> = {
+        let mut table = vec![[Bitboard::empty(); 64]; 64];
+        for from in 0..64u8 {
+            for (file_step, rank_step) in DIRECTIONS {
+                let mut full = Bitboard::from_square(from);
+                for square in ray(from, (file_step, rank_step))
+                    .into_iter()
+                    .chain(ray(from, (-file_step, -rank_step)))
+                {
+                    full.set_square(square);
+                }
+                for to in ray(from, (file_step, rank_step)) {
+                    table[from as usize][to as usize] = full;
+                }
+            }
+        }
+        table
+    };
+}
+
+/// Squares strictly between `a` and `b` on a shared rank, file or diagonal; empty
+/// when the squares are not aligned
+pub fn between(a: u8, b: u8) -> Bitboard {
+    BETWEEN[a as usize][b as usize]
+}
+
+/// The full rank, file or diagonal through `a` and `b`; empty when they are not
+/// aligned
+pub fn line(a: u8, b: u8) -> Bitboard {
+    LINE[a as usize][b as usize]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -254,6 +343,13 @@ mod tests {
         assert_eq!(bb.southwest(), Bitboard::from_square(54)); // g7
     }
 
+    #[test]
+    fn test_adjacent_files() {
+        assert_eq!(adjacent_files(0), FILE_B);
+        assert_eq!(adjacent_files(4), FILE_D | FILE_F);
+        assert_eq!(adjacent_files(7), FILE_G);
+    }
+
     #[test]
     fn test_bitboard_iterator() {
         let mut bb = Bitboard::empty();
@@ -264,4 +360,38 @@ mod tests {
         let squares: Vec<u8> = bb.squares().collect();
         assert_eq!(squares, vec![0, 2, 4]);
     }
+
+    #[test]
+    fn test_between_and_line() {
+        // a1 and d4 share the long diagonal
+        assert_eq!(between(0, 27), Bitboard::from_square(9) | Bitboard::from_square(18));
+        assert_eq!(between(27, 0), between(0, 27));
+        assert_eq!(line(0, 27).count(), 8);
+        assert!(line(0, 27).has_square(63));
+
+        // e1 and e8 share a file; adjacent squares have nothing between them
+        let ends = Bitboard::from_square(4) | Bitboard::from_square(60);
+        assert_eq!(between(4, 60), FILE_E & !ends);
+        assert!(between(4, 12).is_empty());
+        assert_eq!(line(4, 12), FILE_E);
+
+        // a1 and b3 are not aligned
+        assert!(between(0, 17).is_empty());
+        assert!(line(0, 17).is_empty());
+    }
+
+    #[test]
+    fn test_between_and_line_tables() {
+        for a in 0..64u8 {
+            for b in 0..64u8 {
+                assert_eq!(between(a, b), between(b, a));
+                assert_eq!(line(a, b), line(b, a));
+                // The squares between lie on the line, which runs through both ends
+                assert!((between(a, b) & !line(a, b)).is_empty());
+                if !line(a, b).is_empty() {
+                    assert!(line(a, b).has_square(a) && line(a, b).has_square(b));
+                }
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 
-use crate::bitboard::Bitboard;
+use crate::{bitboard::Bitboard, magic_simple as magic, movegen, pawns, Error};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
@@ -48,6 +48,16 @@ pub enum PieceType {
 }
 
 impl PieceType {
+    /// Every piece type, in index order
+    pub const ALL: [PieceType; 6] = [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+        PieceType::King,
+    ];
+
     pub fn from_char(ch: char) -> Option<Self> {
         match ch {
             'P' | 'p' => Some(PieceType::Pawn),
@@ -105,10 +115,80 @@ impl Piece {
 pub struct Square(pub u8);
 
 impl Square {
+    pub const A1: Square = Square(0);
+    pub const B1: Square = Square(1);
+    pub const C1: Square = Square(2);
+    pub const D1: Square = Square(3);
+    pub const E1: Square = Square(4);
+    pub const F1: Square = Square(5);
+    pub const G1: Square = Square(6);
+    pub const H1: Square = Square(7);
+    pub const A2: Square = Square(8);
+    pub const B2: Square = Square(9);
+    pub const C2: Square = Square(10);
+    pub const D2: Square = Square(11);
+    pub const E2: Square = Square(12);
+    pub const F2: Square = Square(13);
+    pub const G2: Square = Square(14);
+    pub const H2: Square = Square(15);
+    pub const A3: Square = Square(16);
+    pub const B3: Square = Square(17);
+    pub const C3: Square = Square(18);
+    pub const D3: Square = Square(19);
+    pub const E3: Square = Square(20);
+    pub const F3: Square = Square(21);
+    pub const G3: Square = Square(22);
+    pub const H3: Square = Square(23);
+    pub const A4: Square = Square(24);
+    pub const B4: Square = Square(25);
+    pub const C4: Square = Square(26);
+    pub const D4: Square = Square(27);
+    pub const E4: Square = Square(28);
+    pub const F4: Square = Square(29);
+    pub const G4: Square = Square(30);
+    pub const H4: Square = Square(31);
+    pub const A5: Square = Square(32);
+    pub const B5: Square = Square(33);
+    pub const C5: Square = Square(34);
+    pub const D5: Square = Square(35);
+    pub const E5: Square = Square(36);
+    pub const F5: Square = Square(37);
+    pub const G5: Square = Square(38);
+    pub const H5: Square = Square(39);
+    pub const A6: Square = Square(40);
+    pub const B6: Square = Square(41);
+    pub const C6: Square = Square(42);
+    pub const D6: Square = Square(43);
+    pub const E6: Square = Square(44);
+    pub const F6: Square = Square(45);
+    pub const G6: Square = Square(46);
+    pub const H6: Square = Square(47);
+    pub const A7: Square = Square(48);
+    pub const B7: Square = Square(49);
+    pub const C7: Square = Square(50);
+    pub const D7: Square = Square(51);
+    pub const E7: Square = Square(52);
+    pub const F7: Square = Square(53);
+    pub const G7: Square = Square(54);
+    pub const H7: Square = Square(55);
+    pub const A8: Square = Square(56);
+    pub const B8: Square = Square(57);
+    pub const C8: Square = Square(58);
+    pub const D8: Square = Square(59);
+    pub const E8: Square = Square(60);
+    pub const F8: Square = Square(61);
+    pub const G8: Square = Square(62);
+    pub const H8: Square = Square(63);
+
     pub const fn new(file: u8, rank: u8) -> Self {
         Square(rank * 8 + file)
     }
 
+    /// Every square, a1 to h8 rank by rank
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(Square)
+    }
+
     pub fn from_algebraic(s: &str) -> Option<Self> {
         if s.len() != 2 {
             return None;
@@ -162,11 +242,48 @@ impl Square {
     pub fn bitboard(self) -> Bitboard {
         Bitboard::from_square(self.0)
     }
+
+    /// The square on the same file, seen from the other side of the board
+    pub const fn flip_rank(self) -> Square {
+        Square(self.0 ^ 56)
+    }
+
+    /// The square on the same rank, with the a and h files swapped
+    pub const fn flip_file(self) -> Square {
+        Square(self.0 ^ 7)
+    }
+
+    /// Number of king moves between the two squares
+    pub fn distance(self, other: Square) -> u8 {
+        let files = self.file().abs_diff(other.file());
+        let ranks = self.rank().abs_diff(other.rank());
+        files.max(ranks)
+    }
+}
+
+impl TryFrom<u8> for Square {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Error> {
+        if value < 64 {
+            Ok(Square(value))
+        } else {
+            Err(Error::InvalidSquare(value.to_string()))
+        }
+    }
+}
+
+impl std::str::FromStr for Square {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Square::from_algebraic(s).ok_or_else(|| Error::InvalidSquare(s.to_string()))
+    }
 }
 
-impl From<u8> for Square {
-    fn from(value: u8) -> Self {
-        Square(value)
+impl std::fmt::Display for Square {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_algebraic())
     }
 }
 
@@ -244,14 +361,7 @@ impl Board {
         let sq_bb = square.bitboard();
 
         for color in [Color::White, Color::Black] {
-            for piece_type in [
-                PieceType::Pawn,
-                PieceType::Knight,
-                PieceType::Bishop,
-                PieceType::Rook,
-                PieceType::Queen,
-                PieceType::King,
-            ] {
+            for piece_type in PieceType::ALL {
                 if (self.pieces[color as usize][piece_type as usize] & sq_bb).0 != 0 {
                     return Some(Piece::new(color, piece_type));
                 }
@@ -261,37 +371,134 @@ impl Board {
         None
     }
 
+    /// Place or remove a piece, replacing whatever stood on `square`
     pub fn set_piece(&mut self, square: Square, piece: Option<Piece>) {
-        for color in [Color::White, Color::Black] {
-            for piece_type in [
-                PieceType::Pawn,
-                PieceType::Knight,
-                PieceType::Bishop,
-                PieceType::Rook,
-                PieceType::Queen,
-                PieceType::King,
-            ] {
-                self.pieces[color as usize][piece_type as usize].clear_square(square.index());
-            }
+        self.remove_piece(square);
+        if let Some(piece) = piece {
+            self.put_piece(square, piece);
         }
+    }
 
-        if let Some(piece) = piece {
-            self.pieces[piece.color as usize][piece.piece_type as usize].set_square(square.index());
+    /// Place `piece` on the empty `square`, updating only the bitboards it touches
+    pub fn put_piece(&mut self, square: Square, piece: Piece) {
+        debug_assert!(self.empty.has_square(square.index()), "{square:?} is occupied");
+        let bb = square.bitboard();
+        self.pieces[piece.color as usize][piece.piece_type as usize] |= bb;
+        match piece.color {
+            Color::White => self.white |= bb,
+            Color::Black => self.black |= bb,
         }
+        self.occupied |= bb;
+        self.empty = !self.occupied;
+    }
 
-        self.update_derived();
+    /// Take the piece off `square`, returning it
+    pub fn remove_piece(&mut self, square: Square) -> Option<Piece> {
+        let piece = self.piece_at(square)?;
+        let bb = !square.bitboard();
+        self.pieces[piece.color as usize][piece.piece_type as usize] &= bb;
+        match piece.color {
+            Color::White => self.white &= bb,
+            Color::Black => self.black &= bb,
+        }
+        self.occupied &= bb;
+        self.empty = !self.occupied;
+        Some(piece)
+    }
+
+    /// Move the piece on `from` to the empty `to`, returning it
+    pub fn move_piece(&mut self, from: Square, to: Square) -> Option<Piece> {
+        let piece = self.remove_piece(from)?;
+        self.put_piece(to, piece);
+        Some(piece)
+    }
+
+    /// Every piece on the board with its square, White first, by piece type
+    pub fn pieces(&self) -> impl Iterator<Item = (Square, Piece)> + '_ {
+        [Color::White, Color::Black]
+            .into_iter()
+            .flat_map(move |color| self.pieces_of_color(color))
+    }
+
+    /// Every piece of one colour with its square, by piece type
+    pub fn pieces_of_color(&self, color: Color) -> impl Iterator<Item = (Square, Piece)> + '_ {
+        PieceType::ALL.into_iter().flat_map(move |piece_type| {
+            let piece = Piece::new(color, piece_type);
+            self.squares_of(color, piece_type).map(move |square| (square, piece))
+        })
+    }
+
+    /// Squares holding pieces of one type and colour
+    pub fn squares_of(&self, color: Color, piece_type: PieceType) -> impl Iterator<Item = Square> {
+        self.piece_bitboard(color, piece_type).squares().map(Square)
     }
 
     pub fn piece_bitboard(&self, color: Color, piece_type: PieceType) -> Bitboard {
         self.pieces[color as usize][piece_type as usize]
     }
 
+    /// Number of pieces of one type and colour
+    pub fn piece_count(&self, color: Color, piece_type: PieceType) -> u32 {
+        self.piece_bitboard(color, piece_type).count()
+    }
+
+    /// Number of pieces of each type of one colour, indexed by `PieceType`
+    pub fn piece_counts(&self, color: Color) -> [u32; 6] {
+        self.pieces[color as usize].map(Bitboard::count)
+    }
+
     pub fn color_bitboard(&self, color: Color) -> Bitboard {
         match color {
             Color::White => self.white,
             Color::Black => self.black,
         }
     }
+
+    /// Pieces of one type of both colours
+    pub fn pieces_of_type(&self, piece_type: PieceType) -> Bitboard {
+        self.piece_bitboard(Color::White, piece_type)
+            | self.piece_bitboard(Color::Black, piece_type)
+    }
+
+    /// Pieces of both colours attacking `square`, with sliders blocked only by
+    /// `occupied`, so pieces can be lifted off to see x-rays
+    pub fn attackers_to(&self, square: Square, occupied: Bitboard) -> Bitboard {
+        let target = square.bitboard();
+        let queens = self.pieces_of_type(PieceType::Queen);
+        let diagonal = self.pieces_of_type(PieceType::Bishop) | queens;
+        let straight = self.pieces_of_type(PieceType::Rook) | queens;
+
+        let white_pawns = self.piece_bitboard(Color::White, PieceType::Pawn);
+        let black_pawns = self.piece_bitboard(Color::Black, PieceType::Pawn);
+
+        // A pawn attacks the square if the square attacks the pawn the other way round
+        (pawns::pawn_attacks(target, Color::Black) & white_pawns)
+            | (pawns::pawn_attacks(target, Color::White) & black_pawns)
+            | (movegen::knight_attacks(square) & self.pieces_of_type(PieceType::Knight))
+            | (movegen::king_attacks(square) & self.pieces_of_type(PieceType::King))
+            | (magic::get_bishop_attacks(square.index(), occupied) & diagonal)
+            | (magic::get_rook_attacks(square.index(), occupied) & straight)
+    }
+
+    /// Every square attacked by a piece of `color`, with sliders blocked only by
+    /// `occupied`
+    pub fn attacks_by(&self, color: Color, occupied: Bitboard) -> Bitboard {
+        let queens = self.piece_bitboard(color, PieceType::Queen);
+        let mut attacks = pawns::pawn_attacks(self.piece_bitboard(color, PieceType::Pawn), color);
+        for square in self.piece_bitboard(color, PieceType::Knight).squares() {
+            attacks |= movegen::knight_attacks(Square(square));
+        }
+        for square in (self.piece_bitboard(color, PieceType::Bishop) | queens).squares() {
+            attacks |= magic::get_bishop_attacks(square, occupied);
+        }
+        for square in (self.piece_bitboard(color, PieceType::Rook) | queens).squares() {
+            attacks |= magic::get_rook_attacks(square, occupied);
+        }
+        for square in self.piece_bitboard(color, PieceType::King).squares() {
+            attacks |= movegen::king_attacks(Square(square));
+        }
+        attacks
+    }
 }
 
 impl Default for Board {
@@ -314,6 +521,9 @@ mod tests {
         assert_eq!(Square::new(0, 0).to_algebraic(), "a1");
         assert_eq!(Square::new(4, 3).to_algebraic(), "e4");
         assert_eq!(Square::new(7, 7).to_algebraic(), "h8");
+
+        assert_eq!(Square::new(0, 0).distance(Square::new(7, 7)), 7);
+        assert_eq!(Square::new(4, 3).distance(Square::new(5, 5)), 2);
     }
 
     #[test]
@@ -338,6 +548,9 @@ mod tests {
         assert_eq!(board.black.count(), 16);
         assert_eq!(board.occupied.count(), 32);
         assert_eq!(board.empty.count(), 32);
+
+        assert_eq!(board.piece_count(Color::White, PieceType::Knight), 2);
+        assert_eq!(board.piece_counts(Color::Black), [8, 2, 2, 2, 1, 1]);
     }
 
     #[test]
@@ -349,4 +562,93 @@ mod tests {
         assert_eq!(Piece::new(Color::White, PieceType::King).to_char(), 'K');
         assert_eq!(Piece::new(Color::Black, PieceType::Queen).to_char(), 'q');
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_attackers_to() {
+        let board = crate::position::Position::from_fen("4k3/8/8/3p4/2N5/1B6/4R3/4K3 w - - 0 1")
+            .unwrap()
+            .board;
+        let square = |name: &str| Square::from_algebraic(name).unwrap();
+        let bitboard = |names: &[&str]| {
+            names.iter().fold(Bitboard::empty(), |bb, name| bb | square(name).bitboard())
+        };
+
+        // The d5 pawn and e2 rook hit e4; the c4 knight screens d5 from the b3 bishop
+        let occupied = board.occupied;
+        assert_eq!(board.attackers_to(square("e4"), occupied), bitboard(&["d5", "e2"]));
+        assert!(board.attackers_to(square("d5"), occupied).is_empty());
+        let lifted = occupied & !square("c4").bitboard();
+        assert_eq!(board.attackers_to(square("d5"), lifted), bitboard(&["b3"]));
+    }
+
+    #[test]
+    fn test_incremental_updates_match_recomputation() {
+        let mut board = Board::starting_position();
+        let square = |name: &str| Square::from_algebraic(name).unwrap();
+        let check = |board: &Board| {
+            let mut recomputed = board.clone();
+            recomputed.update_derived();
+            assert_eq!(
+                (board.white, board.black, board.occupied, board.empty),
+                (recomputed.white, recomputed.black, recomputed.occupied, recomputed.empty)
+            );
+        };
+
+        let pawn = board.move_piece(square("e2"), square("e4"));
+        assert_eq!(pawn, Some(Piece::new(Color::White, PieceType::Pawn)));
+        check(&board);
+        let knight = board.remove_piece(square("b8")).unwrap();
+        check(&board);
+        board.set_piece(square("e4"), Some(knight));
+        assert_eq!(board.piece_at(square("e4")), Some(knight));
+        assert_eq!(board.piece_count(Color::White, PieceType::Pawn), 7);
+        check(&board);
+        assert_eq!(board.remove_piece(square("e5")), None);
+    }
+
+    #[test]
+    fn test_square_constants_and_traits() {
+        assert_eq!(Square::A1, Square(0));
+        assert_eq!(Square::E4, Square::from_algebraic("e4").unwrap());
+        assert_eq!(Square::H8.index(), 63);
+        assert_eq!(Square::G1.to_string(), "g1");
+        assert_eq!("c6".parse::<Square>().unwrap(), Square::C6);
+        assert!("j9".parse::<Square>().is_err());
+        assert_eq!(Square::try_from(63).unwrap(), Square::H8);
+        assert!(Square::try_from(64).is_err());
+        assert_eq!(Square::all().count(), 64);
+        assert_eq!(Square::all().last(), Some(Square::H8));
+    }
+
+    #[test]
+    fn test_piece_iterators() {
+        let board = Board::starting_position();
+        assert_eq!(board.pieces().count(), 32);
+        assert_eq!(board.pieces_of_color(Color::Black).count(), 16);
+        assert!(board
+            .pieces()
+            .all(|(square, piece)| board.piece_at(square) == Some(piece)));
+        assert_eq!(
+            board.squares_of(Color::White, PieceType::Knight).collect::<Vec<_>>(),
+            vec![Square::B1, Square::G1]
+        );
+        assert_eq!(board.pieces().last(), Some((Square::E8, Piece::from_char('k').unwrap())));
+    }
+
+    #[test]
+    fn test_attacks_by() {
+        let board = Board::starting_position();
+        let white = board.attacks_by(Color::White, board.occupied);
+        // Ranks 2 and 3 are covered, the pieces on rank 1 defend each other except a1 and h1
+        assert_eq!(white, (crate::bitboard::RANK_2 | crate::bitboard::RANK_3) | Bitboard(0x7e));
+        for square in Square::all() {
+            let attacked = !(board.attackers_to(square, board.occupied) & board.white).is_empty();
+            assert_eq!(white.has_square(square.0), attacked);
+        }
+
+        // Without the blocking pawns the rooks and queen see along the open files
+        let open = board.occupied & !crate::bitboard::RANK_2;
+        assert!(board.attacks_by(Color::White, open).has_square(Square::A7.0));
+        assert!(board.attacks_by(Color::White, open).has_square(Square::D7.0));
+    }
+}
@@ -0,0 +1,278 @@
+//! Setting up a position piece by piece, checked for legality when it is built
+
+use crate::{
+    bitboard::{RANK_1, RANK_8},
+    board::{Board, Color, Piece, PieceType, Square},
+    position::{CastlingRights, Position},
+    Error, Result,
+};
+
+/// Places pieces and sets the remaining FEN fields, then validates the result in
+/// `build`. Starts from an empty board with White to move and no castling rights.
+#[derive(Debug, Clone)]
+pub struct PositionBuilder {
+    board: Board,
+    side_to_move: Color,
+    castling_rights: CastlingRights,
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+}
+
+impl PositionBuilder {
+    /// An empty board with White to move
+    pub fn new() -> Self {
+        Self {
+            board: Board::new(),
+            side_to_move: Color::White,
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+
+    /// A builder holding the pieces and FEN fields of `position`
+    pub fn from_position(position: &Position) -> Self {
+        Self {
+            board: position.board.clone(),
+            side_to_move: position.side_to_move,
+            castling_rights: position.castling_rights,
+            en_passant: position.en_passant,
+            halfmove_clock: position.halfmove_clock,
+            fullmove_number: position.fullmove_number,
+        }
+    }
+
+    /// Put `piece` on `square`, replacing whatever stood there
+    pub fn piece(mut self, square: Square, piece: Piece) -> Self {
+        self.board.set_piece(square, Some(piece));
+        self
+    }
+
+    /// Empty `square`
+    pub fn remove(mut self, square: Square) -> Self {
+        self.board.set_piece(square, None);
+        self
+    }
+
+    /// Take every piece off the board
+    pub fn clear(mut self) -> Self {
+        self.board = Board::new();
+        self
+    }
+
+    pub fn side_to_move(mut self, color: Color) -> Self {
+        self.side_to_move = color;
+        self
+    }
+
+    /// Grant or take away one castling right, with the rook on the a or h file
+    pub fn castling(mut self, color: Color, kingside: bool, allowed: bool) -> Self {
+        self.castling_rights.set(color, kingside, allowed);
+        self
+    }
+
+    /// Replace all castling rights, including the rook files for Chess960
+    pub fn castling_rights(mut self, rights: CastlingRights) -> Self {
+        self.castling_rights = rights;
+        self
+    }
+
+    /// Square a pawn may capture en passant on, behind the pawn that just moved
+    pub fn en_passant(mut self, square: Option<Square>) -> Self {
+        self.en_passant = square;
+        self
+    }
+
+    pub fn halfmove_clock(mut self, clock: u32) -> Self {
+        self.halfmove_clock = clock;
+        self
+    }
+
+    pub fn fullmove_number(mut self, number: u32) -> Self {
+        self.fullmove_number = number;
+        self
+    }
+
+    /// The position, once it passes the checks of `validate`
+    pub fn build(&self) -> Result<Position> {
+        self.validate()?;
+        Ok(Position::from_parts(
+            self.board.clone(),
+            self.side_to_move,
+            self.castling_rights,
+            self.en_passant,
+            self.halfmove_clock,
+            self.fullmove_number,
+        ))
+    }
+
+    /// Check that each side has one king, no pawn stands on the first or last rank,
+    /// the side not to move is not in check, and the castling rights and en passant
+    /// square fit the pieces
+    pub fn validate(&self) -> Result<()> {
+        let board = &self.board;
+        for color in [Color::White, Color::Black] {
+            let kings = board.piece_bitboard(color, PieceType::King).count();
+            if kings != 1 {
+                return Err(invalid(format!("{color:?} has {kings} kings")));
+            }
+        }
+
+        let pawns = board.piece_bitboard(Color::White, PieceType::Pawn)
+            | board.piece_bitboard(Color::Black, PieceType::Pawn);
+        if let Some(square) = (pawns & (RANK_1 | RANK_8)).lsb() {
+            return Err(invalid(format!("Pawn on {}", Square(square))));
+        }
+
+        let opponent = self.side_to_move.opposite();
+        let king = Square(
+            board
+                .piece_bitboard(opponent, PieceType::King)
+                .lsb()
+                .unwrap(),
+        );
+        if board
+            .attacks_by(self.side_to_move, board.occupied)
+            .has_square(king.0)
+        {
+            return Err(invalid(format!("{opponent:?} is in check but not to move")));
+        }
+
+        for color in [Color::White, Color::Black] {
+            for kingside in [true, false] {
+                if self.castling_rights.has(color, kingside) {
+                    self.validate_castling(color, kingside)?;
+                }
+            }
+        }
+
+        if let Some(square) = self.en_passant {
+            self.validate_en_passant(square)?;
+        }
+        Ok(())
+    }
+
+    fn validate_castling(&self, color: Color, kingside: bool) -> Result<()> {
+        let back_rank = if color == Color::White { 0 } else { 7 };
+        let side = if kingside { "kingside" } else { "queenside" };
+        let king = Square(
+            self.board
+                .piece_bitboard(color, PieceType::King)
+                .lsb()
+                .unwrap(),
+        );
+        let rook = self.castling_rights.rook_square(color, kingside);
+        let rook_beside_king = if kingside {
+            rook.file() > king.file()
+        } else {
+            rook.file() < king.file()
+        };
+        if king.rank() != back_rank
+            || self.board.piece_at(rook) != Some(Piece::new(color, PieceType::Rook))
+            || !rook_beside_king
+        {
+            return Err(invalid(format!(
+                "{color:?} cannot castle {side} without king and rook at home"
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_en_passant(&self, square: Square) -> Result<()> {
+        let color = self.side_to_move;
+        let (rank, forward) = match color {
+            Color::White => (5, 8),
+            Color::Black => (2, -8),
+        };
+        let pawn = Piece::new(color.opposite(), PieceType::Pawn);
+        let valid = square.rank() == rank
+            && self.board.piece_at(square).is_none()
+            && self
+                .board
+                .piece_at(Square((square.0 as i8 + forward) as u8))
+                .is_none()
+            && self
+                .board
+                .piece_at(Square((square.0 as i8 - forward) as u8))
+                == Some(pawn);
+        if !valid {
+            return Err(invalid(format!(
+                "No pawn can be taken en passant on {square}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for PositionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn invalid(reason: String) -> Error {
+    Error::InvalidPosition(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece(color: Color, piece_type: PieceType) -> Piece {
+        Piece::new(color, piece_type)
+    }
+
+    fn kings() -> PositionBuilder {
+        PositionBuilder::new()
+            .piece(Square::E1, piece(Color::White, PieceType::King))
+            .piece(Square::E8, piece(Color::Black, PieceType::King))
+    }
+
+    #[test]
+    fn test_build_matches_fen() {
+        let position = kings()
+            .piece(Square::H1, piece(Color::White, PieceType::Rook))
+            .piece(Square::D5, piece(Color::Black, PieceType::Pawn))
+            .piece(Square::E5, piece(Color::White, PieceType::Pawn))
+            .castling(Color::White, true, true)
+            .en_passant(Some(Square::D6))
+            .halfmove_clock(0)
+            .fullmove_number(12)
+            .build()
+            .unwrap();
+        let fen = "4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 12";
+        assert_eq!(position.to_fen(), fen);
+        assert_eq!(position.key(), Position::from_fen(fen).unwrap().key());
+
+        let start = Position::new();
+        let rebuilt = PositionBuilder::from_position(&start).build().unwrap();
+        assert_eq!(rebuilt.to_fen(), start.to_fen());
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(PositionBuilder::new().build().is_err());
+        assert!(kings()
+            .piece(Square::D1, piece(Color::White, PieceType::King))
+            .build()
+            .is_err());
+        assert!(kings()
+            .piece(Square::A8, piece(Color::White, PieceType::Pawn))
+            .build()
+            .is_err());
+        // Black in check with White to move
+        assert!(kings()
+            .piece(Square::E4, piece(Color::White, PieceType::Rook))
+            .build()
+            .is_err());
+        assert!(kings()
+            .piece(Square::E4, piece(Color::White, PieceType::Rook))
+            .side_to_move(Color::Black)
+            .build()
+            .is_ok());
+        assert!(kings().castling(Color::Black, false, true).build().is_err());
+        assert!(kings().en_passant(Some(Square::D6)).build().is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Piece coordination: doubled rooks, batteries aimed at the king and heavy
+//! pieces sharing an open file
+
+use crate::{
+    bitboard::{between, line, Bitboard},
+    board::{Board, Color, PieceType, Square},
+    evaluate::{self, Score},
+    movegen,
+    params::EvalParams,
+    pawns,
+};
+
+/// Bonus for two rooks on one file with nothing between them
+pub(crate) const DOUBLED_ROOKS_BONUS: Score = Score::new(15, 10);
+
+/// Bonus for a queen lined up behind or in front of a bishop or rook, with the line
+/// running into the enemy king zone
+pub(crate) const BATTERY_BONUS: Score = Score::new(20, 0);
+
+/// Bonus for two heavy pieces connected on an open file
+pub(crate) const OPEN_LINE_BONUS: Score = Score::new(10, 5);
+
+/// Number of coordinated piece pairs earning each bonus
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CoordinationTerms {
+    pub doubled_rooks: i32,
+    pub batteries: i32,
+    pub open_lines: i32,
+}
+
+/// Whether `a` and `b` share a line with no piece between them
+fn connected(board: &Board, a: u8, b: u8) -> bool {
+    !line(a, b).is_empty() && (between(a, b) & board.occupied).is_empty()
+}
+
+fn same_file(a: u8, b: u8) -> bool {
+    a % 8 == b % 8
+}
+
+fn diagonal(a: u8, b: u8) -> bool {
+    (a % 8).abs_diff(b % 8) == (a / 8).abs_diff(b / 8)
+}
+
+/// Whether the line through `back` and `front` continues past `front` into `zone`
+fn aimed_at(back: u8, front: u8, zone: Bitboard) -> bool {
+    (line(back, front) & zone)
+        .squares()
+        .any(|target| target != front && between(back, target).has_square(front))
+}
+
+pub(crate) fn coordination_terms(board: &Board, color: Color) -> CoordinationTerms {
+    let mut terms = CoordinationTerms::default();
+    let rooks: Vec<u8> = board
+        .piece_bitboard(color, PieceType::Rook)
+        .squares()
+        .collect();
+    let queens = board.piece_bitboard(color, PieceType::Queen);
+    let open = pawns::open_files(board);
+
+    for (index, &a) in rooks.iter().enumerate() {
+        for &b in &rooks[index + 1..] {
+            if same_file(a, b) && connected(board, a, b) {
+                terms.doubled_rooks += 1;
+            }
+        }
+    }
+
+    // Heavy pieces supporting each other along an open file
+    let heavy: Vec<u8> = (queens | board.piece_bitboard(color, PieceType::Rook))
+        .squares()
+        .collect();
+    for (index, &a) in heavy.iter().enumerate() {
+        for &b in &heavy[index + 1..] {
+            if same_file(a, b) && open.has_square(a) && connected(board, a, b) {
+                terms.open_lines += 1;
+            }
+        }
+    }
+
+    let Some(king) = board
+        .piece_bitboard(color.opposite(), PieceType::King)
+        .lsb()
+    else {
+        return terms;
+    };
+    let zone = movegen::king_attacks(Square(king)) | Bitboard::from_square(king);
+    for queen in queens.squares() {
+        let partners = board
+            .piece_bitboard(color, PieceType::Bishop)
+            .squares()
+            .filter(|&b| diagonal(queen, b))
+            .chain(
+                board
+                    .piece_bitboard(color, PieceType::Rook)
+                    .squares()
+                    .filter(|&r| !diagonal(queen, r)),
+            );
+        for partner in partners {
+            if connected(board, queen, partner)
+                && (aimed_at(queen, partner, zone) || aimed_at(partner, queen, zone))
+            {
+                terms.batteries += 1;
+            }
+        }
+    }
+
+    terms
+}
+
+/// Coordination bonus of `color`, before tapering
+pub(crate) fn coordination(board: &Board, color: Color, params: &EvalParams) -> Score {
+    let terms = coordination_terms(board, color);
+    params.doubled_rooks_bonus * terms.doubled_rooks
+        + params.battery_bonus * terms.batteries
+        + params.open_line_bonus * terms.open_lines
+}
+
+/// Coordination score of one side
+pub(crate) fn side_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    coordination(board, color, params).taper(evaluate::game_phase(board))
+}
+
+/// Coordination score from White's point of view
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    side_score(board, Color::White, params) - side_score(board, Color::Black, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_doubled_rooks_on_open_file() {
+        let pos = Position::from_fen("6k1/pp3ppp/8/8/8/8/PP3PPP/3RR1K1 w - - 0 1").unwrap();
+        assert_eq!(
+            coordination_terms(&pos.board, Color::White).doubled_rooks,
+            0
+        );
+
+        let doubled = Position::from_fen("6k1/pp3ppp/8/8/8/8/PP1R1PPP/3R2K1 w - - 0 1").unwrap();
+        let terms = coordination_terms(&doubled.board, Color::White);
+        assert_eq!(terms.doubled_rooks, 1);
+        assert_eq!(terms.open_lines, 1);
+
+        // A knight between them breaks the connection
+        let split = Position::from_fen("6k1/pp3ppp/8/3R4/8/3N4/PP3PPP/3R2K1 w - - 0 1").unwrap();
+        assert_eq!(
+            coordination_terms(&split.board, Color::White).doubled_rooks,
+            0
+        );
+    }
+
+    #[test]
+    fn test_battery_at_king() {
+        // Queen behind the d3 bishop on the b1-h7 diagonal towards the castled king
+        let battery =
+            Position::from_fen("r4rk1/ppp2ppp/8/8/8/3B4/PP3PPP/1Q3RK1 w - - 0 1").unwrap();
+        assert_eq!(
+            coordination_terms(&battery.board, Color::White).batteries,
+            1
+        );
+
+        // The same pair pointing at the queenside is no battery
+        let away = Position::from_fen("r4rk1/ppp2ppp/8/8/8/1B6/PP3PPP/3Q1RK1 w - - 0 1").unwrap();
+        assert_eq!(coordination_terms(&away.board, Color::White).batteries, 0);
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        let params = EvalParams::default();
+        assert_eq!(evaluate(&Position::new().board, &params), 0);
+    }
+}
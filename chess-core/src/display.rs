@@ -0,0 +1,202 @@
+//! Text diagrams of a board, for the `d` UCI command and terminal front ends
+
+use std::fmt;
+
+use crate::{
+    board::{Board, Color, Piece, Square},
+    position::Position,
+};
+
+/// ANSI backgrounds of the light and dark squares
+const LIGHT_SQUARE: &str = "\x1b[48;5;180m";
+const DARK_SQUARE: &str = "\x1b[48;5;137m";
+/// ANSI foregrounds of the white and black pieces
+const WHITE_PIECE: &str = "\x1b[1;97m";
+const BLACK_PIECE: &str = "\x1b[1;30m";
+const RESET: &str = "\x1b[0m";
+
+/// A board diagram with rank and file coordinates. Positions add their FEN and
+/// Zobrist key below the board.
+#[derive(Debug, Clone, Copy)]
+pub struct BoardDisplay<'a> {
+    board: &'a Board,
+    position: Option<&'a Position>,
+    unicode: bool,
+    colored: bool,
+    flipped: bool,
+}
+
+impl<'a> BoardDisplay<'a> {
+    fn new(board: &'a Board, position: Option<&'a Position>) -> Self {
+        Self {
+            board,
+            position,
+            unicode: false,
+            colored: false,
+            flipped: false,
+        }
+    }
+
+    /// Draw pieces as chess symbols rather than FEN letters
+    pub fn unicode(mut self, unicode: bool) -> Self {
+        self.unicode = unicode;
+        self
+    }
+
+    /// Shade the squares and pieces with ANSI colours
+    pub fn colored(mut self, colored: bool) -> Self {
+        self.colored = colored;
+        self
+    }
+
+    /// Draw the board from Black's side, with h1 at the top left
+    pub fn flipped(mut self, flipped: bool) -> Self {
+        self.flipped = flipped;
+        self
+    }
+
+    fn symbol(&self, piece: Piece) -> char {
+        if !self.unicode {
+            return piece.to_char();
+        }
+        let symbols = match piece.color {
+            Color::White => ['♙', '♘', '♗', '♖', '♕', '♔'],
+            Color::Black => ['♟', '♞', '♝', '♜', '♛', '♚'],
+        };
+        symbols[piece.piece_type as usize]
+    }
+
+    fn write_square(&self, f: &mut fmt::Formatter<'_>, square: Square) -> fmt::Result {
+        let piece = self.board.piece_at(square);
+        if !self.colored {
+            let symbol = piece.map_or('.', |piece| self.symbol(piece));
+            return write!(f, " {symbol}");
+        }
+
+        let light = (square.file() + square.rank()) % 2 == 1;
+        let background = if light { LIGHT_SQUARE } else { DARK_SQUARE };
+        match piece {
+            Some(piece) => {
+                let foreground = match piece.color {
+                    Color::White => WHITE_PIECE,
+                    Color::Black => BLACK_PIECE,
+                };
+                let symbol = self.symbol(piece);
+                write!(f, "{background}{foreground} {symbol} {RESET}")
+            }
+            None => write!(f, "{background}   {RESET}"),
+        }
+    }
+}
+
+impl fmt::Display for BoardDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranks: Vec<u8> = if self.flipped {
+            (0..8).collect()
+        } else {
+            (0..8).rev().collect()
+        };
+        let files: Vec<u8> = if self.flipped {
+            (0..8).rev().collect()
+        } else {
+            (0..8).collect()
+        };
+
+        for &rank in &ranks {
+            write!(f, "{}", rank + 1)?;
+            for &file in &files {
+                self.write_square(f, Square::new(file, rank))?;
+            }
+            writeln!(f)?;
+        }
+        let gap = if self.colored { "  " } else { " " };
+        write!(f, " ")?;
+        for &file in &files {
+            write!(f, "{gap}{}", (b'a' + file) as char)?;
+            if self.colored {
+                write!(f, " ")?;
+            }
+        }
+        writeln!(f)?;
+
+        if let Some(position) = self.position {
+            writeln!(f)?;
+            writeln!(f, "Fen: {}", position.to_fen())?;
+            writeln!(f, "Key: {:016X}", position.key())?;
+        }
+        Ok(())
+    }
+}
+
+impl Board {
+    /// A diagram of the board, to be adjusted with the `BoardDisplay` options
+    pub fn display(&self) -> BoardDisplay<'_> {
+        BoardDisplay::new(self, None)
+    }
+}
+
+impl Position {
+    /// A diagram of the board followed by the FEN and key
+    pub fn display(&self) -> BoardDisplay<'_> {
+        BoardDisplay::new(&self.board, Some(self))
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display().fmt(f)
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_diagram() {
+        let board = Board::starting_position();
+        let expected = "\
+8 r n b q k b n r
+7 p p p p p p p p
+6 . . . . . . . .
+5 . . . . . . . .
+4 . . . . . . . .
+3 . . . . . . . .
+2 P P P P P P P P
+1 R N B Q K B N R
+  a b c d e f g h
+";
+        assert_eq!(board.to_string(), expected);
+
+        let flipped = board.display().flipped(true).unicode(true).to_string();
+        let lines: Vec<&str> = flipped.lines().collect();
+        assert_eq!(lines[0], "1 ♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖");
+        assert_eq!(lines[7], "8 ♜ ♞ ♝ ♚ ♛ ♝ ♞ ♜");
+        assert_eq!(lines[8], "  h g f e d c b a");
+    }
+
+    #[test]
+    fn test_position_diagram() {
+        let position = Position::new();
+        let text = position.to_string();
+        assert!(text.starts_with(&position.board.to_string()));
+        assert!(text.contains(&format!("Fen: {}\n", position.to_fen())));
+        assert!(text.contains(&format!("Key: {:016X}\n", position.key())));
+
+        let colored = position.display().colored(true).to_string();
+        assert!(colored.contains(RESET));
+        assert!(colored.lines().nth(8).unwrap().ends_with("h "));
+        assert!(!position
+            .board
+            .display()
+            .colored(true)
+            .to_string()
+            .contains("Fen"));
+    }
+}
@@ -0,0 +1,474 @@
+//! Specialised evaluation of endings the generic terms play poorly, dispatched by
+//! the material on the board
+
+use crate::{
+    bitboard::{DARK_SQUARES, FILE_A, FILE_B, FILE_C, FILE_D, LIGHT_SQUARES},
+    board::{Board, Color, Piece, PieceType, Square},
+    evaluate, pawns,
+};
+
+/// Base score of an ending the strong side wins with correct technique
+pub const KNOWN_WIN: i32 = 10000;
+
+/// Bonus per step the losing king is pushed towards the edge or corner
+const PUSH_TO_EDGE: i32 = 20;
+
+/// Bonus per step the winning king is brought closer to the losing king
+const PUSH_CLOSE: i32 = 10;
+
+/// Material lead, without pawns, from which the mop-up term applies
+const MOP_UP_MARGIN: i32 = 400;
+
+/// Scale factor that leaves an evaluation unchanged
+pub const SCALE_NORMAL: i32 = 64;
+
+/// Scale of a bishop ending with opposite-coloured bishops and at most one passer
+const OCB_DRAWISH_SCALE: i32 = 16;
+
+/// Scale of a bishop ending with opposite-coloured bishops and several passers
+const OCB_SCALE: i32 = 32;
+
+/// Scale of opposite-coloured bishops with other pieces still on the board
+const OCB_WITH_PIECES_SCALE: i32 = 48;
+
+/// Scale when the strong side has no pawns and is at most a minor piece ahead, as
+/// in rook and minor against rook
+const NO_PAWNS_SCALE: i32 = 12;
+
+/// Scale of a wrong bishop with rook pawns whose corner the defending king holds,
+/// when the defender still has pawns of its own
+const WRONG_BISHOP_SCALE: i32 = 8;
+
+/// Scale when the pieces are level, every pawn is on one wing and the strong side
+/// has at most one extra
+const ONE_WING_SCALE: i32 = 48;
+
+/// Number of pieces of each type, pawn to queen, owned by one side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialSignature([u32; 5]);
+
+impl MaterialSignature {
+    pub fn of(board: &Board, color: Color) -> Self {
+        let counts = board.piece_counts(color);
+        Self([counts[0], counts[1], counts[2], counts[3], counts[4]])
+    }
+
+    pub fn count(&self, piece_type: PieceType) -> u32 {
+        self.0[piece_type as usize]
+    }
+
+    /// Only the king is left
+    pub fn is_bare(&self) -> bool {
+        self.0 == [0; 5]
+    }
+
+    fn minors(&self) -> u32 {
+        self.count(PieceType::Knight) + self.count(PieceType::Bishop)
+    }
+
+    fn majors(&self) -> u32 {
+        self.count(PieceType::Rook) + self.count(PieceType::Queen)
+    }
+
+    fn non_pawn_value(&self) -> i32 {
+        self.value() - self.count(PieceType::Pawn) as i32 * evaluate::PIECE_VALUES[0]
+    }
+
+    fn value(&self) -> i32 {
+        (0..5)
+            .map(|index| self.0[index] as i32 * evaluate::PIECE_VALUES[index])
+            .sum()
+    }
+}
+
+fn king_square(board: &Board, color: Color) -> Square {
+    Square(
+        board
+            .piece_bitboard(color, PieceType::King)
+            .lsb()
+            .unwrap_or(0),
+    )
+}
+
+/// Steps from the centre, 0 on the central squares and 6 in the corners
+pub(crate) fn centre_distance(square: Square) -> i32 {
+    let file = square.file() as i32;
+    let rank = square.rank() as i32;
+    (3 - file).max(file - 4) + (3 - rank).max(rank - 4)
+}
+
+/// Bonus for having the kings close together
+fn closeness(strong_king: Square, weak_king: Square) -> i32 {
+    PUSH_CLOSE * (7 - strong_king.distance(weak_king) as i32)
+}
+
+/// Piece counts of both sides, pawn to queen, packed four bits each with White's
+/// first. `Position` keeps one up to date as pieces move, so the material can be
+/// compared or used as a table key without counting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MaterialKey(pub u64);
+
+impl MaterialKey {
+    pub fn of(board: &Board) -> Self {
+        let mut key = Self::default();
+        for (_, piece) in board.pieces() {
+            key.add(piece);
+        }
+        key
+    }
+
+    fn shift(color: Color, piece_type: PieceType) -> u32 {
+        (color as u32 * 5 + piece_type as u32) * 4
+    }
+
+    /// Count one more `piece`; kings are not counted
+    pub fn add(&mut self, piece: Piece) {
+        if piece.piece_type != PieceType::King {
+            self.0 += 1 << Self::shift(piece.color, piece.piece_type);
+        }
+    }
+
+    /// Count one fewer `piece`
+    pub fn remove(&mut self, piece: Piece) {
+        if piece.piece_type != PieceType::King {
+            self.0 -= 1 << Self::shift(piece.color, piece.piece_type);
+        }
+    }
+
+    pub fn count(self, color: Color, piece_type: PieceType) -> u32 {
+        if piece_type == PieceType::King {
+            return 1;
+        }
+        ((self.0 >> Self::shift(color, piece_type)) & 0xF) as u32
+    }
+
+    /// The pieces of one side
+    pub fn signature(self, color: Color) -> MaterialSignature {
+        MaterialSignature(std::array::from_fn(|index| {
+            self.count(color, PieceType::ALL[index])
+        }))
+    }
+}
+
+/// Score of the ending from White's point of view, if it is one with special knowledge
+pub fn probe(board: &Board) -> Option<i32> {
+    probe_with(board, MaterialKey::of(board))
+}
+
+/// Like `probe`, with the material taken from `material` rather than counted
+pub fn probe_with(board: &Board, material: MaterialKey) -> Option<i32> {
+    let white = material.signature(Color::White);
+    let black = material.signature(Color::Black);
+
+    // Neither side can mate with at most one minor piece
+    let no_pawns = white.count(PieceType::Pawn) + black.count(PieceType::Pawn) == 0;
+    if no_pawns
+        && white.majors() + black.majors() == 0
+        && white.minors() <= 1
+        && black.minors() <= 1
+    {
+        return Some(0);
+    }
+
+    for strong in [Color::White, Color::Black] {
+        let (ours, theirs) = match strong {
+            Color::White => (white, black),
+            Color::Black => (black, white),
+        };
+        if !theirs.is_bare() {
+            continue;
+        }
+
+        let score = if ours.count(PieceType::Pawn) == 0 {
+            mate_with_pieces(board, strong, ours)
+        } else {
+            wrong_bishop(board, strong, ours)
+        };
+        if let Some(score) = score {
+            return Some(match strong {
+                Color::White => score,
+                Color::Black => -score,
+            });
+        }
+    }
+    None
+}
+
+/// Pawnless material against a bare king, scored for the strong side
+fn mate_with_pieces(board: &Board, strong: Color, material: MaterialSignature) -> Option<i32> {
+    let strong_king = king_square(board, strong);
+    let weak_king = king_square(board, strong.opposite());
+    let knights = material.count(PieceType::Knight);
+    let bishops = material.count(PieceType::Bishop);
+
+    if material.majors() == 0 && knights == 1 && bishops == 1 {
+        return Some(bishop_and_knight(board, strong, strong_king, weak_king));
+    }
+
+    // Two knights cannot force mate
+    if material.majors() == 0 && bishops == 0 {
+        return Some(0);
+    }
+
+    Some(
+        KNOWN_WIN
+            + material.value()
+            + PUSH_TO_EDGE * centre_distance(weak_king)
+            + closeness(strong_king, weak_king),
+    )
+}
+
+/// KBNK: mate is only possible in a corner of the bishop's colour, so drive the king there
+fn bishop_and_knight(board: &Board, strong: Color, strong_king: Square, weak_king: Square) -> i32 {
+    let bishop = board.piece_bitboard(strong, PieceType::Bishop);
+    let corners = if (bishop & DARK_SQUARES).is_empty() {
+        [Square::new(7, 0), Square::new(0, 7)]
+    } else {
+        [Square::new(0, 0), Square::new(7, 7)]
+    };
+    let corner_distance = corners
+        .iter()
+        .map(|&corner| weak_king.distance(corner) as i32)
+        .min()
+        .unwrap_or(0);
+
+    KNOWN_WIN
+        + evaluate::PIECE_VALUES[PieceType::Knight as usize]
+        + evaluate::PIECE_VALUES[PieceType::Bishop as usize]
+        + PUSH_TO_EDGE * (7 - corner_distance)
+        + closeness(strong_king, weak_king)
+}
+
+/// Bishop and rook pawns whose promotion square the bishop does not control are a
+/// draw once the defending king reaches the corner
+fn wrong_bishop(board: &Board, strong: Color, material: MaterialSignature) -> Option<i32> {
+    if material.count(PieceType::Bishop) != 1 || material.minors() + material.majors() != 1 {
+        return None;
+    }
+
+    let pawns = board.piece_bitboard(strong, PieceType::Pawn);
+    let file = pawns.lsb()? % 8;
+    if (file != 0 && file != 7) || pawns.squares().any(|square| square % 8 != file) {
+        return None;
+    }
+
+    let promotion = match strong {
+        Color::White => Square::new(file, 7),
+        Color::Black => Square::new(file, 0),
+    };
+    let promotion_colour = if DARK_SQUARES.has_square(promotion.0) {
+        DARK_SQUARES
+    } else {
+        LIGHT_SQUARES
+    };
+    let bishop = board.piece_bitboard(strong, PieceType::Bishop);
+    let weak_king = king_square(board, strong.opposite());
+
+    ((bishop & promotion_colour).is_empty() && weak_king.distance(promotion) <= 1).then_some(0)
+}
+
+/// Bonus for `color` when it is far ahead against a side without pawns, for
+/// pushing the enemy king to the edge and approaching it with its own
+pub(crate) fn mop_up(board: &Board, color: Color) -> i32 {
+    let ours = MaterialSignature::of(board, color);
+    let theirs = MaterialSignature::of(board, color.opposite());
+    if theirs.count(PieceType::Pawn) > 0 || ours.value() - theirs.value() < MOP_UP_MARGIN {
+        return 0;
+    }
+
+    let our_king = king_square(board, color);
+    let their_king = king_square(board, color.opposite());
+    PUSH_TO_EDGE * centre_distance(their_king) + closeness(our_king, their_king)
+}
+
+/// Mop-up score from White's point of view
+pub fn evaluate_mop_up(board: &Board) -> i32 {
+    mop_up(board, Color::White) - mop_up(board, Color::Black)
+}
+
+/// Whether each side has exactly one bishop and they travel on different colours
+pub fn opposite_coloured_bishops(board: &Board) -> bool {
+    let white = board.piece_bitboard(Color::White, PieceType::Bishop);
+    let black = board.piece_bitboard(Color::Black, PieceType::Bishop);
+    if white.count() != 1 || black.count() != 1 {
+        return false;
+    }
+    let white_on_light = !(white & LIGHT_SQUARES).is_empty();
+    let black_on_light = !(black & LIGHT_SQUARES).is_empty();
+    white_on_light != black_on_light
+}
+
+/// Whether every pawn on the board stands on the queenside or every pawn on the kingside
+fn pawns_on_one_wing(board: &Board) -> bool {
+    let pawns = board.piece_bitboard(Color::White, PieceType::Pawn)
+        | board.piece_bitboard(Color::Black, PieceType::Pawn);
+    let queenside = FILE_A | FILE_B | FILE_C | FILE_D;
+    !pawns.is_empty() && ((pawns & queenside).is_empty() || (pawns & !queenside).is_empty())
+}
+
+/// How much of an evaluation favouring `strong` to keep, out of `SCALE_NORMAL`
+pub fn scale_factor(board: &Board, strong: Color) -> i32 {
+    let ours = MaterialSignature::of(board, strong);
+    let theirs = MaterialSignature::of(board, strong.opposite());
+
+    if wrong_bishop(board, strong, ours).is_some() {
+        return WRONG_BISHOP_SCALE;
+    }
+
+    let minor = evaluate::PIECE_VALUES[PieceType::Bishop as usize];
+    if ours.count(PieceType::Pawn) == 0 && ours.non_pawn_value() - theirs.non_pawn_value() <= minor
+    {
+        return NO_PAWNS_SCALE;
+    }
+
+    if opposite_coloured_bishops(board) {
+        let only_bishops =
+            ours.majors() + theirs.majors() == 0 && ours.minors() + theirs.minors() == 2;
+        if !only_bishops {
+            return OCB_WITH_PIECES_SCALE;
+        }
+
+        // One passer is usually blockaded by the defending bishop on its own colour
+        return if pawns::passed_pawns(board, strong).count() <= 1 {
+            OCB_DRAWISH_SCALE
+        } else {
+            OCB_SCALE
+        };
+    }
+
+    if pawns_on_one_wing(board)
+        && ours.non_pawn_value() == theirs.non_pawn_value()
+        && ours.count(PieceType::Pawn) <= theirs.count(PieceType::Pawn) + 1
+    {
+        return ONE_WING_SCALE;
+    }
+
+    SCALE_NORMAL
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    fn probe_fen(fen: &str) -> Option<i32> {
+        probe(&Position::from_fen(fen).unwrap().board)
+    }
+
+    #[test]
+    fn test_drawn_material() {
+        assert_eq!(probe_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), Some(0));
+        assert_eq!(probe_fen("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"), Some(0));
+        assert_eq!(probe_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1"), Some(0));
+        assert_eq!(probe_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn test_rook_mate_drives_king_to_edge() {
+        let centre = probe_fen("8/8/8/3k4/8/8/8/R3K3 w - - 0 1").unwrap();
+        let edge = probe_fen("3k4/8/3K4/8/8/8/8/R7 w - - 0 1").unwrap();
+        assert!(centre > KNOWN_WIN);
+        assert!(edge > centre);
+
+        // Black's rook makes the score negative
+        assert!(probe_fen("r3k3/8/8/8/3K4/8/8/8 w - - 0 1").unwrap() < -KNOWN_WIN);
+    }
+
+    #[test]
+    fn test_bishop_and_knight_prefer_bishop_corner() {
+        // The dark-squared bishop mates on a1 or h8, not on h1
+        let right = probe_fen("8/8/8/8/8/1K6/8/k1B1N3 w - - 0 1").unwrap();
+        let wrong = probe_fen("8/8/8/8/8/6K1/8/2B1N2k w - - 0 1").unwrap();
+        assert!(right > wrong);
+    }
+
+    #[test]
+    fn test_wrong_bishop_rook_pawn() {
+        // Light-squared bishop cannot control h8 and the king holds the corner
+        assert_eq!(probe_fen("7k/8/8/7P/8/8/8/4KB2 w - - 0 1"), Some(0));
+        // A dark-squared bishop wins
+        assert_eq!(probe_fen("7k/8/8/7P/8/8/8/2B1K3 w - - 0 1"), None);
+        // So does the same bishop when the king is far away
+        assert_eq!(probe_fen("8/8/8/7P/8/8/k7/4KB2 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn test_opposite_coloured_bishops_scale() {
+        // White's extra pawn in a pure opposite-coloured bishop ending
+        let ocb = Position::from_fen("4k3/5p2/3b4/8/8/3B4/P4P2/4K3 w - - 0 1").unwrap();
+        assert!(opposite_coloured_bishops(&ocb.board));
+        assert_eq!(scale_factor(&ocb.board, Color::White), OCB_DRAWISH_SCALE);
+
+        // Bishops on the same colour are not scaled
+        let same = Position::from_fen("4k3/5p2/2b5/8/8/3B4/P4P2/4K3 w - - 0 1").unwrap();
+        assert!(!opposite_coloured_bishops(&same.board));
+        assert_eq!(scale_factor(&same.board, Color::White), SCALE_NORMAL);
+
+        // Rooks on the board keep more winning chances
+        let rooks = Position::from_fen("r3k3/5p2/3b4/8/8/3B4/P4P2/R3K3 w - - 0 1").unwrap();
+        assert_eq!(
+            scale_factor(&rooks.board, Color::White),
+            OCB_WITH_PIECES_SCALE
+        );
+    }
+
+    #[test]
+    fn test_mop_up_against_lone_piece() {
+        // Queen against knight is not a bare king ending, but still drives the king
+        let centre = Position::from_fen("8/8/8/3kn3/8/8/8/3QK3 w - - 0 1").unwrap();
+        let edge = Position::from_fen("3kn3/8/3K4/8/8/8/8/3Q4 w - - 0 1").unwrap();
+        assert_eq!(probe(&centre.board), None);
+        assert!(evaluate_mop_up(&edge.board) > evaluate_mop_up(&centre.board));
+        assert_eq!(mop_up(&edge.board, Color::Black), 0);
+
+        // No mop-up while the defender still has pawns
+        let pawns = Position::from_fen("3kn3/3p4/3K4/8/8/8/8/3Q4 w - - 0 1").unwrap();
+        assert_eq!(evaluate_mop_up(&pawns.board), 0);
+    }
+
+    #[test]
+    fn test_drawish_material_scales() {
+        // Rook and bishop against rook, without pawns
+        let rook_minor = Position::from_fen("4k3/8/8/8/8/8/3r4/2B1KR2 w - - 0 1").unwrap();
+        assert_eq!(
+            scale_factor(&rook_minor.board, Color::White),
+            NO_PAWNS_SCALE
+        );
+
+        // Wrong bishop and h-pawn, with a black pawn still on the board
+        let wrong = Position::from_fen("7k/8/8/7P/8/p7/8/4KB2 w - - 0 1").unwrap();
+        assert_eq!(probe(&wrong.board), None);
+        assert_eq!(scale_factor(&wrong.board, Color::White), WRONG_BISHOP_SCALE);
+
+        // Four rook pawns against three on the kingside
+        let one_wing = Position::from_fen("6k1/5pp1/7p/8/8/6PP/r4P1P/3R2K1 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&one_wing.board, Color::White), ONE_WING_SCALE);
+
+        // A queenside pawn as well keeps the winning chances
+        let both_wings = Position::from_fen("6k1/5pp1/7p/8/8/P5P1/r4P1P/3R2K1 w - - 0 1").unwrap();
+        assert_eq!(scale_factor(&both_wings.board, Color::White), SCALE_NORMAL);
+    }
+
+    #[test]
+    fn test_material_key() {
+        let board = Position::from_fen("4k3/pp6/8/8/8/8/5PPP/1N1QK2R w K - 0 1")
+            .unwrap()
+            .board;
+        let key = MaterialKey::of(&board);
+        assert_eq!(key.count(Color::White, PieceType::Pawn), 3);
+        assert_eq!(key.count(Color::White, PieceType::Rook), 1);
+        assert_eq!(key.count(Color::Black, PieceType::Pawn), 2);
+        assert_eq!(key.count(Color::Black, PieceType::Queen), 0);
+        assert_eq!(
+            key.signature(Color::White),
+            MaterialSignature::of(&board, Color::White)
+        );
+
+        let mut changed = key;
+        let knight = Piece::new(Color::White, PieceType::Knight);
+        changed.remove(knight);
+        assert_ne!(changed, key);
+        changed.add(knight);
+        assert_eq!(changed, key);
+    }
+}
@@ -0,0 +1,270 @@
+//! Extended Position Description: a position's first four FEN fields followed by
+//! opcodes such as `bm Nf3;`, `id "WAC.001";` or `ce 35;`, as used by test suites
+//! and tuning datasets
+
+use crate::{moves::Move, position::Position, Error, Result};
+
+/// One EPD record: a position and its operations in the order they were written
+#[derive(Debug, Clone)]
+pub struct Epd {
+    pub position: Position,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    /// A record for `position` without operations
+    pub fn new(position: Position) -> Self {
+        Self {
+            position,
+            operations: Vec::new(),
+        }
+    }
+
+    /// Parse one EPD line. The `hmvc` and `fmvn` opcodes set the move counters.
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let mut fields = Vec::new();
+        let mut rest = line;
+        for _ in 0..4 {
+            rest = rest.trim_start();
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(Error::InvalidEpd(format!("Not enough fields: {line}")));
+            }
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        let position = Position::from_fen(&fields.join(" "))?;
+        let operations = parse_operations(rest)
+            .ok_or_else(|| Error::InvalidEpd(format!("Malformed operations: {line}")))?;
+
+        let mut epd = Self {
+            position,
+            operations,
+        };
+        if let Some(clock) = epd.number("hmvc") {
+            epd.position.halfmove_clock = clock;
+        }
+        if let Some(number) = epd.number("fmvn") {
+            epd.position.fullmove_number = number;
+        }
+        Ok(epd)
+    }
+
+    /// Parse every non-empty line of `text`
+    pub fn parse_all(text: &str) -> Result<Vec<Self>> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    /// Operands of `opcode`, if present
+    pub fn get(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// Set the operands of `opcode`, replacing any earlier ones
+    pub fn set(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(name, _)| name == opcode) {
+            Some((_, old)) => *old = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    /// Remove `opcode`, returning its operands
+    pub fn remove(&mut self, opcode: &str) -> Option<Vec<String>> {
+        let index = self
+            .operations
+            .iter()
+            .position(|(name, _)| name == opcode)?;
+        Some(self.operations.remove(index).1)
+    }
+
+    /// The `id` operand, naming the record
+    pub fn id(&self) -> Option<&str> {
+        self.get("id")?.first().map(String::as_str)
+    }
+
+    /// The `ce` operand: centipawns for the side to move
+    pub fn centipawn_eval(&self) -> Option<i32> {
+        self.number("ce")
+    }
+
+    /// Moves of the `bm` opcode, resolved from SAN against the legal moves
+    pub fn best_moves(&self) -> Result<Vec<Move>> {
+        self.moves("bm")
+    }
+
+    /// Moves of the `am` opcode, resolved from SAN against the legal moves
+    pub fn avoid_moves(&self) -> Result<Vec<Move>> {
+        self.moves("am")
+    }
+
+    /// Operands of `opcode` read as SAN moves; empty when the opcode is absent
+    pub fn moves(&self, opcode: &str) -> Result<Vec<Move>> {
+        self.get(opcode)
+            .unwrap_or_default()
+            .iter()
+            .map(|san| self.position.san_to_move(san))
+            .collect()
+    }
+
+    fn number<T: std::str::FromStr>(&self, opcode: &str) -> Option<T> {
+        self.get(opcode)?.first()?.parse().ok()
+    }
+
+    /// The EPD line: four FEN fields followed by each operation
+    pub fn to_epd(&self) -> String {
+        let fen = self.position.to_fen();
+        let mut epd: Vec<&str> = fen.split_whitespace().take(4).collect();
+        let operations: Vec<String> = self
+            .operations
+            .iter()
+            .map(|(opcode, operands)| {
+                let mut operation = opcode.clone();
+                for operand in operands {
+                    operation.push(' ');
+                    operation.push_str(&quote(opcode, operand));
+                }
+                operation.push(';');
+                operation
+            })
+            .collect();
+        epd.extend(operations.iter().map(String::as_str));
+        epd.join(" ")
+    }
+}
+
+impl std::fmt::Display for Epd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_epd())
+    }
+}
+
+impl std::str::FromStr for Epd {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+/// Opcodes whose operands are strings, written in quotes
+fn is_string_opcode(opcode: &str) -> bool {
+    matches!(
+        opcode,
+        "id" | "c0" | "c1" | "c2" | "c3" | "c4" | "c5" | "c6" | "c7" | "c8" | "c9"
+    )
+}
+
+fn quote(opcode: &str, operand: &str) -> String {
+    let plain = !operand.is_empty()
+        && !operand.contains(|c: char| c.is_whitespace() || c == ';' || c == '"');
+    if plain && !is_string_opcode(opcode) {
+        operand.to_string()
+    } else {
+        format!("\"{}\"", operand.replace('"', "'"))
+    }
+}
+
+/// Opcodes with their operands, each operation ending in `;`; quoted operands may
+/// hold spaces and semicolons
+fn parse_operations(text: &str) -> Option<Vec<(String, Vec<String>)>> {
+    let mut operations = Vec::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Some(operations);
+        }
+
+        let mut opcode = String::new();
+        while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != ';') {
+            opcode.push(c);
+        }
+        if opcode.is_empty() {
+            return None;
+        }
+
+        let mut operands = Vec::new();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.next()? {
+                ';' => break,
+                '"' => {
+                    let mut operand = String::new();
+                    loop {
+                        match chars.next()? {
+                            '"' => break,
+                            c => operand.push(c),
+                        }
+                    }
+                    operands.push(operand);
+                }
+                c => {
+                    let mut operand = c.to_string();
+                    while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != ';') {
+                        operand.push(c);
+                    }
+                    operands.push(operand);
+                }
+            }
+        }
+        operations.push((opcode, operands));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAC_1: &str =
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+
+    #[test]
+    fn test_parse_operations() {
+        let epd = Epd::parse(WAC_1).unwrap();
+        assert_eq!(epd.id(), Some("WAC.001"));
+        let best = epd.best_moves().unwrap();
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[0].to_algebraic(), "g3g6");
+        assert!(epd.avoid_moves().unwrap().is_empty());
+
+        let line = "4k3/8/8/8/8/8/8/4K2R w K - am Kf2 Kd2; ce -15; c0 \"a; b\"; hmvc 12;";
+        let epd = Epd::parse(line).unwrap();
+        assert_eq!(epd.avoid_moves().unwrap().len(), 2);
+        assert_eq!(epd.centipawn_eval(), Some(-15));
+        assert_eq!(epd.get("c0").unwrap(), ["a; b"]);
+        assert_eq!(epd.position.halfmove_clock, 12);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let epd = Epd::parse(WAC_1).unwrap();
+        assert_eq!(epd.to_epd(), WAC_1);
+
+        let mut epd = Epd::new(Position::new());
+        epd.set("bm", vec!["e4".to_string(), "d4".to_string()]);
+        epd.set("c0", vec!["main line".to_string()]);
+        let line = epd.to_string();
+        assert_eq!(
+            line,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4 d4; c0 \"main line\";"
+        );
+        assert_eq!(Epd::parse(&line).unwrap().operations, epd.operations);
+    }
+
+    #[test]
+    fn test_invalid_epd() {
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w -").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - bm Kd2").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"open;").is_err());
+        let epd = Epd::parse("4k3/8/8/8/8/8/8/4K3 w - - bm Qd2;").unwrap();
+        assert!(epd.best_moves().is_err());
+    }
+}
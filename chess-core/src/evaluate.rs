@@ -1,12 +1,18 @@
 
 
 use crate::{
-    board::{Board, Color, PieceType},
+    bitboard::{DARK_SQUARES, LIGHT_SQUARES},
+    board::{Board, Color, Piece, PieceType},
+    coordination, endgame, imbalance, initiative, king_activity, king_safety, majority,
+    params::EvalParams,
+    pawns::{self, PawnTable},
+    pieces,
     position::Position,
+    space, threats, weak_squares,
 };
 
 
-const PIECE_VALUES: [i32; 6] = [
+pub(crate) const PIECE_VALUES: [i32; 6] = [
     100,   
     300,   
     300,   
@@ -16,42 +22,42 @@ const PIECE_VALUES: [i32; 6] = [
 ];
 
 
-const PAWN_TABLE: [i32; 64] = [
+pub(crate) const PAWN_TABLE: [i32; 64] = [
     0, 0, 0, 0, 0, 0, 0, 0, 50, 50, 50, 50, 50, 50, 50, 50, 10, 10, 20, 30, 30, 20, 10, 10, 5, 5,
     10, 25, 25, 10, 5, 5, 0, 0, 0, 20, 20, 0, 0, 0, 5, -5, -10, 0, 0, -10, -5, 5, 5, 10, 10, -20,
     -20, 10, 10, 5, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
 
 
-const KNIGHT_TABLE: [i32; 64] = [
+pub(crate) const KNIGHT_TABLE: [i32; 64] = [
     -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, 0, 0, 0, 0, -20, -40, -30, 0, 10, 15, 15, 10,
     0, -30, -30, 5, 15, 20, 20, 15, 5, -30, -30, 0, 15, 20, 20, 15, 0, -30, -30, 5, 10, 15, 15, 10,
     5, -30, -40, -20, 0, 5, 5, 0, -20, -40, -50, -40, -30, -30, -30, -30, -40, -50,
 ];
 
 
-const BISHOP_TABLE: [i32; 64] = [
+pub(crate) const BISHOP_TABLE: [i32; 64] = [
     -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 10, 10, 5, 0,
     -10, -10, 5, 5, 10, 10, 5, 5, -10, -10, 0, 10, 10, 10, 10, 0, -10, -10, 10, 10, 10, 10, 10, 10,
     -10, -10, 5, 0, 0, 0, 0, 5, -10, -20, -10, -10, -10, -10, -10, -10, -20,
 ];
 
 
-const ROOK_TABLE: [i32; 64] = [
+pub(crate) const ROOK_TABLE: [i32; 64] = [
     0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, 10, 10, 10, 10, 5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0,
     0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, 0, 0,
     0, 5, 5, 0, 0, 0,
 ];
 
 
-const QUEEN_TABLE: [i32; 64] = [
+pub(crate) const QUEEN_TABLE: [i32; 64] = [
     -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
     -5, 0, 5, 5, 5, 5, 0, -5, 0, 0, 5, 5, 5, 5, 0, -5, -10, 5, 5, 5, 5, 5, 0, -10, -10, 0, 5, 0, 0,
     0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
 ];
 
 
-const KING_TABLE: [i32; 64] = [
+pub(crate) const KING_TABLE: [i32; 64] = [
     -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40,
     -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -20, -30, -30, -40, -40, -30,
     -30, -20, -10, -20, -20, -20, -20, -20, -20, -10, 20, 20, 0, 0, 0, 0, 20, 20, 20, 30, 10, 0, 0,
@@ -59,14 +65,334 @@ const KING_TABLE: [i32; 64] = [
 ];
 
 
+/// Game phase with all minor and major pieces on the board
+pub const MAX_PHASE: i32 = 24;
+
+/// Phase weight of each piece type, pawn to king
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+
+/// How far the game is from the endgame, from `MAX_PHASE` at the start down to 0
+pub fn game_phase(board: &Board) -> i32 {
+    let mut phase = 0;
+    for piece_type in [
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+    ] {
+        let count = board.piece_bitboard(Color::White, piece_type).count()
+            + board.piece_bitboard(Color::Black, piece_type).count();
+        phase += count as i32 * PHASE_WEIGHTS[piece_type as usize];
+    }
+    phase.min(MAX_PHASE)
+}
+
+/// Coefficients of the score at which a win becomes as likely as not, as a cubic in
+/// the game phase scaled to 0..=1; with more material left an edge is harder to keep
+const WDL_MIDPOINT: [f64; 4] = [110.0, 60.0, 70.0, -20.0];
+
+/// Coefficients of the spread of the win probability curve, cubic in the scaled phase
+const WDL_SPREAD: [f64; 4] = [55.0, 10.0, 20.0, -5.0];
+
+/// Win, draw and loss probabilities in per mille, for the side the score belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wdl {
+    pub win: u32,
+    pub draw: u32,
+    pub loss: u32,
+}
+
+fn cubic(coefficients: &[f64; 4], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, &c| acc * x + c)
+}
+
+/// Win probability in per mille of a centipawn `score` at `phase`
+fn win_rate(score: i32, phase: i32) -> f64 {
+    let x = phase.clamp(0, MAX_PHASE) as f64 / MAX_PHASE as f64;
+    let midpoint = cubic(&WDL_MIDPOINT, x);
+    let spread = cubic(&WDL_SPREAD, x);
+    1000.0 / (1.0 + ((midpoint - score as f64) / spread).exp())
+}
+
+/// Expected outcome of a centipawn `score` with `phase` material on the board
+pub fn wdl(score: i32, phase: i32) -> Wdl {
+    let win = win_rate(score, phase).round() as u32;
+    let loss = win_rate(-score, phase).round() as u32;
+    Wdl {
+        win,
+        draw: 1000 - win - loss,
+        loss,
+    }
+}
+
+/// Middlegame and endgame values of a term, blended by the game phase
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
+
+    /// Interpolate between the endgame and middlegame values
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl std::ops::Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl std::ops::AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl std::ops::Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl std::ops::Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, factor: i32) -> Score {
+        Score::new(self.mg * factor, self.eg * factor)
+    }
+}
+
+/// Small direct-mapped cache of static evaluations keyed by position hash
+pub struct EvalCache {
+    entries: Vec<EvalCacheEntry>,
+    mask: usize,
+    /// Pawn structure scores, which survive many evaluation misses
+    pawns: PawnTable,
+}
+
+#[derive(Clone, Copy, Default)]
+struct EvalCacheEntry {
+    key: u64,
+    score: i32,
+}
+
+impl EvalCache {
+    /// Default number of cached evaluations
+    pub const DEFAULT_ENTRIES: usize = 1 << 16;
+
+    /// Create a cache with `entries` slots, rounded up to a power of two
+    pub fn new(entries: usize) -> Self {
+        let entries = entries.max(1).next_power_of_two();
+        Self {
+            entries: vec![EvalCacheEntry::default(); entries],
+            mask: entries - 1,
+            pawns: PawnTable::default(),
+        }
+    }
+
+    pub fn probe(&self, key: u64) -> Option<i32> {
+        let entry = self.entries[key as usize & self.mask];
+        (entry.key == key && key != 0).then_some(entry.score)
+    }
+
+    pub fn store(&mut self, key: u64, score: i32) {
+        self.entries[key as usize & self.mask] = EvalCacheEntry { key, score };
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(EvalCacheEntry::default());
+        self.pawns.clear();
+    }
+}
+
+impl Default for EvalCache {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_ENTRIES)
+    }
+}
+
+/// Margin outside the search window beyond which the terms after material and
+/// piece-square tables are assumed unable to bring the score back
+pub const LAZY_MARGIN: i32 = 400;
+
+/// Evaluate through the cache, computing and storing the score on a miss
+pub fn evaluate_cached(position: &Position, key: u64, cache: &mut EvalCache) -> i32 {
+    evaluate_lazy(position, key, -i32::MAX, i32::MAX, cache)
+}
+
+/// A backend that scores positions relative to the side to move
+pub trait Evaluator: std::fmt::Debug + Send + Sync {
+    /// Score `position` for the search window `(alpha, beta)` through `cache`, under
+    /// `key`. Scores well outside the window may be approximate.
+    fn evaluate(
+        &self,
+        position: &Position,
+        key: u64,
+        alpha: i32,
+        beta: i32,
+        cache: &mut EvalCache,
+    ) -> i32;
+}
+
+/// The hand-written evaluation, ignoring any network attached to the position
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Classical;
+
+impl Evaluator for Classical {
+    fn evaluate(
+        &self,
+        position: &Position,
+        key: u64,
+        alpha: i32,
+        beta: i32,
+        cache: &mut EvalCache,
+    ) -> i32 {
+        evaluate_lazy_with(position, key, alpha, beta, cache, false)
+    }
+}
+
+/// The network attached to the position, or the classical evaluation without one
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Nnue;
+
+impl Evaluator for Nnue {
+    fn evaluate(
+        &self,
+        position: &Position,
+        key: u64,
+        alpha: i32,
+        beta: i32,
+        cache: &mut EvalCache,
+    ) -> i32 {
+        evaluate_lazy_with(position, key, alpha, beta, cache, true)
+    }
+}
+
+/// Evaluate through the cache for the search window `(alpha, beta)`.
+///
+/// When material and piece-square tables alone are more than `LAZY_MARGIN` outside
+/// the window, that score is returned without the remaining terms and not cached.
+pub fn evaluate_lazy(
+    position: &Position,
+    key: u64,
+    alpha: i32,
+    beta: i32,
+    cache: &mut EvalCache,
+) -> i32 {
+    Nnue.evaluate(position, key, alpha, beta, cache)
+}
+
+fn evaluate_lazy_with(
+    position: &Position,
+    key: u64,
+    alpha: i32,
+    beta: i32,
+    cache: &mut EvalCache,
+    use_network: bool,
+) -> i32 {
+    if let Some(score) = cache.probe(key) {
+        return score;
+    }
+
+    if let Some(score) = endgame_score(position) {
+        cache.store(key, score);
+        return score;
+    }
+
+    let network_score = if use_network {
+        position.evaluate_nnue()
+    } else {
+        None
+    };
+    if let Some(score) = network_score {
+        cache.store(key, score);
+        return score;
+    }
+
+    let mut lazy = scale(&position.board, position.material_pst());
+    if position.side_to_move == Color::Black {
+        lazy = -lazy;
+    }
+    if lazy.saturating_sub(LAZY_MARGIN) >= beta || lazy.saturating_add(LAZY_MARGIN) <= alpha {
+        return lazy;
+    }
+
+    let pawn_score = pawns::evaluate_cached(
+        &position.board,
+        position.pawn_key(),
+        position.params(),
+        &mut cache.pawns,
+    );
+    let score = evaluate_with_pawns(position, pawn_score);
+    cache.store(key, score);
+    score
+}
+
 pub fn evaluate(position: &Position) -> i32 {
-    let mut score = 0;
+    if let Some(score) = endgame_score(position) {
+        return score;
+    }
+    if let Some(score) = position.evaluate_nnue() {
+        return score;
+    }
+    evaluate_with_pawns(position, pawns::evaluate(&position.board, position.params()))
+}
 
-    
-    score += material_score(&position.board);
+/// Score from a specialised endgame evaluator, relative to the side to move;
+/// dead-drawn material scores zero
+fn endgame_score(position: &Position) -> Option<i32> {
+    if is_insufficient_material(&position.board) {
+        return Some(0);
+    }
+    let score = endgame::probe_with(&position.board, position.material_key())?;
+    Some(match position.side_to_move {
+        Color::White => score,
+        Color::Black => -score,
+    })
+}
 
-    
-    score += piece_square_score(&position.board);
+/// Full evaluation around an already computed pawn structure score
+fn evaluate_with_pawns(position: &Position, pawn_score: i32) -> i32 {
+    let params = position.params();
+    debug_assert_eq!(position.material_pst(), material_pst(&position.board, params));
+    let mut score = position.material_pst();
+
+    score += pawn_score;
+
+    score += majority::evaluate(&position.board, params);
+
+    score += imbalance::evaluate(&position.board, params);
+
+    score += pieces::evaluate(&position.board, params);
+
+    score += coordination::evaluate(&position.board, params);
+
+    score += space::evaluate(&position.board, params);
+
+    score += threats::evaluate(&position.board, params);
+
+    score += weak_squares::evaluate(&position.board, params);
+
+    score += king_safety::evaluate(&position.board, params);
+
+    score += initiative::evaluate(&position.board, params);
+
+    score += king_activity::evaluate(&position.board, position.side_to_move, params);
+
+    score += endgame::evaluate_mop_up(&position.board);
+
+    score = scale(&position.board, score);
 
     
     if position.side_to_move == Color::Black {
@@ -77,7 +403,28 @@ pub fn evaluate(position: &Position) -> i32 {
 }
 
 
-fn material_score(board: &Board) -> i32 {
+/// Shrink a White-relative score by the scale factor of the side it favours
+fn scale(board: &Board, score: i32) -> i32 {
+    let strong = if score >= 0 { Color::White } else { Color::Black };
+    score * endgame::scale_factor(board, strong) / endgame::SCALE_NORMAL
+}
+
+/// Material plus piece-square score of a board from scratch, from White's point of view
+pub fn material_pst(board: &Board, params: &EvalParams) -> i32 {
+    material_score(board, params) + piece_square_score(board, params)
+}
+
+/// Contribution of one piece on `square` to `material_pst`
+pub fn piece_value_pst(piece: Piece, square: u8, params: &EvalParams) -> i32 {
+    let value = params.piece_values[piece.piece_type as usize]
+        + get_piece_square_value(piece.piece_type, square, piece.color, params);
+    match piece.color {
+        Color::White => value,
+        Color::Black => -value,
+    }
+}
+
+fn material_score(board: &Board, params: &EvalParams) -> i32 {
     let mut score = 0;
 
     for piece_type in [
@@ -90,7 +437,7 @@ fn material_score(board: &Board) -> i32 {
     ] {
         let white_count = board.piece_bitboard(Color::White, piece_type).count() as i32;
         let black_count = board.piece_bitboard(Color::Black, piece_type).count() as i32;
-        let piece_value = PIECE_VALUES[piece_type as usize];
+        let piece_value = params.piece_values[piece_type as usize];
 
         score += (white_count - black_count) * piece_value;
     }
@@ -99,7 +446,7 @@ fn material_score(board: &Board) -> i32 {
 }
 
 
-fn piece_square_score(board: &Board) -> i32 {
+fn piece_square_score(board: &Board, params: &EvalParams) -> i32 {
     let mut score = 0;
 
     
@@ -113,7 +460,7 @@ fn piece_square_score(board: &Board) -> i32 {
     ] {
         let pieces = board.piece_bitboard(Color::White, piece_type);
         for square in pieces.squares() {
-            score += get_piece_square_value(piece_type, square, Color::White);
+            score += get_piece_square_value(piece_type, square, Color::White, params);
         }
     }
 
@@ -128,7 +475,7 @@ fn piece_square_score(board: &Board) -> i32 {
     ] {
         let pieces = board.piece_bitboard(Color::Black, piece_type);
         for square in pieces.squares() {
-            score -= get_piece_square_value(piece_type, square, Color::Black);
+            score -= get_piece_square_value(piece_type, square, Color::Black, params);
         }
     }
 
@@ -136,76 +483,212 @@ fn piece_square_score(board: &Board) -> i32 {
 }
 
 
-fn get_piece_square_value(piece_type: PieceType, square: u8, color: Color) -> i32 {
+fn get_piece_square_value(
+    piece_type: PieceType,
+    square: u8,
+    color: Color,
+    params: &EvalParams,
+) -> i32 {
     let table_index = match color {
         Color::White => square as usize,
         Color::Black => 63 - square as usize, 
     };
 
-    match piece_type {
-        PieceType::Pawn => PAWN_TABLE[table_index],
-        PieceType::Knight => KNIGHT_TABLE[table_index],
-        PieceType::Bishop => BISHOP_TABLE[table_index],
-        PieceType::Rook => ROOK_TABLE[table_index],
-        PieceType::Queen => QUEEN_TABLE[table_index],
-        PieceType::King => KING_TABLE[table_index],
+    params.table(piece_type as usize)[table_index]
+}
+
+
+/// Score of one evaluation term for each side; `total` is White minus Black
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TermScore {
+    pub white: i32,
+    pub black: i32,
+}
+
+impl TermScore {
+    fn from_sides(side: impl Fn(Color) -> i32) -> Self {
+        Self {
+            white: side(Color::White),
+            black: side(Color::Black),
+        }
+    }
+
+    pub fn total(&self) -> i32 {
+        self.white - self.black
     }
 }
 
+/// Classical evaluation split into its terms
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EvalTrace {
+    /// Piece values, without the kings
+    pub material: TermScore,
+    pub piece_square: TermScore,
+    pub pawn_structure: TermScore,
+    pub majority: TermScore,
+    pub imbalance: TermScore,
+    pub pieces: TermScore,
+    pub coordination: TermScore,
+    pub space: TermScore,
+    pub threats: TermScore,
+    pub weak_squares: TermScore,
+    pub king_safety: TermScore,
+    pub initiative: TermScore,
+    pub king_activity: TermScore,
+    pub mop_up: TermScore,
+    /// Scale factor applied to the sum of the terms, out of `endgame::SCALE_NORMAL`
+    pub scale_factor: i32,
+}
 
-pub fn is_insufficient_material(board: &Board) -> bool {
-    let total_pieces = board.occupied.count();
+impl EvalTrace {
+    /// Every term with its display name
+    pub fn terms(&self) -> [(&'static str, TermScore); 14] {
+        [
+            ("Material", self.material),
+            ("Piece-square", self.piece_square),
+            ("Pawn structure", self.pawn_structure),
+            ("Pawn majorities", self.majority),
+            ("Imbalance", self.imbalance),
+            ("Pieces", self.pieces),
+            ("Coordination", self.coordination),
+            ("Space", self.space),
+            ("Threats", self.threats),
+            ("Weak squares", self.weak_squares),
+            ("King safety", self.king_safety),
+            ("Initiative", self.initiative),
+            ("King activity", self.king_activity),
+            ("Mop-up", self.mop_up),
+        ]
+    }
 
-    if total_pieces == 2 {
-        
-        return true;
+    /// Sum of all terms from White's point of view, before scaling
+    pub fn unscaled_total(&self) -> i32 {
+        self.terms().iter().map(|(_, term)| term.total()).sum()
     }
 
-    if total_pieces == 3 {
-        
-        let white_pieces = board.white.count();
-        let black_pieces = board.black.count();
-
-        if white_pieces == 2 && black_pieces == 1 {
-            let minor_piece = if board
-                .piece_bitboard(Color::White, PieceType::Bishop)
-                .count()
-                == 1
-            {
-                PieceType::Bishop
-            } else if board
-                .piece_bitboard(Color::White, PieceType::Knight)
-                .count()
-                == 1
-            {
-                PieceType::Knight
-            } else {
-                return false;
-            };
-            return minor_piece == PieceType::Bishop || minor_piece == PieceType::Knight;
-        }
+    /// Final score from White's point of view
+    pub fn total(&self) -> i32 {
+        self.unscaled_total() * self.scale_factor / endgame::SCALE_NORMAL
+    }
+}
 
-        if black_pieces == 2 && white_pieces == 1 {
-            let minor_piece = if board
-                .piece_bitboard(Color::Black, PieceType::Bishop)
-                .count()
-                == 1
-            {
-                PieceType::Bishop
-            } else if board
-                .piece_bitboard(Color::Black, PieceType::Knight)
-                .count()
-                == 1
-            {
-                PieceType::Knight
-            } else {
-                return false;
-            };
-            return minor_piece == PieceType::Bishop || minor_piece == PieceType::Knight;
+impl std::fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<16}{:>8}{:>8}{:>8}", "Term", "White", "Black", "Total")?;
+        for (name, term) in self.terms() {
+            writeln!(
+                f,
+                "{:<16}{:>8}{:>8}{:>8}",
+                name,
+                term.white,
+                term.black,
+                term.total()
+            )?;
         }
+        writeln!(
+            f,
+            "{:<16}{:>8}{:>8}{:>8}",
+            "Scale",
+            "",
+            "",
+            format!("{}/{}", self.scale_factor, endgame::SCALE_NORMAL)
+        )?;
+        write!(f, "{:<16}{:>8}{:>8}{:>8}", "Total", "", "", self.total())
     }
+}
 
-    false
+/// Break the classical evaluation of a position down by term and side.
+///
+/// Scores are White-relative and ignore any loaded network.
+pub fn trace(position: &Position) -> EvalTrace {
+    let board = &position.board;
+    let params = position.params();
+    let closedness = imbalance::closedness(board);
+
+    let mut trace = EvalTrace {
+        material: TermScore::from_sides(|color| side_material(board, color, params)),
+        piece_square: TermScore::from_sides(|color| side_piece_square(board, color, params)),
+        pawn_structure: TermScore::from_sides(|color| pawns::side_score(board, color, params)),
+        majority: TermScore::from_sides(|color| majority::side_score(board, color, params)),
+        imbalance: TermScore::from_sides(|color| {
+            imbalance::side_score(board, color, closedness, params)
+        }),
+        pieces: TermScore::from_sides(|color| pieces::side_score(board, color, params)),
+        coordination: TermScore::from_sides(|color| {
+            coordination::side_score(board, color, params)
+        }),
+        space: TermScore::from_sides(|color| space::side_score(board, color, params)),
+        threats: TermScore::from_sides(|color| threats::side_score(board, color, params)),
+        weak_squares: TermScore::from_sides(|color| {
+            weak_squares::side_score(board, color, params)
+        }),
+        king_safety: TermScore::from_sides(|color| {
+            king_safety::side_score(board, color, params)
+        }),
+        initiative: TermScore::from_sides(|color| {
+            initiative::side_score(board, color, params)
+        }),
+        king_activity: TermScore::from_sides(|color| {
+            king_activity::side_score(board, color, position.side_to_move, params)
+        }),
+        mop_up: TermScore::from_sides(|color| endgame::mop_up(board, color)),
+        scale_factor: endgame::SCALE_NORMAL,
+    };
+    let strong = if trace.unscaled_total() >= 0 {
+        Color::White
+    } else {
+        Color::Black
+    };
+    trace.scale_factor = endgame::scale_factor(board, strong);
+    trace
+}
+
+fn side_material(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+    ]
+    .iter()
+    .map(|&piece_type| {
+        board.piece_bitboard(color, piece_type).count() as i32
+            * params.piece_values[piece_type as usize]
+    })
+    .sum()
+}
+
+fn side_piece_square(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    board
+        .pieces_of_color(color)
+        .map(|(square, piece)| get_piece_square_value(piece.piece_type, square.0, color, params))
+        .sum()
+}
+
+
+/// Neither side can force mate: at most one minor piece, two knights against a bare
+/// king, or only bishops that all stand on one square colour
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let both = |piece_type| {
+        board.piece_bitboard(Color::White, piece_type)
+            | board.piece_bitboard(Color::Black, piece_type)
+    };
+    let knights = both(PieceType::Knight);
+    let bishops = both(PieceType::Bishop);
+    let minors = knights | bishops;
+    if !(board.occupied & !(minors | both(PieceType::King))).is_empty() {
+        return false;
+    }
+
+    if minors.count() <= 1 {
+        return true;
+    }
+    // Same-coloured bishops never attack the other colour, whoever owns them
+    if knights.is_empty() {
+        return (bishops & LIGHT_SQUARES).is_empty() || (bishops & DARK_SQUARES).is_empty();
+    }
+    bishops.is_empty() && knights.count() == 2 && (board.white & knights).count() != 1
 }
 
 
@@ -240,6 +723,21 @@ mod tests {
         assert!(score.abs() < 50);
     }
 
+    #[test]
+    fn test_eval_cache() {
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let key = pos.key();
+        let mut cache = EvalCache::new(16);
+
+        assert_eq!(cache.probe(key), None);
+        let score = evaluate_cached(&pos, key, &mut cache);
+        assert_eq!(score, evaluate(&pos));
+        assert_eq!(cache.probe(key), Some(score));
+
+        cache.clear();
+        assert_eq!(cache.probe(key), None);
+    }
+
     #[test]
     fn test_material_score() {
         let mut board = Board::new();
@@ -251,7 +749,7 @@ mod tests {
         );
         board.update_derived();
 
-        let score = material_score(&board);
+        let score = material_score(&board, &EvalParams::default());
         assert_eq!(score, 900); 
     }
 
@@ -261,7 +759,7 @@ mod tests {
 
         
         for square in 0..64 {
-            board.set_piece(Square::from(square), None);
+            board.set_piece(Square(square), None);
         }
 
         
@@ -295,4 +793,129 @@ mod tests {
 
         assert!(!is_insufficient_material(&board));
     }
+
+    #[test]
+    fn test_backends_agree_without_network() {
+        let pos = Position::from_fen(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+        )
+        .unwrap();
+        let full_window = |evaluator: &dyn Evaluator| {
+            evaluator.evaluate(&pos, pos.key(), -i32::MAX, i32::MAX, &mut EvalCache::default())
+        };
+        let classical = full_window(&Classical);
+        let nnue = full_window(&Nnue);
+        assert_eq!(classical, evaluate(&pos));
+        assert_eq!(nnue, classical);
+    }
+
+    #[test]
+    fn test_wdl() {
+        for phase in [0, 12, MAX_PHASE] {
+            let even = wdl(0, phase);
+            assert_eq!(even.win, even.loss);
+            assert_eq!(even.win + even.draw + even.loss, 1000);
+
+            let ahead = wdl(300, phase);
+            assert!(ahead.win > even.win && ahead.loss < even.loss);
+            assert_eq!(wdl(-300, phase).loss, ahead.win);
+            assert_eq!(wdl(crate::search::MATE_SCORE, phase).win, 1000);
+        }
+        // The same edge converts more often once the pieces are off
+        assert!(wdl(150, 0).win > wdl(150, MAX_PHASE).win);
+    }
+
+    #[test]
+    fn test_insufficient_material_configurations() {
+        let drawn = [
+            "4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1",
+            "2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3BKB2 w - - 0 1",
+        ];
+        for fen in drawn {
+            let pos = Position::from_fen(fen).unwrap();
+            assert!(is_insufficient_material(&pos.board), "{}", fen);
+            assert_eq!(evaluate(&pos), 0, "{}", fen);
+        }
+
+        let playable = [
+            "1b2k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1",
+            "1n2k3/8/8/8/8/8/8/1N2K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w - - 0 1",
+        ];
+        for fen in playable {
+            let pos = Position::from_fen(fen).unwrap();
+            assert!(!is_insufficient_material(&pos.board), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_trace_matches_evaluation() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "6k1/1R6/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1",
+            "4k3/5p2/3b4/8/8/3B4/P4P2/4K3 b - - 0 1",
+        ] {
+            let pos = Position::from_fen(fen).unwrap();
+            let trace = trace(&pos);
+            let white_relative = match pos.side_to_move {
+                Color::White => evaluate(&pos),
+                Color::Black => -evaluate(&pos),
+            };
+            assert_eq!(trace.total(), white_relative, "{}", fen);
+        }
+
+        let trace = trace(&Position::new());
+        assert_eq!(trace.material.white, 3900);
+        assert_eq!(trace.material.total(), 0);
+        assert!(trace.to_string().contains("Pawn structure"));
+    }
+
+    #[test]
+    fn test_tapered_score() {
+        let score = Score::new(40, 10) + Score::new(2, 2) * 5;
+        assert_eq!(score, Score::new(50, 20));
+        assert_eq!(score.taper(MAX_PHASE), 50);
+        assert_eq!(score.taper(0), 20);
+        assert_eq!(score.taper(MAX_PHASE / 2), 35);
+
+        assert_eq!(game_phase(&Position::new().board), MAX_PHASE);
+        let rook_ending = Position::from_fen("4k3/p4r2/8/8/8/8/P7/R3K3 w - - 0 1").unwrap();
+        assert_eq!(game_phase(&rook_ending.board), 4);
+    }
+
+    #[test]
+    fn test_lazy_evaluation() {
+        // White is a queen up, far above a window around equality
+        let pos = Position::from_fen("4k3/pppp4/8/8/8/8/PPPP4/3QK3 w - - 0 1").unwrap();
+        let key = pos.key();
+        let mut cache = EvalCache::new(16);
+
+        let lazy = evaluate_lazy(&pos, key, -50, 50, &mut cache);
+        assert_eq!(lazy, pos.material_pst());
+        assert_eq!(cache.probe(key), None);
+
+        // Inside the window the full evaluation is computed and cached
+        let full = evaluate_lazy(&pos, key, lazy - 10, lazy + 10, &mut cache);
+        assert_eq!(full, evaluate(&pos));
+        assert_eq!(cache.probe(key), Some(full));
+    }
+
+    #[test]
+    fn test_custom_params() {
+        let mut pos = Position::from_fen("4k3/pppp4/8/8/8/8/PPPP4/1N2K3 w - - 0 1").unwrap();
+        let before = evaluate(&pos);
+
+        let mut params = EvalParams::default();
+        params.piece_values[PieceType::Knight as usize] += 100;
+        pos.set_params(std::sync::Arc::new(params));
+        assert_eq!(evaluate(&pos), before + 100);
+
+        // Moves keep the incremental score in step with the new values
+        let mv = crate::movegen::generate_moves(&pos.board, Color::White)[0];
+        pos.make_move(&mv).unwrap();
+        assert_eq!(pos.material_pst(), material_pst(&pos.board, pos.params()));
+    }
 }
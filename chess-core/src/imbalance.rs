@@ -0,0 +1,169 @@
+//! Material imbalance terms that depend on which pieces are on the board together
+
+use crate::{
+    bitboard::{DARK_SQUARES, LIGHT_SQUARES},
+    board::{Board, Color, PieceType},
+    params::EvalParams,
+};
+
+/// Bonus for owning bishops on both square colours
+pub(crate) const BISHOP_PAIR_BONUS: i32 = 30;
+
+/// Closedness at which knights and bishops are worth their base values
+pub(crate) const NEUTRAL_CLOSEDNESS: i32 = 12;
+
+/// Gain of each knight per unit of closedness above neutral
+pub(crate) const KNIGHT_CLOSED_BONUS: i32 = 2;
+
+/// Loss of each bishop per unit of closedness above neutral
+pub(crate) const BISHOP_CLOSED_PENALTY: i32 = 2;
+
+/// Divisor of the imbalance tables, which are in sixteenths of a centipawn
+pub(crate) const IMBALANCE_SCALE: i32 = 16;
+
+/// Pawn count at which the pawn columns of the imbalance tables are neutral
+const NEUTRAL_PAWNS: i32 = 5;
+
+/// Interaction of each piece type with our own pieces, indexed
+/// `[piece][other]` from pawn to queen. Following Kaufman, knights gain and rooks
+/// lose with more pawns, and a second rook or a queen beside rooks is partly redundant.
+pub(crate) const IMBALANCE_OURS: [[i32; 5]; 5] = [
+    [0, 0, 0, 0, 0],
+    [100, -8, 0, 0, 0],
+    [0, 0, 0, 0, 0],
+    [-200, 0, 0, -96, 0],
+    [0, 0, 0, -256, 0],
+];
+
+/// Interaction of each piece type with the enemy pieces; minor pieces gain against
+/// a queen
+pub(crate) const IMBALANCE_THEIRS: [[i32; 5]; 5] = [
+    [0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 64],
+    [0, 0, 0, 0, 64],
+    [0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0],
+];
+
+/// Piece counts from pawn to queen, with pawns relative to `NEUTRAL_PAWNS`
+fn table_counts(board: &Board, color: Color) -> [i32; 5] {
+    let counts = board.piece_counts(color);
+    [
+        counts[PieceType::Pawn as usize] as i32 - NEUTRAL_PAWNS,
+        counts[PieceType::Knight as usize] as i32,
+        counts[PieceType::Bishop as usize] as i32,
+        counts[PieceType::Rook as usize] as i32,
+        counts[PieceType::Queen as usize] as i32,
+    ]
+}
+
+/// Second-order material score of one side from the imbalance tables
+pub(crate) fn quadratic(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let ours = table_counts(board, color);
+    let theirs = table_counts(board, color.opposite());
+
+    let mut score = 0;
+    // Pawns only appear as the interacting piece
+    for piece in 1..5 {
+        if ours[piece] == 0 {
+            continue;
+        }
+        let mut value = 0;
+        for other in 0..5 {
+            value += params.imbalance_ours[piece][other] * ours[other]
+                + params.imbalance_theirs[piece][other] * theirs[other];
+        }
+        score += ours[piece] * value;
+    }
+    score / IMBALANCE_SCALE
+}
+
+/// How closed the position is: the number of pawns, with head-on blocked pawn pairs
+/// counting double since they keep files and diagonals shut
+pub fn closedness(board: &Board) -> i32 {
+    let white = board.piece_bitboard(Color::White, PieceType::Pawn);
+    let black = board.piece_bitboard(Color::Black, PieceType::Pawn);
+    let locked = (white.north() & black).count() as i32;
+    (white | black).count() as i32 + 2 * locked
+}
+
+/// Whether `color` has bishops on both square colours
+pub fn has_bishop_pair(board: &Board, color: Color) -> bool {
+    let bishops = board.piece_bitboard(color, PieceType::Bishop);
+    !(bishops & LIGHT_SQUARES).is_empty() && !(bishops & DARK_SQUARES).is_empty()
+}
+
+pub(crate) fn side_score(board: &Board, color: Color, closedness: i32, params: &EvalParams) -> i32 {
+    let knights = board.piece_bitboard(color, PieceType::Knight).count() as i32;
+    let bishops = board.piece_bitboard(color, PieceType::Bishop).count() as i32;
+    let closed = closedness - NEUTRAL_CLOSEDNESS;
+
+    let mut score = knights * params.knight_closed_bonus * closed
+        - bishops * params.bishop_closed_penalty * closed;
+    if has_bishop_pair(board, color) {
+        score += params.bishop_pair_bonus;
+    }
+    score + quadratic(board, color, params)
+}
+
+/// Imbalance score from White's point of view
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    let closedness = closedness(board);
+    side_score(board, Color::White, closedness, params)
+        - side_score(board, Color::Black, closedness, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_bishop_pair() {
+        let pos = Position::from_fen("2b1kb2/8/8/8/8/8/8/2B1KN2 w - - 0 1").unwrap();
+        assert!(!has_bishop_pair(&pos.board, Color::White));
+        assert!(has_bishop_pair(&pos.board, Color::Black));
+
+        // Two bishops on the same colour are not a pair
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1").unwrap();
+        assert!(!has_bishop_pair(&pos.board, Color::White));
+    }
+
+    #[test]
+    fn test_knight_prefers_closed_positions() {
+        // White knight against black bishop, with every pawn blocked
+        let closed =
+            Position::from_fen("4k3/1b6/p1p1p1p1/PpPpPpPp/1P1P1P1P/8/6N1/4K3 w - - 0 1").unwrap();
+        let open = Position::from_fen("4k3/1b6/8/8/8/8/6N1/4K3 w - - 0 1").unwrap();
+
+        assert!(closedness(&closed.board) > closedness(&open.board));
+        assert!(evaluate(&closed.board, &EvalParams::default()) > 0);
+        assert!(evaluate(&open.board, &EvalParams::default()) < 0);
+    }
+
+    #[test]
+    fn test_imbalance_tables() {
+        // A second rook adds less than the first
+        let one = Position::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/R3K3 w - - 0 1").unwrap();
+        let two = Position::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w - - 0 1").unwrap();
+        let first = quadratic(&one.board, Color::White, &EvalParams::default());
+        assert!(quadratic(&two.board, Color::White, &EvalParams::default()) - first < first);
+
+        // Knights like pawns, rooks prefer open boards
+        let knight_pawns = Position::from_fen("4k3/8/8/8/8/8/PPPPPPPP/1N2K3 w - - 0 1").unwrap();
+        let knight_alone = Position::from_fen("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1").unwrap();
+        assert!(
+            quadratic(&knight_pawns.board, Color::White, &EvalParams::default())
+                > quadratic(&knight_alone.board, Color::White, &EvalParams::default())
+        );
+
+        // Minor pieces gain against a queen
+        let queen = Position::from_fen("3qk3/8/8/8/8/8/PPPPP3/1NB1K3 w - - 0 1").unwrap();
+        assert!(quadratic(&queen.board, Color::White, &EvalParams::default()) > 0);
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        assert_eq!(evaluate(&Position::new().board, &EvalParams::default()), 0);
+    }
+}
@@ -0,0 +1,120 @@
+//! Initiative: with material level, reward the side with more threats, passed pawns
+//! and pressure on the enemy king, so the engine keeps the tension instead of trading
+//! into sterile equality
+
+use crate::{
+    bitboard::Bitboard,
+    board::{Board, Color, PieceType, Square},
+    evaluate::{self, Score},
+    movegen,
+    params::EvalParams,
+    pawns, threats,
+};
+
+/// Bonus per point of initiative: a threat, a passed pawn or an attacked square
+/// next to the enemy king
+pub(crate) const INITIATIVE_BONUS: Score = Score::new(4, 6);
+
+/// Largest material difference, in centipawns, still counted as level
+const LEVEL_MATERIAL_MARGIN: i32 = 100;
+
+/// Material of `color` by the parameter piece values, kings excluded
+fn material(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    board
+        .piece_counts(color)
+        .iter()
+        .zip(&params.piece_values[..5])
+        .map(|(&count, &value)| count as i32 * value)
+        .sum()
+}
+
+/// Whether neither side is ahead by more than the margin
+pub fn material_is_level(board: &Board, params: &EvalParams) -> bool {
+    let difference = material(board, Color::White, params) - material(board, Color::Black, params);
+    difference.abs() <= LEVEL_MATERIAL_MARGIN
+}
+
+/// Enemy pieces `color` attacks that are undefended or attacked more often than
+/// defended
+fn threat_count(board: &Board, color: Color) -> i32 {
+    let attackers = threats::attack_counts(board, color);
+    let defenders = threats::attack_counts(board, color.opposite());
+    let enemy_king = board.piece_bitboard(color.opposite(), PieceType::King);
+    let targets = board.color_bitboard(color.opposite()) & !enemy_king;
+    targets
+        .squares()
+        .filter(|&square| attackers[square as usize] > defenders[square as usize])
+        .count() as i32
+}
+
+/// Squares around the enemy king that `color` attacks
+fn king_pressure(board: &Board, color: Color) -> i32 {
+    let Some(king) = board
+        .piece_bitboard(color.opposite(), PieceType::King)
+        .lsb()
+    else {
+        return 0;
+    };
+    let zone = movegen::king_attacks(Square(king)) | Bitboard::from_square(king);
+    (threats::attacked_squares(board, color) & zone).count() as i32
+}
+
+/// Initiative points of `color`: threats, passed pawns and king pressure
+pub fn initiative_points(board: &Board, color: Color) -> i32 {
+    threat_count(board, color)
+        + pawns::passed_pawns(board, color).count() as i32
+        + king_pressure(board, color)
+}
+
+/// Initiative bonus of `color`, before tapering; nothing unless material is level
+pub(crate) fn initiative(board: &Board, color: Color, params: &EvalParams) -> Score {
+    if !material_is_level(board, params) {
+        return Score::default();
+    }
+    params.initiative_bonus * initiative_points(board, color)
+}
+
+/// Initiative score of one side
+pub(crate) fn side_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    initiative(board, color, params).taper(evaluate::game_phase(board))
+}
+
+/// Initiative score from White's point of view
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    side_score(board, Color::White, params) - side_score(board, Color::Black, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_threats_and_passers_count() {
+        // The e5 pawn attacks the undefended knight on d6; both white pawns are passed
+        let pos = Position::from_fen("4k3/8/3n4/4P3/8/8/P7/4K3 w - - 0 1").unwrap();
+        assert_eq!(threat_count(&pos.board, Color::White), 1);
+        assert_eq!(pawns::passed_pawns(&pos.board, Color::White).count(), 2);
+    }
+
+    #[test]
+    fn test_only_with_level_material() {
+        let params = EvalParams::default();
+        // A lone extra pawn still counts as level, an extra knight does not
+        let level = Position::from_fen("4k3/8/8/4P3/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(material_is_level(&level.board, &params));
+        assert!(initiative(&level.board, Color::White, &params).eg > 0);
+
+        let ahead = Position::from_fen("4k3/8/8/4P3/8/8/8/4KN2 w - - 0 1").unwrap();
+        assert!(!material_is_level(&ahead.board, &params));
+        assert_eq!(
+            initiative(&ahead.board, Color::White, &params),
+            Score::default()
+        );
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        assert_eq!(evaluate(&Position::new().board, &EvalParams::default()), 0);
+    }
+}
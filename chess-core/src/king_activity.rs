@@ -0,0 +1,163 @@
+//! Endgame king activity: centralisation, closeness to passed pawns and the
+//! opposition in pawn endings
+
+use crate::{
+    board::{Board, Color, PieceType, Square},
+    endgame,
+    evaluate::{self, Score},
+    params::EvalParams,
+    pawns,
+};
+
+/// Bonus per step the king stands closer to the centre than a corner
+pub(crate) const KING_CENTRALISATION: Score = Score::new(0, 15);
+
+/// Bonus per step the king is closer to the stop square of a passed pawn, of either
+/// side, than the far edge of the board
+pub(crate) const KING_PASSER_PROXIMITY: Score = Score::new(0, 5);
+
+/// Bonus for holding the opposition in a king and pawn ending
+pub(crate) const OPPOSITION_BONUS: Score = Score::new(0, 20);
+
+fn king_square(board: &Board, color: Color) -> Option<Square> {
+    board
+        .piece_bitboard(color, PieceType::King)
+        .lsb()
+        .map(Square)
+}
+
+/// Only kings and pawns are left
+pub fn is_pawn_ending(board: &Board) -> bool {
+    let pawns = board.piece_bitboard(Color::White, PieceType::Pawn)
+        | board.piece_bitboard(Color::Black, PieceType::Pawn);
+    let kings = board.piece_bitboard(Color::White, PieceType::King)
+        | board.piece_bitboard(Color::Black, PieceType::King);
+    (board.occupied & !(pawns | kings)).is_empty()
+}
+
+/// Whether `color` holds the opposition: the kings face each other on a file or
+/// rank with an odd number of squares between them and the opponent is to move
+pub fn has_opposition(board: &Board, color: Color, side_to_move: Color) -> bool {
+    let (Some(ours), Some(theirs)) = (
+        king_square(board, color),
+        king_square(board, color.opposite()),
+    ) else {
+        return false;
+    };
+    let gap = if ours.file() == theirs.file() {
+        ours.rank().abs_diff(theirs.rank())
+    } else if ours.rank() == theirs.rank() {
+        ours.file().abs_diff(theirs.file())
+    } else {
+        return false;
+    };
+    side_to_move != color && gap % 2 == 0
+}
+
+/// Square in front of a pawn of `color` on `square`
+fn stop_square(square: u8, color: Color) -> Square {
+    match color {
+        Color::White => Square(square + 8),
+        Color::Black => Square(square - 8),
+    }
+}
+
+/// Activity bonus of the king of `color`, before tapering
+pub(crate) fn activity(
+    board: &Board,
+    color: Color,
+    side_to_move: Color,
+    params: &EvalParams,
+) -> Score {
+    let Some(king) = king_square(board, color) else {
+        return Score::default();
+    };
+
+    let mut score = params.king_centralisation * (6 - endgame::centre_distance(king));
+    for owner in [Color::White, Color::Black] {
+        for square in pawns::passed_pawns(board, owner).squares() {
+            let distance = king.distance(stop_square(square, owner)) as i32;
+            score += params.king_passer_proximity * (7 - distance);
+        }
+    }
+    if is_pawn_ending(board) && has_opposition(board, color, side_to_move) {
+        score += params.opposition_bonus;
+    }
+    score
+}
+
+/// King activity score of one side
+pub(crate) fn side_score(
+    board: &Board,
+    color: Color,
+    side_to_move: Color,
+    params: &EvalParams,
+) -> i32 {
+    activity(board, color, side_to_move, params).taper(evaluate::game_phase(board))
+}
+
+/// King activity score from White's point of view
+pub fn evaluate(board: &Board, side_to_move: Color, params: &EvalParams) -> i32 {
+    side_score(board, Color::White, side_to_move, params)
+        - side_score(board, Color::Black, side_to_move, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_central_king_is_active() {
+        let params = EvalParams::default();
+        let central = Position::from_fen("4k3/8/8/8/4K3/8/8/8 w - - 0 1").unwrap();
+        let corner = Position::from_fen("4k3/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert!(
+            activity(&central.board, Color::White, Color::White, &params).eg
+                > activity(&corner.board, Color::White, Color::White, &params).eg
+        );
+        assert_eq!(
+            activity(&central.board, Color::White, Color::White, &params).mg,
+            0
+        );
+    }
+
+    #[test]
+    fn test_king_near_passer() {
+        let params = EvalParams::default();
+        // The d5 passer is escorted by the white king on e5 but not from a1
+        let near = Position::from_fen("7k/8/8/3PK3/8/8/8/8 w - - 0 1").unwrap();
+        let far = Position::from_fen("7k/8/8/3P4/8/8/8/K7 w - - 0 1").unwrap();
+        let centralisation = |pos: &Position| {
+            let king = king_square(&pos.board, Color::White).unwrap();
+            params.king_centralisation * (6 - endgame::centre_distance(king))
+        };
+        let proximity = |pos: &Position| {
+            activity(&pos.board, Color::White, Color::Black, &params) - centralisation(pos)
+        };
+        assert!(proximity(&near).eg > proximity(&far).eg);
+    }
+
+    #[test]
+    fn test_opposition() {
+        // Kings on e4 and e6: whoever is not to move holds the opposition
+        let pos = Position::from_fen("8/8/4k3/8/4K3/8/4P3/8 w - - 0 1").unwrap();
+        assert!(is_pawn_ending(&pos.board));
+        assert!(has_opposition(&pos.board, Color::Black, Color::White));
+        assert!(!has_opposition(&pos.board, Color::White, Color::White));
+        assert!(has_opposition(&pos.board, Color::White, Color::Black));
+
+        // Adjacent files are no opposition
+        let pos = Position::from_fen("8/8/3k4/8/4K3/8/4P3/8 w - - 0 1").unwrap();
+        assert!(!has_opposition(&pos.board, Color::Black, Color::White));
+
+        let pieces = Position::from_fen("8/8/4k3/8/4K3/8/4P3/7R w - - 0 1").unwrap();
+        assert!(!is_pawn_ending(&pieces.board));
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        let params = EvalParams::default();
+        assert_eq!(evaluate(&Position::new().board, Color::White, &params), 0);
+    }
+}
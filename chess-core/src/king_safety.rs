@@ -0,0 +1,120 @@
+//! King safety: pawn storms against the enemy king and the pawn shelter of our own
+
+use crate::{
+    bitboard::{adjacent_files, Bitboard, FILES},
+    board::{Board, Color, PieceType},
+    evaluate::{self, Score},
+    params::EvalParams,
+};
+
+/// Bonus for a pawn storming the enemy king, by relative rank
+pub(crate) const STORM_BONUS: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(0, 0),
+    Score::new(0, 0),
+    Score::new(5, 0),
+    Score::new(10, 0),
+    Score::new(20, 0),
+    Score::new(30, 0),
+    Score::new(0, 0),
+];
+
+/// Penalty per rank a shelter pawn has advanced from its home square
+pub(crate) const SHELTER_ADVANCE_PENALTY: Score = Score::new(8, 0);
+
+fn king_file(board: &Board, color: Color) -> Option<u8> {
+    board
+        .piece_bitboard(color, PieceType::King)
+        .lsb()
+        .map(|square| square % 8)
+}
+
+/// Whether one king is on the queenside (files a-c) and the other on the kingside (f-h)
+pub fn opposite_wings(board: &Board) -> bool {
+    match (
+        king_file(board, Color::White),
+        king_file(board, Color::Black),
+    ) {
+        (Some(white), Some(black)) => (white <= 2 && black >= 5) || (white >= 5 && black <= 2),
+        _ => false,
+    }
+}
+
+/// The king's file and its neighbours
+fn king_zone_files(file: u8) -> Bitboard {
+    FILES[file as usize] | adjacent_files(file)
+}
+
+fn relative_rank(square: u8, color: Color) -> usize {
+    match color {
+        Color::White => (square / 8) as usize,
+        Color::Black => 7 - (square / 8) as usize,
+    }
+}
+
+/// Storm bonus and shelter penalty of one side, only with kings on opposite wings
+pub(crate) fn pawn_storm(board: &Board, color: Color, params: &EvalParams) -> Score {
+    if !opposite_wings(board) {
+        return Score::default();
+    }
+    let (Some(own_file), Some(enemy_file)) =
+        (king_file(board, color), king_file(board, color.opposite()))
+    else {
+        return Score::default();
+    };
+
+    let pawns = board.piece_bitboard(color, PieceType::Pawn);
+    let mut score = Score::default();
+    for square in (pawns & king_zone_files(enemy_file)).squares() {
+        score += params.storm_bonus[relative_rank(square, color)];
+    }
+    for square in (pawns & king_zone_files(own_file)).squares() {
+        let advanced = relative_rank(square, color).saturating_sub(1) as i32;
+        score = score - params.shelter_advance_penalty * advanced;
+    }
+    score
+}
+
+/// King safety score of one side
+pub(crate) fn side_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    pawn_storm(board, color, params).taper(evaluate::game_phase(board))
+}
+
+/// King safety score from White's point of view
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    side_score(board, Color::White, params) - side_score(board, Color::Black, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_storm_needs_opposite_wings() {
+        let same = Position::from_fen("r4rk1/ppp2ppp/8/8/6P1/8/PPP2P1P/R4RK1 w - - 0 1").unwrap();
+        assert!(!opposite_wings(&same.board));
+        assert_eq!(
+            pawn_storm(&same.board, Color::White, &EvalParams::default()),
+            Score::default()
+        );
+    }
+
+    #[test]
+    fn test_storming_pawns() {
+        // White castled long and pushes the g- and h-pawns at the black king
+        let storm = Position::from_fen("r4rk1/ppp2pp1/7p/6PP/8/8/PPP2P2/2KR3R w - - 0 1").unwrap();
+        let quiet = Position::from_fen("r4rk1/ppp2pp1/7p/8/8/8/PPP2PPP/2KR3R w - - 0 1").unwrap();
+        assert!(opposite_wings(&storm.board));
+        assert!(
+            pawn_storm(&storm.board, Color::White, &EvalParams::default()).mg
+                > pawn_storm(&quiet.board, Color::White, &EvalParams::default()).mg
+        );
+
+        // Black's own h6 push weakens its shelter
+        assert_eq!(
+            pawn_storm(&quiet.board, Color::Black, &EvalParams::default()),
+            Score::default() - SHELTER_ADVANCE_PENALTY
+        );
+    }
+}
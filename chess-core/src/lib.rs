@@ -4,9 +4,39 @@ pub  mod magic;
 pub mod magic_simple;
 pub  mod moves;
 pub  mod position;
+pub mod builder;
 pub mod movegen;
+pub mod rules;
+pub mod san;
+pub mod epd;
+pub mod pgn;
+pub mod display;
 pub  mod evaluate;
+pub mod endgame;
+pub mod pawns;
+pub mod majority;
+pub mod imbalance;
+pub mod pieces;
+pub mod coordination;
+pub mod king_safety;
+pub mod initiative;
+pub mod king_activity;
+pub mod space;
+pub mod threats;
+pub mod motifs;
+pub mod weak_squares;
+pub mod params;
+pub mod nnue;
+pub mod tuner;
+#[cfg(any(test, feature = "arbitrary"))]
+pub mod arbitrary;
 pub  mod search;
+pub mod smp;
+pub mod mcts;
+pub mod search_trace;
+pub mod time;
+pub mod tt;
+pub mod zobrist;
 
 /// Result type for chess operations
 pub type Result<T> = std::result::Result<T, Error>;
@@ -15,8 +45,23 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     #[error("Invalid move: {0}")]
     InvalidMove(String),
+    #[error("Illegal move {mv}: {reason}")]
+    IllegalMove {
+        mv: String,
+        reason: moves::IllegalMoveReason,
+    },
     #[error("Invalid position: {0}")]
     InvalidPosition(String),
     #[error("Invalid FEN: {0}")]
     InvalidFen(String),
+    #[error("Invalid square: {0}")]
+    InvalidSquare(String),
+    #[error("Invalid EPD: {0}")]
+    InvalidEpd(String),
+    #[error("Invalid PGN: {0}")]
+    InvalidPgn(String),
+    #[error("Invalid network: {0}")]
+    InvalidNetwork(String),
+    #[error("Invalid evaluation parameters: {0}")]
+    InvalidParams(String),
 }
\ No newline at end of file
@@ -0,0 +1,114 @@
+//! Pawn majorities and outside passed pawns, which decide many endings once the
+//! pieces come off
+
+use crate::{
+    bitboard::{Bitboard, FILE_A, FILE_B, FILE_C, FILE_D},
+    board::{Board, Color, PieceType},
+    evaluate::{self, Score},
+    params::EvalParams,
+    pawns,
+};
+
+/// Bonus per wing where we have more pawns than the opponent, doubled on the wing
+/// away from the enemy king, where the majority can make an outside passer
+pub(crate) const PAWN_MAJORITY_BONUS: Score = Score::new(0, 10);
+
+/// Bonus per passed pawn at least `OUTSIDE_FILES` files away from every enemy pawn
+pub(crate) const OUTSIDE_PASSER_BONUS: Score = Score::new(5, 30);
+
+/// Files between an outside passer and the nearest enemy pawn
+const OUTSIDE_FILES: u8 = 3;
+
+const QUEENSIDE: Bitboard = Bitboard(FILE_A.0 | FILE_B.0 | FILE_C.0 | FILE_D.0);
+const KINGSIDE: Bitboard = Bitboard(!QUEENSIDE.0);
+
+/// Wings on which `color` has more pawns, with whether the enemy king is on the
+/// other wing
+pub fn majorities(board: &Board, color: Color) -> Vec<(Bitboard, bool)> {
+    let ours = board.piece_bitboard(color, PieceType::Pawn);
+    let theirs = board.piece_bitboard(color.opposite(), PieceType::Pawn);
+    let enemy_king = board.piece_bitboard(color.opposite(), PieceType::King);
+
+    [QUEENSIDE, KINGSIDE]
+        .into_iter()
+        .filter(|&wing| (ours & wing).count() > (theirs & wing).count())
+        .map(|wing| (wing, (enemy_king & wing).is_empty()))
+        .collect()
+}
+
+/// Passed pawns of `color` far from every enemy pawn, so the enemy king must
+/// abandon its other pawns to stop them
+pub fn outside_passers(board: &Board, color: Color) -> Bitboard {
+    let theirs = board.piece_bitboard(color.opposite(), PieceType::Pawn);
+    if theirs.is_empty() {
+        return Bitboard::empty();
+    }
+
+    let mut outside = Bitboard::empty();
+    for square in pawns::passed_pawns(board, color).squares() {
+        let file = square % 8;
+        if theirs
+            .squares()
+            .all(|enemy| (enemy % 8).abs_diff(file) >= OUTSIDE_FILES)
+        {
+            outside.set_square(square);
+        }
+    }
+    outside
+}
+
+/// Majority and outside passer bonus of `color`, before tapering
+pub(crate) fn majority(board: &Board, color: Color, params: &EvalParams) -> Score {
+    let mut score = Score::default();
+    for (_, away_from_king) in majorities(board, color) {
+        let weight = if away_from_king { 2 } else { 1 };
+        score += params.pawn_majority_bonus * weight;
+    }
+    score + params.outside_passer_bonus * outside_passers(board, color).count() as i32
+}
+
+/// Majority score of one side
+pub(crate) fn side_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    majority(board, color, params).taper(evaluate::game_phase(board))
+}
+
+/// Majority score from White's point of view
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    side_score(board, Color::White, params) - side_score(board, Color::Black, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_queenside_majority_away_from_king() {
+        // Three pawns against two on the queenside, with the black king on g8
+        let pos = Position::from_fen("6k1/pp3ppp/8/8/8/8/PPP2PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            majorities(&pos.board, Color::White),
+            vec![(QUEENSIDE, true)]
+        );
+        assert!(majorities(&pos.board, Color::Black).is_empty());
+    }
+
+    #[test]
+    fn test_outside_passer() {
+        // The a-pawn is passed and five files from the nearest black pawn
+        let pos = Position::from_fen("6k1/5ppp/8/8/P7/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(
+            outside_passers(&pos.board, Color::White),
+            Bitboard::from_square(24)
+        );
+
+        // A passer next to the enemy pawns is not outside
+        let pos = Position::from_fen("6k1/6pp/8/8/4P3/8/6PP/6K1 w - - 0 1").unwrap();
+        assert!(outside_passers(&pos.board, Color::White).is_empty());
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        assert_eq!(evaluate(&Position::new().board, &EvalParams::default()), 0);
+    }
+}
@@ -0,0 +1,280 @@
+//! Monte Carlo tree search (UCT) as an alternative to alpha-beta.
+//!
+//! Rollouts are truncated at the leaf: a newly expanded node is scored by the
+//! static evaluation, mapped onto a win probability, instead of playing random
+//! moves to the end of the game.
+
+use crate::{
+    evaluate::EvalCache,
+    moves::Move,
+    position::Position,
+    search::{
+        self, IterationInfo, SearchObserver, SearchParams, SearchResult, SearchStats, MATE_BOUND,
+        MATE_SCORE,
+    },
+    Result,
+};
+use std::sync::atomic::Ordering;
+
+/// Exploration constant of the UCT formula
+const EXPLORATION: f64 = 1.4;
+
+/// Playouts run when neither a node nor a time limit is given
+pub const DEFAULT_PLAYOUTS: u64 = 10_000;
+
+/// Centipawn difference that turns into 10:1 odds of winning
+const SCORE_SCALE: f64 = 400.0;
+
+/// A node of the search tree, stored in an arena and linked by index
+struct Node {
+    /// Move leading here from the parent; `None` at the root
+    mv: Option<Move>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    expanded: bool,
+    visits: u32,
+    /// Sum of results for the side that played `mv`
+    value: f64,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, parent: Option<usize>) -> Self {
+        Self {
+            mv,
+            parent,
+            children: Vec::new(),
+            expanded: false,
+            visits: 0,
+            value: 0.0,
+        }
+    }
+
+    fn mean(&self) -> f64 {
+        if self.visits == 0 {
+            0.5
+        } else {
+            self.value / self.visits as f64
+        }
+    }
+}
+
+/// Map a centipawn score onto the probability of winning
+fn win_probability(score: i32) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-score as f64 / SCORE_SCALE))
+}
+
+/// Inverse of `win_probability`, clamped below the mate scores: a certain result
+/// is not a proven mate
+fn centipawns(probability: f64) -> i32 {
+    let limit = (MATE_BOUND - 1) as f64;
+    (-SCORE_SCALE * (1.0 / probability - 1.0).log10()).clamp(-limit, limit) as i32
+}
+
+/// Whether the move into `node` checkmates: the node has been expanded, has no
+/// moves and was lost for the side to move there
+fn mates(tree: &[Node], node: usize) -> bool {
+    let node = &tree[node];
+    node.expanded && node.children.is_empty() && node.mean() >= 1.0
+}
+
+/// Pick the child maximising the UCT bound, trying unvisited children first
+fn select_child(tree: &[Node], node: usize) -> usize {
+    let parent_visits = (tree[node].visits.max(1) as f64).ln();
+    let uct = |child: usize| {
+        let child = &tree[child];
+        if child.visits == 0 {
+            return f64::INFINITY;
+        }
+        child.mean() + EXPLORATION * (parent_visits / child.visits as f64).sqrt()
+    };
+
+    let mut best = tree[node].children[0];
+    let mut best_uct = uct(best);
+    for &child in &tree[node].children[1..] {
+        let value = uct(child);
+        if value > best_uct {
+            best = child;
+            best_uct = value;
+        }
+    }
+    best
+}
+
+/// Most visited child, which is the move MCTS commits to
+fn most_visited(tree: &[Node], node: usize) -> Option<usize> {
+    tree[node]
+        .children
+        .iter()
+        .copied()
+        .max_by_key(|&child| tree[child].visits)
+}
+
+/// Search with UCT until the playout, time or stop limit is reached.
+///
+/// `params.nodes_limit` counts playouts; `params.depth` is ignored.
+pub fn search(
+    position: &Position,
+    params: &SearchParams,
+    observer: &mut dyn SearchObserver,
+) -> Result<SearchResult> {
+    let time = params.time_manager(position.side_to_move);
+    let max_playouts = match (params.nodes_limit, time.is_limited()) {
+        (Some(nodes), _) => nodes.max(1),
+        (None, true) => u64::MAX,
+        (None, false) => DEFAULT_PLAYOUTS,
+    };
+
+    let mut position = position.clone();
+    let mut tree = vec![Node::new(None, None)];
+    let mut stats = SearchStats::default();
+    let evaluator = &params.evaluator;
+    let mut eval_cache = EvalCache::default();
+
+    while stats.nodes_searched < max_playouts {
+        // The root must be expanded before any limit may stop the search
+        if stats.nodes_searched > 0
+            && (params.stop.load(Ordering::Relaxed)
+                || (time.hard_limit_reached() && !params.ponder.load(Ordering::Relaxed)))
+        {
+            break;
+        }
+        stats.nodes_searched += 1;
+
+        // Selection
+        let mut node = 0;
+        let mut ply = 0;
+        while tree[node].expanded && !tree[node].children.is_empty() {
+            node = select_child(&tree, node);
+            let mv = tree[node].mv.expect("only the root has no move");
+            search::make(&mut position, &mv);
+            ply += 1;
+        }
+        stats.seldepth = stats.seldepth.max(ply);
+
+        // Expansion
+        let mut moves = search::legal_moves(&position);
+        if node == 0 {
+            if let Some(allowed) = &params.search_moves {
+                moves.retain(|mv| allowed.iter().any(|other| search::same_move(mv, other)));
+            }
+        }
+        if !tree[node].expanded {
+            tree[node].expanded = true;
+            for mv in &moves {
+                let child = tree.len();
+                tree.push(Node::new(Some(*mv), Some(node)));
+                tree[node].children.push(child);
+            }
+        }
+
+        // Truncated rollout, scored for the side to move at the leaf
+        let mut result = if !moves.is_empty() {
+            let key = position.key();
+            win_probability(evaluator.evaluate(
+                &position,
+                key,
+                -i32::MAX,
+                i32::MAX,
+                &mut eval_cache,
+            ))
+        } else if position.in_check() {
+            0.0
+        } else {
+            0.5
+        };
+
+        // Backpropagation
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let node = &mut tree[index];
+            node.visits += 1;
+            node.value += 1.0 - result;
+            result = 1.0 - result;
+            if node.parent.is_some() {
+                search::unmake(&mut position);
+            }
+            current = node.parent;
+        }
+    }
+
+    let best = most_visited(&tree, 0);
+    let best_move = best.and_then(|child| tree[child].mv);
+    let score = match best {
+        Some(child) if mates(&tree, child) => MATE_SCORE - 1,
+        Some(child) => centipawns(tree[child].mean()),
+        None if position.in_check() => -MATE_SCORE,
+        None => 0,
+    };
+
+    // Principal variation along the most visited children
+    let mut pv = Vec::new();
+    let mut node = best;
+    while let Some(index) = node {
+        pv.extend(tree[index].mv);
+        node = most_visited(&tree, index);
+    }
+
+    stats.depth = pv.len() as u32;
+    stats.elapsed_ms = time.elapsed_ms();
+    if best_move.is_some() {
+        observer.on_iteration(&IterationInfo {
+            depth: stats.depth,
+            seldepth: stats.seldepth,
+            score,
+            best_move,
+            pv,
+            nodes: stats.nodes_searched,
+            elapsed_ms: stats.elapsed_ms,
+        });
+    }
+
+    Ok(SearchResult {
+        best_move,
+        score,
+        depth: stats.depth,
+        stats,
+        trace: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{NullObserver, SearchAlgorithm};
+
+    fn params(playouts: u64) -> SearchParams {
+        SearchParams {
+            algorithm: SearchAlgorithm::Mcts,
+            nodes_limit: Some(playouts),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_score_conversion() {
+        assert!((win_probability(0) - 0.5).abs() < 1e-9);
+        assert_eq!(centipawns(win_probability(250)), 250);
+        assert_eq!(centipawns(1.0), MATE_BOUND - 1);
+        assert_eq!(centipawns(0.0), -(MATE_BOUND - 1));
+        assert_eq!(search::mate_in(centipawns(1.0)), None);
+    }
+
+    #[test]
+    fn test_finds_mate_in_one() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = search(&pos, &params(2_000), &mut NullObserver).unwrap();
+
+        assert_eq!(result.best_move.unwrap().to_algebraic(), "a1a8");
+        assert_eq!(search::mate_in(result.score), Some(1));
+        assert_eq!(result.stats.nodes_searched, 2_000);
+    }
+
+    #[test]
+    fn test_captures_hanging_queen() {
+        let pos = Position::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result = search::think(&pos, &params(2_000)).unwrap();
+
+        assert_eq!(result.best_move.unwrap().to_algebraic(), "d2d5");
+        assert!(result.score > 0);
+    }
+}
@@ -0,0 +1,216 @@
+//! Tactical motifs standing on the board: forks, pins, skewers and discovered
+//! attacks, for annotating games and picking out puzzles
+
+use crate::{
+    board::{Board, Color, Piece, PieceType, Square},
+    evaluate,
+    threats::{attacked_squares, piece_attacks},
+};
+
+/// Steps of the straight and diagonal rays, as file and rank deltas
+const STRAIGHT: [(i8, i8); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+const DIAGONAL: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const QUEEN: [(i8, i8); 8] = [
+    (0, 1),
+    (0, -1),
+    (1, 0),
+    (-1, 0),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+/// A tactical pattern, seen from the side making use of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Motif {
+    /// One piece attacking two or more enemy pieces that are undefended, worth more
+    /// than the attacker, or the king
+    Fork {
+        attacker: Square,
+        targets: Vec<Square>,
+    },
+    /// A slider attacking `pinned`, which shields the more valuable `target` behind
+    /// it. The pin is absolute when the target is the king.
+    Pin {
+        pinner: Square,
+        pinned: Square,
+        target: Square,
+        absolute: bool,
+    },
+    /// A slider attacking `front`, which has to step aside and give up the less
+    /// valuable `behind`
+    Skewer {
+        attacker: Square,
+        front: Square,
+        behind: Square,
+    },
+    /// A piece of the attacking side standing between its own slider and an enemy
+    /// piece, uncovering an attack on it when it moves
+    DiscoveredAttack {
+        mover: Square,
+        slider: Square,
+        target: Square,
+    },
+}
+
+/// Every motif `color` can make use of, forks first, then the line motifs by slider
+pub fn detect(board: &Board, color: Color) -> Vec<Motif> {
+    let mut motifs = forks(board, color);
+    for (square, piece) in board.pieces_of_color(color) {
+        let directions: &[(i8, i8)] = match piece.piece_type {
+            PieceType::Bishop => &DIAGONAL,
+            PieceType::Rook => &STRAIGHT,
+            PieceType::Queen => &QUEEN,
+            _ => continue,
+        };
+        for &(file_step, rank_step) in directions {
+            if let Some(motif) = line_motif(board, piece, square, file_step, rank_step) {
+                motifs.push(motif);
+            }
+        }
+    }
+    motifs
+}
+
+fn forks(board: &Board, color: Color) -> Vec<Motif> {
+    let defended = attacked_squares(board, color.opposite());
+    let mut forks = Vec::new();
+    for (attacker, piece) in board.pieces_of_color(color) {
+        let attacks = piece_attacks(board, piece.piece_type, attacker.0, color);
+        let targets: Vec<Square> = board
+            .pieces_of_color(color.opposite())
+            .filter(|&(target, victim)| {
+                attacks.has_square(target.0)
+                    && victim.piece_type != PieceType::Pawn
+                    && (victim.piece_type == PieceType::King
+                        || !defended.has_square(target.0)
+                        || value(victim) > value(piece))
+            })
+            .map(|(target, _)| target)
+            .collect();
+        if targets.len() >= 2 {
+            forks.push(Motif::Fork { attacker, targets });
+        }
+    }
+    forks
+}
+
+/// The pin, skewer or discovered attack along one ray of the slider on `square`
+fn line_motif(
+    board: &Board,
+    slider: Piece,
+    square: Square,
+    file_step: i8,
+    rank_step: i8,
+) -> Option<Motif> {
+    let mut pieces = ray(square, file_step, rank_step)
+        .filter_map(|square| board.piece_at(square).map(|piece| (square, piece)));
+    let (first, front) = pieces.next()?;
+    let (second, behind) = pieces.next()?;
+    if behind.color == slider.color {
+        return None;
+    }
+
+    if front.color == slider.color {
+        return (behind.piece_type != PieceType::Pawn).then_some(Motif::DiscoveredAttack {
+            mover: first,
+            slider: square,
+            target: second,
+        });
+    }
+    if value(behind) > value(front) {
+        Some(Motif::Pin {
+            pinner: square,
+            pinned: first,
+            target: second,
+            absolute: behind.piece_type == PieceType::King,
+        })
+    } else if value(front) > value(behind) && value(front) > value(slider) {
+        Some(Motif::Skewer {
+            attacker: square,
+            front: first,
+            behind: second,
+        })
+    } else {
+        None
+    }
+}
+
+/// Squares from `square` outwards in one direction, up to the edge of the board
+fn ray(square: Square, file_step: i8, rank_step: i8) -> impl Iterator<Item = Square> {
+    (1..8).map_while(move |distance| {
+        let file = square.file() as i8 + file_step * distance;
+        let rank = square.rank() as i8 + rank_step * distance;
+        ((0..8).contains(&file) && (0..8).contains(&rank))
+            .then(|| Square::new(file as u8, rank as u8))
+    })
+}
+
+fn value(piece: Piece) -> i32 {
+    evaluate::PIECE_VALUES[piece.piece_type as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    fn motifs(fen: &str, color: Color) -> Vec<Motif> {
+        detect(&Position::from_fen(fen).unwrap().board, color)
+    }
+
+    #[test]
+    fn test_fork() {
+        // The c7 knight forks the king on e8 and the rook on a8
+        let found = motifs("r3k3/2N5/8/8/8/8/8/4K3 b - - 0 1", Color::White);
+        assert_eq!(
+            found,
+            vec![Motif::Fork {
+                attacker: Square::C7,
+                targets: vec![Square::A8, Square::E8],
+            }]
+        );
+        assert!(motifs("r3k3/2N5/8/8/8/8/8/4K3 b - - 0 1", Color::Black).is_empty());
+    }
+
+    #[test]
+    fn test_pins_and_skewers() {
+        // The b5 bishop pins the c6 knight to the e8 king
+        let found = motifs("4k3/8/2n5/1B6/8/8/8/4K3 w - - 0 1", Color::White);
+        assert!(found.contains(&Motif::Pin {
+            pinner: Square::B5,
+            pinned: Square::C6,
+            target: Square::E8,
+            absolute: true,
+        }));
+
+        // The e1 rook pins the e4 knight to the e7 queen
+        let found = motifs("k7/4q3/8/8/4n3/8/8/K3R3 w - - 0 1", Color::White);
+        assert!(found.contains(&Motif::Pin {
+            pinner: Square::E1,
+            pinned: Square::E4,
+            target: Square::E7,
+            absolute: false,
+        }));
+
+        // The a4 rook skewers the e4 king to the h4 queen
+        let found = motifs("8/8/8/8/R3k2q/8/8/K7 b - - 0 1", Color::White);
+        assert!(found.contains(&Motif::Skewer {
+            attacker: Square::A4,
+            front: Square::E4,
+            behind: Square::H4,
+        }));
+    }
+
+    #[test]
+    fn test_discovered_attack() {
+        // The d3 knight stands between the d1 rook and the d7 queen
+        let found = motifs("k7/3q4/8/8/8/3N4/8/K2R4 w - - 0 1", Color::White);
+        assert!(found.contains(&Motif::DiscoveredAttack {
+            mover: Square::D3,
+            slider: Square::D1,
+            target: Square::D7,
+        }));
+    }
+}
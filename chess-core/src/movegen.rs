@@ -1,9 +1,11 @@
 
 use crate::{
-    bitboard::Bitboard,
-    board::{Board, Color, PieceType, Square},
+    bitboard::{self, Bitboard},
+    board::{Board, Color, Piece, PieceType, Square},
     magic_simple as magic,
-    moves::Move,
+    moves::{Move, MoveList},
+    pawns,
+    position::CastlingRights,
 };
 
 lazy_static::lazy_static! {
@@ -26,24 +28,39 @@ lazy_static::lazy_static! {
     };
 }
 
+/// All piece types, in the order moves are generated
+const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+/// Pseudo-legal moves of `color`: every move a piece can make, without castling or
+/// en passant and whether or not it leaves the own king in check
+pub fn generate_moves(board: &Board, color: Color) -> MoveList {
+    let mut moves = MoveList::new();
+    generate_targeted(board, color, &PIECE_TYPES, Bitboard(!0), &mut moves);
+    moves
+}
 
-pub fn generate_moves(board: &Board, color: Color) -> Vec<Move> {
-    let mut moves = Vec::new();
-
-    
-    for piece_type in [
-        PieceType::Pawn,
-        PieceType::Knight,
-        PieceType::Bishop,
-        PieceType::Rook,
-        PieceType::Queen,
-        PieceType::King,
-    ] {
+/// Pseudo-legal moves of `piece_types` of `color` landing on `mask`, promotions
+/// expanded to all four pieces
+fn generate_targeted(
+    board: &Board,
+    color: Color,
+    piece_types: &[PieceType],
+    mask: Bitboard,
+    moves: &mut MoveList,
+) {
+    for &piece_type in piece_types {
         let pieces = board.piece_bitboard(color, piece_type);
         for from_square in pieces.squares() {
-            let targets = generate_piece_moves(board, color, piece_type, from_square);
+            let targets = generate_piece_moves(board, color, piece_type, from_square) & mask;
             for to_square in targets.squares() {
-                
+                let (from, to) = (Square(from_square), Square(to_square));
                 if piece_type == PieceType::Pawn && is_promotion_rank(to_square, color) {
                     for promotion in [
                         PieceType::Knight,
@@ -51,30 +68,267 @@ pub fn generate_moves(board: &Board, color: Color) -> Vec<Move> {
                         PieceType::Rook,
                         PieceType::Queen,
                     ] {
-                        moves.push(Move::new_promotion(
-                            Square::from(from_square),
-                            Square::from(to_square),
-                            piece_type,
-                            promotion,
-                        ));
+                        moves.push(Move::new_promotion(from, to, piece_type, promotion));
                     }
                 } else {
-                    moves.push(Move::new(
-                        Square::from(from_square),
-                        Square::from(to_square),
-                        piece_type,
-                    ));
+                    moves.push(Move::new(from, to, piece_type));
                 }
             }
         }
     }
+}
 
-    
-    
+/// Pseudo-legal captures of `color`, capturing promotions and en passant onto
+/// `en_passant` included, for quiescence search
+pub fn generate_captures(board: &Board, color: Color, en_passant: Option<Square>) -> MoveList {
+    let mut moves = MoveList::new();
+    let enemies = board.color_bitboard(color.opposite());
+    generate_targeted(board, color, &PIECE_TYPES, enemies, &mut moves);
+    moves.extend(generate_en_passant_moves(board, color, en_passant));
+    moves
+}
 
+/// Pseudo-legal moves of `color` that capture nothing, quiet promotions and
+/// castling included
+pub fn generate_quiets(board: &Board, color: Color, rights: &CastlingRights) -> MoveList {
+    let mut moves = MoveList::new();
+    generate_targeted(board, color, &PIECE_TYPES, board.empty, &mut moves);
+    moves.extend(generate_castling_moves(board, color, rights));
     moves
 }
 
+/// Pseudo-legal replies of `color` to check: king moves and, against a single
+/// checker, capturing it or blocking the line between it and the king
+pub fn generate_evasions(board: &Board, color: Color, en_passant: Option<Square>) -> MoveList {
+    let mut moves = MoveList::new();
+    generate_targeted(board, color, &[PieceType::King], Bitboard(!0), &mut moves);
+    let Some(king) = board.piece_bitboard(color, PieceType::King).lsb() else {
+        return moves;
+    };
+    let checkers =
+        board.attackers_to(Square(king), board.occupied) & board.color_bitboard(color.opposite());
+    let Some(checker) = checkers.lsb() else {
+        return moves;
+    };
+    if checkers.count() > 1 {
+        return moves;
+    }
+
+    let mask = bitboard::between(checker, king) | Bitboard::from_square(checker);
+    generate_targeted(board, color, &PIECE_TYPES[..5], mask, &mut moves);
+    // En passant takes a checking pawn, or lands on the line of a checking slider
+    if let Some(target) = en_passant {
+        let victim = match color {
+            Color::White => target.index() - 8,
+            Color::Black => target.index() + 8,
+        };
+        if victim == checker || mask.has_square(target.index()) {
+            moves.extend(generate_en_passant_moves(board, color, en_passant));
+        }
+    }
+    moves
+}
+
+/// Legal moves of `color`, castling and en passant onto `en_passant` included.
+/// In check only evasions are generated, as nothing else can be legal.
+pub fn generate_legal_moves(
+    board: &Board,
+    color: Color,
+    rights: &CastlingRights,
+    en_passant: Option<Square>,
+) -> MoveList {
+    let mut moves = if king_in_check(board, color) {
+        generate_evasions(board, color, en_passant)
+    } else {
+        let mut moves = generate_moves(board, color);
+        moves.extend(generate_castling_moves(board, color, rights));
+        moves.extend(generate_en_passant_moves(board, color, en_passant));
+        moves
+    };
+    moves.retain(|mv| is_legal(board, color, mv));
+    moves
+}
+
+/// Whether `color` has a legal move, found without generating the others: king
+/// moves are tried first, then the other pieces one type at a time
+pub fn has_legal_move(
+    board: &Board,
+    color: Color,
+    rights: &CastlingRights,
+    en_passant: Option<Square>,
+) -> bool {
+    let legal = |mv: &Move| is_legal(board, color, mv);
+    if king_in_check(board, color) {
+        return generate_evasions(board, color, en_passant).iter().any(legal);
+    }
+    for piece_type in PIECE_TYPES.into_iter().rev() {
+        let mut moves = MoveList::new();
+        generate_targeted(board, color, &[piece_type], Bitboard(!0), &mut moves);
+        if moves.iter().any(legal) {
+            return true;
+        }
+    }
+    generate_en_passant_moves(board, color, en_passant).iter().any(legal)
+        || generate_castling_moves(board, color, rights).iter().any(legal)
+}
+
+fn king_in_check(board: &Board, color: Color) -> bool {
+    board
+        .piece_bitboard(color, PieceType::King)
+        .lsb()
+        .is_some_and(|king| is_square_attacked(board, Square(king), color.opposite()))
+}
+
+/// En passant captures of `color` onto `en_passant`, the square skipped by the
+/// enemy pawn's double push. Legality, such as a pin along the rank both pawns
+/// leave, is left to `is_legal`.
+pub fn generate_en_passant_moves(
+    board: &Board,
+    color: Color,
+    en_passant: Option<Square>,
+) -> MoveList {
+    let Some(target) = en_passant else {
+        return MoveList::new();
+    };
+    // Our pawns that attack the target are those it would attack as an enemy pawn
+    let attackers = pawns::pawn_attacks(target.bitboard(), color.opposite())
+        & board.piece_bitboard(color, PieceType::Pawn);
+    attackers
+        .squares()
+        .map(|from| Move::new_en_passant(Square(from), target))
+        .collect()
+}
+
+/// Castling moves of `color` that `rights` allow with every square the king and
+/// rook cross empty; whether the king passes through check is left to `is_legal`.
+/// In Chess960 the king and rook may start anywhere on the back rank but land on
+/// the same squares as in standard chess.
+pub fn generate_castling_moves(board: &Board, color: Color, rights: &CastlingRights) -> MoveList {
+    let rank = match color {
+        Color::White => 0,
+        Color::Black => 7,
+    };
+    let Some(king) = board
+        .piece_bitboard(color, PieceType::King)
+        .squares()
+        .map(Square)
+        .find(|square| square.rank() == rank)
+    else {
+        return MoveList::new();
+    };
+
+    let rooks = board.piece_bitboard(color, PieceType::Rook);
+    let mut moves = MoveList::new();
+    for (kingside, king_file, rook_file) in [(true, 6, 5), (false, 2, 3)] {
+        let rook = rights.rook_square(color, kingside);
+        if !rights.has(color, kingside) || !rooks.has_square(rook.index()) {
+            continue;
+        }
+        let king_to = Square::new(king_file, rank);
+        let rook_to = Square::new(rook_file, rank);
+        let crossed = span(king, king_to) | span(rook, rook_to);
+        let others = board.occupied & !king.bitboard() & !rook.bitboard();
+        if (crossed & others).is_empty() {
+            moves.push(Move::new_castling(king, king_to, color));
+        }
+    }
+    moves
+}
+
+/// Squares from `a` to `b` inclusive, for two squares on one rank
+fn span(a: Square, b: Square) -> Bitboard {
+    bitboard::between(a.index(), b.index()) | a.bitboard() | b.bitboard()
+}
+
+/// The rook a castling king on `king` castles with towards `king_to`: the first
+/// piece beside the king on that side, as the squares between must be empty
+fn castling_rook(board: &Board, king: Square, king_to: Square) -> Option<Square> {
+    let step: i8 = if king_to.file() == 6 { 1 } else { -1 };
+    let mut file = king.file() as i8 + step;
+    while (0..8).contains(&file) {
+        let square = Square::new(file as u8, king.rank());
+        if board.piece_at(square).is_some() {
+            return Some(square);
+        }
+        file += step;
+    }
+    None
+}
+
+/// Whether the pseudo-legal `mv` of `color` leaves its own king safe. A castling
+/// king must also not start in or pass through check.
+pub fn is_legal(board: &Board, color: Color, mv: &Move) -> bool {
+    let them = color.opposite();
+    if mv.is_castling() {
+        // Lift king and rook off first, so an attack along the back rank that the
+        // rook screened on its home square is seen
+        let (from, to) = (mv.from(), mv.to());
+        let mut occupied = board.occupied & !from.bitboard();
+        if let Some(rook) = castling_rook(board, from, to) {
+            occupied &= !rook.bitboard();
+        }
+        return (board.attacks_by(them, occupied) & span(from, to)).is_empty();
+    }
+
+    let mut after = board.clone();
+    let (from, to) = (mv.from(), mv.to());
+    after.remove_piece(to);
+    if mv.is_en_passant() {
+        let captured = match color {
+            Color::White => to.index() - 8,
+            Color::Black => to.index() + 8,
+        };
+        after.remove_piece(Square(captured));
+    }
+    let landed = mv.promotion_piece().unwrap_or(mv.piece_type());
+    after.remove_piece(from);
+    after.put_piece(to, Piece::new(color, landed));
+
+    match after.piece_bitboard(color, PieceType::King).lsb() {
+        Some(king) => !is_square_attacked(&after, Square(king), them),
+        None => true,
+    }
+}
+
+/// Pieces of either colour standing alone between the king of `color` and an enemy
+/// slider aimed at it: moving one off the line exposes the king
+pub fn king_blockers(board: &Board, color: Color) -> Bitboard {
+    let Some(king) = board.piece_bitboard(color, PieceType::King).lsb() else {
+        return Bitboard::empty();
+    };
+    let them = color.opposite();
+    let queens = board.piece_bitboard(them, PieceType::Queen);
+    let straight = board.piece_bitboard(them, PieceType::Rook) | queens;
+    let diagonal = board.piece_bitboard(them, PieceType::Bishop) | queens;
+    let snipers = (magic::get_rook_attacks(king, Bitboard::empty()) & straight)
+        | (magic::get_bishop_attacks(king, Bitboard::empty()) & diagonal);
+
+    let mut blockers = Bitboard::empty();
+    for sniper in snipers.squares() {
+        let between = bitboard::between(sniper, king) & board.occupied;
+        if between.count() == 1 {
+            blockers |= between;
+        }
+    }
+    blockers
+}
+
+/// Pieces of `color` absolutely pinned to their king
+pub fn pinned_pieces(board: &Board, color: Color) -> Bitboard {
+    king_blockers(board, color) & board.color_bitboard(color)
+}
+
+/// Pieces of `color` screening one of its own sliders from the enemy king, which
+/// give discovered check by stepping off the line
+pub fn discovered_checkers(board: &Board, color: Color) -> Bitboard {
+    king_blockers(board, color.opposite()) & board.color_bitboard(color)
+}
+
+/// Whether any piece of `by_color` attacks `square`
+pub fn is_square_attacked(board: &Board, square: Square, by_color: Color) -> bool {
+    !(board.attackers_to(square, board.occupied) & board.color_bitboard(by_color)).is_empty()
+}
+
 
 pub fn generate_piece_moves(
     board: &Board,
@@ -82,7 +336,7 @@ pub fn generate_piece_moves(
     piece_type: PieceType,
     from_square: u8,
 ) -> Bitboard {
-    let from = Square::from(from_square);
+    let from = Square(from_square);
     let mut targets = match piece_type {
         PieceType::Pawn => generate_pawn_moves(board, color, from),
         PieceType::Knight => generate_knight_moves(board, color, from),
@@ -166,6 +420,50 @@ pub fn generate_knight_moves(board: &Board, color: Color, from: Square) -> Bitbo
     KNIGHT_ATTACKS[from.index() as usize] & !board.color_bitboard(color)
 }
 
+/// Squares attacked by a knight on `square`, regardless of occupancy
+pub fn knight_attacks(square: Square) -> Bitboard {
+    KNIGHT_ATTACKS[square.index() as usize]
+}
+
+/// Squares attacked by a king on `square`, regardless of occupancy
+pub fn king_attacks(square: Square) -> Bitboard {
+    KING_ATTACKS[square.index() as usize]
+}
+
+/// Squares a `piece_type` of `color` on `square` attacks, sliders stopping at the
+/// first square of `occupied` in each direction
+pub fn piece_attacks(
+    piece_type: PieceType,
+    color: Color,
+    square: Square,
+    occupied: Bitboard,
+) -> Bitboard {
+    match piece_type {
+        PieceType::Pawn => pawns::pawn_attacks(square.bitboard(), color),
+        PieceType::Knight => knight_attacks(square),
+        PieceType::Bishop => magic::get_bishop_attacks(square.index(), occupied),
+        PieceType::Rook => magic::get_rook_attacks(square.index(), occupied),
+        PieceType::Queen => magic::get_queen_attacks(square.index(), occupied),
+        PieceType::King => king_attacks(square),
+    }
+}
+
+/// Squares a rook on `square` attacks only through the first piece of `blockers`
+/// in each direction, stopping at the next piece of `occupied`: what a battery
+/// behind the blocker, or the piece pinned or skewered by it, can see
+pub fn xray_rook_attacks(square: Square, occupied: Bitboard, blockers: Bitboard) -> Bitboard {
+    let attacks = magic::get_rook_attacks(square.index(), occupied);
+    let blockers = blockers & attacks;
+    attacks ^ magic::get_rook_attacks(square.index(), occupied ^ blockers)
+}
+
+/// Like `xray_rook_attacks`, along the diagonals
+pub fn xray_bishop_attacks(square: Square, occupied: Bitboard, blockers: Bitboard) -> Bitboard {
+    let attacks = magic::get_bishop_attacks(square.index(), occupied);
+    let blockers = blockers & attacks;
+    attacks ^ magic::get_bishop_attacks(square.index(), occupied ^ blockers)
+}
+
 
 pub fn generate_bishop_moves(board: &Board, color: Color, from: Square) -> Bitboard {
     magic::get_bishop_attacks(from.index(), board.occupied) & !board.color_bitboard(color)
@@ -218,7 +516,7 @@ fn compute_knight_attacks(square: u8) -> Bitboard {
         let new_rank = rank as i8 + dr;
         let new_file = file as i8 + df;
 
-        if new_rank >= 0 && new_rank < 8 && new_file >= 0 && new_file < 8 {
+        if (0..8).contains(&new_rank) && (0..8).contains(&new_file) {
             attacks.0 |= 1u64 << (new_rank * 8 + new_file);
         }
     }
@@ -242,7 +540,7 @@ fn compute_king_attacks(square: u8) -> Bitboard {
             let new_rank = rank as i8 + dr;
             let new_file = file as i8 + df;
 
-            if new_rank >= 0 && new_rank < 8 && new_file >= 0 && new_file < 8 {
+            if (0..8).contains(&new_rank) && (0..8).contains(&new_file) {
                 attacks.0 |= 1u64 << (new_rank * 8 + new_file);
             }
         }
@@ -263,6 +561,7 @@ fn is_promotion_rank(square: u8, color: Color) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::position::Position;
 
     #[test]
     fn test_knight_attacks() {
@@ -327,4 +626,179 @@ mod tests {
             assert_ne!(mv.from(), mv.to());
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pinned_piece_cannot_move() {
+        // The e2 knight is pinned against the king by the e8 rook
+        let pos = Position::from_fen("k3r3/8/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        let moves = pos.generate_moves();
+        assert!(moves.iter().all(|mv| mv.piece_type() == PieceType::King));
+        // The king may not step along the rook's file either
+        assert!(moves.iter().all(|mv| mv.to().file() != 4));
+    }
+
+    #[test]
+    fn test_castling() {
+        let pos = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let castles = pos.generate_moves().into_iter().filter(|mv| mv.is_castling()).count();
+        assert_eq!(castles, 2);
+
+        // The f1 square is covered by the f8 rook, and b1 being attacked does not matter
+        let pos = Position::from_fen("1r2kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let castles: Vec<String> = pos
+            .generate_moves()
+            .into_iter()
+            .filter(|mv| mv.is_castling())
+            .map(|mv| mv.to_algebraic())
+            .collect();
+        assert_eq!(castles, vec!["e1c1"]);
+
+        // No castling out of check
+        let pos = Position::from_fen("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1").unwrap();
+        assert!(!pos.generate_moves().iter().any(|mv| mv.is_castling()));
+    }
+
+    #[test]
+    fn test_checkmate_has_no_legal_moves() {
+        let pos = Position::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert!(pos.generate_moves().is_empty());
+        assert!(pos.is_checkmate());
+    }
+
+    #[test]
+    fn test_en_passant() {
+        let pos = Position::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let captures: Vec<Move> = pos
+            .generate_moves()
+            .into_iter()
+            .filter(|mv| mv.is_en_passant())
+            .collect();
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0].to_algebraic(), "e5d6");
+
+        // Both pawns leave the fifth rank, exposing the king to the h5 rook
+        let pos = Position::from_fen("8/8/8/K2pP2r/8/8/8/4k3 w - d6 0 1").unwrap();
+        assert!(!pos.generate_moves().iter().any(|mv| mv.is_en_passant()));
+
+        // A pawn pinned on the diagonal may still capture along it
+        let pos = Position::from_fen("4k3/2b5/8/3pP3/8/8/7K/8 w - d6 0 1").unwrap();
+        assert!(pos.generate_moves().iter().any(|mv| mv.is_en_passant()));
+    }
+
+    #[test]
+    fn test_pins_and_discovered_checkers() {
+        // The e2 knight is pinned by the e8 rook; the b2 bishop screens the a1 queen
+        // from the h8 king
+        let pos = Position::from_fen("4r2k/8/8/8/8/8/1B2N3/Q3K3 w - - 0 1").unwrap();
+        let square = |name| Bitboard::from_square(Square::from_algebraic(name).unwrap().index());
+        assert_eq!(pinned_pieces(&pos.board, Color::White), square("e2"));
+        assert_eq!(discovered_checkers(&pos.board, Color::White), square("b2"));
+        assert!(pinned_pieces(&pos.board, Color::Black).is_empty());
+        assert!(discovered_checkers(&pos.board, Color::Black).is_empty());
+
+        // Two pieces on the line make neither of them pinned
+        let pos = Position::from_fen("4r2k/8/8/8/4P3/8/4N3/4K3 w - - 0 1").unwrap();
+        assert!(pinned_pieces(&pos.board, Color::White).is_empty());
+    }
+
+    #[test]
+    fn test_evasions() {
+        // Evasions must match filtering the full move list for legality
+        for fen in [
+            // Single check from the b4 bishop, blocked on c3 or d2
+            "rnbqk1nr/pppp1ppp/8/4p3/1b1P4/8/PPP1PPPP/RNBQKBNR w KQkq - 1 3",
+            // Double check: only king moves
+            "4r1k1/8/8/8/8/3n4/8/4K3 w - - 0 1",
+            // The d5 pawn gives check and can be taken en passant
+            "8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1",
+        ] {
+            let pos = Position::from_fen(fen).unwrap();
+            let color = pos.side_to_move;
+            let mut expected: Vec<Move> = generate_moves(&pos.board, color)
+                .into_iter()
+                .chain(generate_en_passant_moves(&pos.board, color, pos.en_passant))
+                .filter(|mv| is_legal(&pos.board, color, mv))
+                .collect();
+            let mut evasions = pos.generate_moves();
+            let key = |mv: &Move| mv.to_bits();
+            expected.sort_by_key(key);
+            evasions.sort_by_key(key);
+            assert_eq!(evasions.into_vec(), expected, "{fen}");
+        }
+
+        let pos = Position::from_fen("4r1k1/8/8/8/8/3n4/8/4K3 w - - 0 1").unwrap();
+        let evasions = generate_evasions(&pos.board, Color::White, None);
+        assert!(evasions.iter().all(|mv| mv.piece_type() == PieceType::King));
+    }
+
+    #[test]
+    fn test_captures_and_quiets_partition_moves() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        for fen in [fen, "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1"] {
+            let pos = Position::from_fen(fen).unwrap();
+            let captures = pos.generate_captures();
+            let quiets = pos.generate_quiets();
+            assert!(captures.iter().all(|mv| mv.is_capture(&pos.board)));
+            assert!(!quiets.iter().any(|mv| mv.is_capture(&pos.board)));
+
+            let bits = |moves: &mut dyn Iterator<Item = &Move>| {
+                let mut bits: Vec<u32> = moves.map(|mv| mv.to_bits()).collect();
+                bits.sort_unstable();
+                bits
+            };
+            let all = pos.generate_moves();
+            assert_eq!(bits(&mut captures.iter().chain(&quiets)), bits(&mut all.iter()), "{fen}");
+        }
+    }
+
+    #[test]
+    fn test_xray_attacks() {
+        let board = Position::from_fen("3qk3/8/8/3p4/8/8/3Q2p1/3RKB2 w - - 0 1").unwrap().board;
+        let squares = |names: &[&str]| {
+            names.iter().fold(Bitboard::empty(), |bb, name| {
+                bb | Square::from_algebraic(name).unwrap().bitboard()
+            })
+        };
+        let white = board.color_bitboard(Color::White);
+
+        // The rook sees through its own queen up the d-file to the d5 pawn
+        let queens = board.piece_bitboard(Color::White, PieceType::Queen);
+        let xray = xray_rook_attacks(Square::D1, board.occupied, queens);
+        assert_eq!(xray, squares(&["d3", "d4", "d5"]));
+        // Only the first blocker in each direction is seen through, the king as well
+        let xray = xray_rook_attacks(Square::D1, board.occupied, white);
+        assert_eq!(xray, squares(&["d3", "d4", "d5", "f1"]));
+        // Nothing beyond the g2 pawn, which is not a blocker here
+        assert!(xray_bishop_attacks(Square::F1, board.occupied, white).is_empty());
+        let xray = xray_bishop_attacks(Square::F1, board.occupied, board.occupied);
+        assert_eq!(xray, squares(&["h3"]));
+    }
+
+    #[test]
+    fn test_has_legal_move() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            // Checkmate and stalemate
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+            // En passant would expose the king along the rank, but the king can move
+            "8/8/8/KPp4r/8/8/8/7k w - c6 0 1",
+            // The only legal move is en passant
+            "7k/5Q2/8/8/3pP3/3B4/8/K7 b - e3 0 1",
+        ];
+        for fen in fens {
+            let pos = Position::from_fen(fen).unwrap();
+            let (board, color) = (&pos.board, pos.side_to_move);
+            let expected = !pos.generate_moves().is_empty();
+            assert_eq!(
+                has_legal_move(board, color, &pos.castling_rights, pos.en_passant),
+                expected,
+                "{fen}"
+            );
+            assert_eq!(pos.has_legal_move(), expected, "{fen}");
+        }
+        let only_en_passant = Position::from_fen(fens[4]).unwrap();
+        assert_eq!(only_en_passant.legal_move_count(), 1);
+        assert_eq!(Position::new().legal_move_count(), 20);
+    }
+}
@@ -1,5 +1,8 @@
 
-use crate::board::{Color, PieceType, Square};
+use crate::{
+    board::{Color, PieceType, Square},
+    position::Position,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Move {
@@ -39,6 +42,16 @@ impl Move {
         Self { data }
     }
 
+    /// Raw packed representation, e.g. for storage in hash tables
+    pub fn to_bits(self) -> u32 {
+        self.data
+    }
+
+    /// Rebuild a move from `to_bits` output
+    pub const fn from_bits(bits: u32) -> Self {
+        Self { data: bits }
+    }
+
     /// Get the source square
     pub fn from(self) -> Square {
         Square((self.data & 0x3F) as u8)
@@ -119,6 +132,24 @@ impl Move {
         format!("{}{}", from, to)
     }
 
+    /// The legal move that turns `before` into `after`, found by where the pieces
+    /// stand and whose turn it is, so castling, en passant and promotions come back
+    /// from two FENs alone
+    pub fn infer(before: &Position, after: &Position) -> Option<Self> {
+        if after.side_to_move != before.side_to_move.opposite() {
+            return None;
+        }
+        let mut position = before.clone();
+        before.generate_moves().into_iter().find(|mv| {
+            if position.make_move(mv).is_err() {
+                return false;
+            }
+            let reached = position.board.pieces == after.board.pieces;
+            position.undo_move().expect("Move was just made");
+            reached
+        })
+    }
+
     pub fn from_algebraic(s: &str, piece_type: PieceType) -> Option<Self> {
         if s.len() < 4 {
             return None;
@@ -153,6 +184,212 @@ impl std::fmt::Display for Move {
     }
 }
 
+/// Why a move cannot be played, for errors a GUI or the UCI layer can act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalMoveReason {
+    /// The from-square is empty
+    NoPiece(Square),
+    /// The piece on the from-square belongs to the opponent
+    WrongColor(Square),
+    /// The move names a different piece type from the one on its from-square
+    PieceMismatch(Square),
+    /// The destination holds a piece of the side to move
+    OwnPieceOnTarget(Square),
+    /// The piece does not move that way, or something stands in its path
+    Unreachable { from: Square, to: Square },
+    /// A pawn reaching the last rank without naming its promotion piece
+    MissingPromotion,
+    /// A promotion by a move not reaching the last rank, or to a pawn or king
+    InvalidPromotion,
+    /// No pawn can be taken en passant on the square
+    NoEnPassant(Square),
+    /// The right to castle on that side has been lost
+    NoCastlingRight,
+    /// Pieces stand between the king and rook, or on their destinations
+    CastlingBlocked,
+    /// The king is in check
+    CastlingOutOfCheck,
+    /// The king would cross or land on an attacked square
+    CastlingThroughCheck,
+    /// The king would be in check after the move
+    LeavesKingInCheck,
+    /// Legal in standard chess but not under the position's rules
+    ForbiddenByRules,
+}
+
+impl std::fmt::Display for IllegalMoveReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoPiece(square) => write!(f, "no piece on {square}"),
+            Self::WrongColor(square) => write!(f, "the piece on {square} is the opponent's"),
+            Self::PieceMismatch(square) => {
+                write!(f, "the piece on {square} is not the piece that moves")
+            }
+            Self::OwnPieceOnTarget(square) => write!(f, "{square} is occupied by an own piece"),
+            Self::Unreachable { from, to } => write!(f, "the piece on {from} cannot reach {to}"),
+            Self::MissingPromotion => write!(f, "a pawn reaching the last rank must promote"),
+            Self::InvalidPromotion => write!(
+                f,
+                "only a pawn reaching the last rank promotes, to a knight, bishop, rook or queen"
+            ),
+            Self::NoEnPassant(square) => write!(f, "no en passant capture on {square}"),
+            Self::NoCastlingRight => write!(f, "the right to castle on that side is lost"),
+            Self::CastlingBlocked => write!(f, "pieces stand in the way of castling"),
+            Self::CastlingOutOfCheck => write!(f, "cannot castle out of check"),
+            Self::CastlingThroughCheck => write!(f, "the king would castle through check"),
+            Self::LeavesKingInCheck => write!(f, "the king would be left in check"),
+            Self::ForbiddenByRules => write!(f, "not allowed by the rules of this variant"),
+        }
+    }
+}
+
+/// Most moves a generator can produce for one position; the record is 218
+pub const MAX_MOVES: usize = 256;
+
+/// Fixed-capacity list of moves kept on the stack, so generating moves never
+/// allocates. It derefs to a slice for iteration and in-place sorting.
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub const fn new() -> Self {
+        Self {
+            moves: [Move::from_bits(0); MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    /// Append a move; panics beyond `MAX_MOVES`, which no legal position reaches
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+
+    /// Keep only the moves for which `keep` returns true, in their original order
+    pub fn retain(&mut self, mut keep: impl FnMut(&Move) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            if keep(&self.moves[i]) {
+                self.moves[kept] = self.moves[i];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Copy the moves into a `Vec`, for callers that need to own them
+    pub fn into_vec(self) -> Vec<Move> {
+        self.to_vec()
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::ops::Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+
+impl std::ops::DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl std::fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for MoveList {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for MoveList {}
+
+impl Extend<Move> for MoveList {
+    fn extend<I: IntoIterator<Item = Move>>(&mut self, moves: I) {
+        for mv in moves {
+            self.push(mv);
+        }
+    }
+}
+
+impl FromIterator<Move> for MoveList {
+    fn from_iter<I: IntoIterator<Item = Move>>(moves: I) -> Self {
+        let mut list = Self::new();
+        list.extend(moves);
+        list
+    }
+}
+
+impl From<MoveList> for Vec<Move> {
+    fn from(list: MoveList) -> Self {
+        list.into_vec()
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = MoveListIter;
+
+    fn into_iter(self) -> MoveListIter {
+        MoveListIter {
+            list: self,
+            next: 0,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Owning iterator over a `MoveList`
+pub struct MoveListIter {
+    list: MoveList,
+    next: usize,
+}
+
+impl Iterator for MoveListIter {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        let mv = self.list.get(self.next).copied()?;
+        self.next += 1;
+        Some(mv)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.list.len - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for MoveListIter {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,4 +456,51 @@ mod tests {
         let promotion = Move::new_promotion(from, to, PieceType::Pawn, PieceType::Queen);
         assert_eq!(promotion.to_algebraic(), "e2e4q");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_infer_move() {
+        let infer = |before: &str, after: &str| {
+            let before = Position::from_fen(before).unwrap();
+            Move::infer(&before, &Position::from_fen(after).unwrap())
+        };
+
+        let mv = infer(
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1",
+        )
+        .unwrap();
+        assert!(mv.is_castling());
+        assert_eq!(mv.to_algebraic(), "e1g1");
+
+        // En passant: the d5 pawn disappears from a square the e5 pawn never reached
+        let mv = infer("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", "4k3/8/3P4/8/8/8/8/4K3 b - - 0 2");
+        assert!(mv.unwrap().is_en_passant());
+
+        let mv = infer("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "N3k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(mv.unwrap().promotion_piece(), Some(PieceType::Knight));
+
+        // Two moves apart, or the same side to move
+        let start = Position::new().to_fen();
+        let after_two = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+        assert!(infer(&start, after_two).is_none());
+        assert!(infer(&start, &start).is_none());
+    }
+
+    #[test]
+    fn test_move_list() {
+        let a = Move::new(Square(12), Square(28), PieceType::Pawn);
+        let b = Move::new(Square(6), Square(21), PieceType::Knight);
+        let mut list = MoveList::new();
+        assert!(list.is_empty());
+        list.push(a);
+        list.extend([b, a]);
+        assert_eq!(list.len(), 3);
+
+        list.retain(|mv| mv.piece_type() == PieceType::Pawn);
+        assert_eq!(list.into_vec(), vec![a, a]);
+
+        let mut list: MoveList = [a, b].into_iter().collect();
+        list.sort_by_key(|mv| mv.from().index());
+        assert_eq!(list.into_iter().collect::<Vec<_>>(), vec![b, a]);
+    }
+}
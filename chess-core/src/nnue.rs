@@ -0,0 +1,250 @@
+//! Efficiently updatable neural network (NNUE) evaluation.
+//!
+//! The network is a single hidden layer over 768 piece-square inputs, seen from
+//! both sides' perspectives: `768 -> HIDDEN (x2) -> 1` with a clipped ReLU. The
+//! hidden layer sums live in an `Accumulator` that `Position` updates as pieces
+//! are placed and removed, so an evaluation only has to run the output layer.
+
+use crate::{
+    board::{Board, Color, Piece},
+    Error, Result,
+};
+
+/// Number of input features: colour x piece type x square
+pub const INPUTS: usize = 768;
+
+/// Width of the hidden layer for each perspective
+pub const HIDDEN: usize = 128;
+
+/// Clipping bound of the hidden activations
+const QA: i32 = 255;
+
+/// Quantisation of the output weights
+const QB: i32 = 64;
+
+/// Centipawns per unit of network output
+const SCALE: i32 = 400;
+
+/// Quantised network weights
+#[derive(Clone)]
+pub struct Network {
+    feature_weights: Vec<[i16; HIDDEN]>,
+    feature_bias: [i16; HIDDEN],
+    /// Weights for the side to move's half followed by the opponent's half
+    output_weights: [i16; 2 * HIDDEN],
+    output_bias: i16,
+}
+
+impl Network {
+    /// Size of a network file in bytes
+    pub const FILE_SIZE: usize = 2 * (INPUTS * HIDDEN + HIDDEN + 2 * HIDDEN + 1);
+
+    /// A network with every weight zero, which evaluates everything as equal
+    pub fn zeroed() -> Self {
+        Self {
+            feature_weights: vec![[0; HIDDEN]; INPUTS],
+            feature_bias: [0; HIDDEN],
+            output_weights: [0; 2 * HIDDEN],
+            output_bias: 0,
+        }
+    }
+
+    /// Parse a network from little-endian `i16` values in the order: feature
+    /// weights (input-major), feature biases, output weights, output bias
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::FILE_SIZE {
+            return Err(Error::InvalidNetwork(format!(
+                "expected {} bytes, found {}",
+                Self::FILE_SIZE,
+                bytes.len()
+            )));
+        }
+
+        let mut values = bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+        let mut next = || values.next().expect("length was checked");
+
+        let mut network = Self::zeroed();
+        for weights in network.feature_weights.iter_mut() {
+            weights.iter_mut().for_each(|w| *w = next());
+        }
+        network.feature_bias.iter_mut().for_each(|b| *b = next());
+        network.output_weights.iter_mut().for_each(|w| *w = next());
+        network.output_bias = next();
+        Ok(network)
+    }
+
+    /// Serialise in the format read by `from_bytes`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let values = self
+            .feature_weights
+            .iter()
+            .flatten()
+            .chain(&self.feature_bias)
+            .chain(&self.output_weights)
+            .chain(std::iter::once(&self.output_bias));
+        values.flat_map(|value| value.to_le_bytes()).collect()
+    }
+
+    /// Load a network file from disk
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| Error::InvalidNetwork(e.to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Set the hidden-layer weights of one input feature
+    pub fn set_feature_weights(&mut self, feature: usize, weights: [i16; HIDDEN]) {
+        self.feature_weights[feature] = weights;
+    }
+
+    /// Set the output weights for the side to move's and the opponent's halves
+    pub fn set_output_weights(&mut self, weights: [i16; 2 * HIDDEN]) {
+        self.output_weights = weights;
+    }
+
+    /// Score from the side to move's point of view
+    pub fn evaluate(&self, accumulator: &Accumulator, side_to_move: Color) -> i32 {
+        let (us, them) = match side_to_move {
+            Color::White => (&accumulator.white, &accumulator.black),
+            Color::Black => (&accumulator.black, &accumulator.white),
+        };
+
+        let mut sum = 0;
+        for (value, weight) in us.iter().chain(them).zip(&self.output_weights) {
+            sum += (*value as i32).clamp(0, QA) * *weight as i32;
+        }
+        (sum + self.output_bias as i32 * QA) * SCALE / (QA * QB)
+    }
+}
+
+impl std::fmt::Debug for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Network")
+            .field("inputs", &INPUTS)
+            .field("hidden", &HIDDEN)
+            .finish()
+    }
+}
+
+/// Input feature of `piece` on `square` from `perspective`'s point of view.
+///
+/// Each side sees the board from its own first rank with its own pieces first,
+/// so the network is shared between both perspectives.
+pub fn feature_index(perspective: Color, piece: Piece, square: u8) -> usize {
+    let (relative_color, relative_square) = match perspective {
+        Color::White => (piece.color as usize, square),
+        Color::Black => (piece.color.opposite() as usize, square ^ 56),
+    };
+    relative_color * 384 + piece.piece_type as usize * 64 + relative_square as usize
+}
+
+/// Hidden layer sums for both perspectives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Accumulator {
+    white: [i16; HIDDEN],
+    black: [i16; HIDDEN],
+}
+
+impl Accumulator {
+    /// Compute the sums for every piece on the board from scratch
+    pub fn new(network: &Network, board: &Board) -> Self {
+        let mut accumulator = Self {
+            white: network.feature_bias,
+            black: network.feature_bias,
+        };
+        for (square, piece) in board.pieces() {
+            accumulator.add(network, piece, square.0);
+        }
+        accumulator
+    }
+
+    pub fn add(&mut self, network: &Network, piece: Piece, square: u8) {
+        self.update(network, piece, square, 1);
+    }
+
+    pub fn remove(&mut self, network: &Network, piece: Piece, square: u8) {
+        self.update(network, piece, square, -1);
+    }
+
+    fn update(&mut self, network: &Network, piece: Piece, square: u8, sign: i16) {
+        let white = &network.feature_weights[feature_index(Color::White, piece, square)];
+        let black = &network.feature_weights[feature_index(Color::Black, piece, square)];
+        for i in 0..HIDDEN {
+            self.white[i] = self.white[i].wrapping_add(sign.wrapping_mul(white[i]));
+            self.black[i] = self.black[i].wrapping_add(sign.wrapping_mul(black[i]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::PieceType, position::Position};
+    use std::sync::Arc;
+
+    /// A network whose first hidden unit counts queens from each perspective
+    fn queen_network() -> Network {
+        let mut network = Network::zeroed();
+        let mut weights = [0; HIDDEN];
+        weights[0] = 100;
+        for square in 0..64 {
+            let ours = feature_index(
+                Color::White,
+                Piece::new(Color::White, PieceType::Queen),
+                square,
+            );
+            network.set_feature_weights(ours, weights);
+        }
+        let mut output = [0; 2 * HIDDEN];
+        output[0] = QB as i16;
+        output[HIDDEN] = -(QB as i16);
+        network.set_output_weights(output);
+        network
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let network = queen_network();
+        let bytes = network.to_bytes();
+        assert_eq!(bytes.len(), Network::FILE_SIZE);
+
+        let parsed = Network::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.to_bytes(), bytes);
+        assert!(Network::from_bytes(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn test_evaluation_is_symmetric() {
+        let network = Arc::new(queen_network());
+        let mut pos = Position::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        pos.set_network(Some(Arc::clone(&network)));
+        let white = pos.evaluate_nnue().unwrap();
+        assert!(white > 0);
+
+        let mut mirrored = Position::from_fen("3qk3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        mirrored.set_network(Some(network));
+        assert_eq!(mirrored.evaluate_nnue(), Some(white));
+    }
+
+    #[test]
+    fn test_accumulator_is_updated_incrementally() {
+        let network = Arc::new(queen_network());
+        let mut pos = Position::from_fen("4k3/8/8/8/8/8/3q4/3QK3 w - - 0 1").unwrap();
+        pos.set_network(Some(Arc::clone(&network)));
+        let before = pos.evaluate_nnue();
+
+        let capture = pos
+            .generate_moves()
+            .into_iter()
+            .find(|mv| mv.to_algebraic() == "d1d2")
+            .unwrap();
+        pos.make_move(&capture).unwrap();
+        let mut refreshed = pos.clone();
+        refreshed.set_network(Some(network));
+        assert_eq!(pos.evaluate_nnue(), refreshed.evaluate_nnue());
+
+        pos.undo_move().unwrap();
+        assert_eq!(pos.evaluate_nnue(), before);
+    }
+}
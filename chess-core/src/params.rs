@@ -0,0 +1,533 @@
+//! Runtime evaluation parameters.
+//!
+//! `EvalParams` gathers every weight of the classical evaluation. The defaults are
+//! the constants compiled into each evaluation module, and a parameter file can
+//! override any subset of them. Files use a small subset of TOML: one
+//! `name = value` pair per entry, where a value is an integer or a (possibly
+//! nested, possibly multi-line) array of integers, and `#` starts a comment.
+//! A `Score` is written as `[mg, eg]`.
+//!
+//! The scalar weights can also be set one at a time by name, which is how external
+//! SPSA tuners reach them through hidden UCI options.
+
+use crate::{
+    coordination,
+    evaluate::{self, Score},
+    imbalance, initiative, king_activity, king_safety, majority, pawns, pieces, space, threats,
+    weak_squares, Error, Result,
+};
+use std::sync::Arc;
+
+/// Smallest half-width of the range a tuner may explore around a default weight
+const TUNABLE_SPAN: i32 = 100;
+
+/// A single integer parameter exposed to external tuners
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tunable {
+    pub name: String,
+    pub value: i32,
+    pub min: i32,
+    pub max: i32,
+}
+
+impl Tunable {
+    pub fn new(name: impl Into<String>, value: i32, min: i32, max: i32) -> Self {
+        Self {
+            name: name.into(),
+            value,
+            min,
+            max,
+        }
+    }
+}
+
+/// Every tunable weight of the classical evaluation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalParams {
+    /// Piece values, pawn to king
+    pub piece_values: [i32; 6],
+    pub pawn_table: [i32; 64],
+    pub knight_table: [i32; 64],
+    pub bishop_table: [i32; 64],
+    pub rook_table: [i32; 64],
+    pub queen_table: [i32; 64],
+    pub king_table: [i32; 64],
+    pub doubled_pawn_penalty: i32,
+    pub isolated_pawn_penalty: i32,
+    pub backward_pawn_penalty: i32,
+    pub passed_pawn_bonus: [i32; 8],
+    pub candidate_passer_bonus: [i32; 8],
+    pub connected_passer_bonus: i32,
+    pub bishop_pair_bonus: i32,
+    pub knight_closed_bonus: i32,
+    pub bishop_closed_penalty: i32,
+    pub imbalance_ours: [[i32; 5]; 5],
+    pub imbalance_theirs: [[i32; 5]; 5],
+    pub rook_open_file_bonus: i32,
+    pub rook_semi_open_file_bonus: i32,
+    pub rook_seventh_rank_bonus: i32,
+    pub rook_behind_passer_bonus: i32,
+    pub bad_bishop_penalty: i32,
+    pub bad_bishop_centre_penalty: i32,
+    pub tropism_weights: [Score; 4],
+    pub trapped_bishop_penalty: i32,
+    pub trapped_knight_penalty: i32,
+    pub trapped_rook_penalty: i32,
+    pub space_scale: i32,
+    pub hanging_penalty: [i32; 5],
+    pub loose_penalty: [i32; 5],
+    pub storm_bonus: [Score; 8],
+    pub shelter_advance_penalty: Score,
+    pub hole_occupied_penalty: [i32; 6],
+    pub weak_complex_penalty: i32,
+    pub king_centralisation: Score,
+    pub king_passer_proximity: Score,
+    pub opposition_bonus: Score,
+    pub doubled_rooks_bonus: Score,
+    pub battery_bonus: Score,
+    pub open_line_bonus: Score,
+    pub initiative_bonus: Score,
+    pub pawn_majority_bonus: Score,
+    pub outside_passer_bonus: Score,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            piece_values: evaluate::PIECE_VALUES,
+            pawn_table: evaluate::PAWN_TABLE,
+            knight_table: evaluate::KNIGHT_TABLE,
+            bishop_table: evaluate::BISHOP_TABLE,
+            rook_table: evaluate::ROOK_TABLE,
+            queen_table: evaluate::QUEEN_TABLE,
+            king_table: evaluate::KING_TABLE,
+            doubled_pawn_penalty: pawns::DOUBLED_PAWN_PENALTY,
+            isolated_pawn_penalty: pawns::ISOLATED_PAWN_PENALTY,
+            backward_pawn_penalty: pawns::BACKWARD_PAWN_PENALTY,
+            passed_pawn_bonus: pawns::PASSED_PAWN_BONUS,
+            candidate_passer_bonus: pawns::CANDIDATE_PASSER_BONUS,
+            connected_passer_bonus: pawns::CONNECTED_PASSER_BONUS,
+            bishop_pair_bonus: imbalance::BISHOP_PAIR_BONUS,
+            knight_closed_bonus: imbalance::KNIGHT_CLOSED_BONUS,
+            bishop_closed_penalty: imbalance::BISHOP_CLOSED_PENALTY,
+            imbalance_ours: imbalance::IMBALANCE_OURS,
+            imbalance_theirs: imbalance::IMBALANCE_THEIRS,
+            rook_open_file_bonus: pieces::ROOK_OPEN_FILE_BONUS,
+            rook_semi_open_file_bonus: pieces::ROOK_SEMI_OPEN_FILE_BONUS,
+            rook_seventh_rank_bonus: pieces::ROOK_SEVENTH_RANK_BONUS,
+            rook_behind_passer_bonus: pieces::ROOK_BEHIND_PASSER_BONUS,
+            bad_bishop_penalty: pieces::BAD_BISHOP_PENALTY,
+            bad_bishop_centre_penalty: pieces::BAD_BISHOP_CENTRE_PENALTY,
+            tropism_weights: pieces::TROPISM_WEIGHTS,
+            trapped_bishop_penalty: pieces::TRAPPED_BISHOP_PENALTY,
+            trapped_knight_penalty: pieces::TRAPPED_KNIGHT_PENALTY,
+            trapped_rook_penalty: pieces::TRAPPED_ROOK_PENALTY,
+            space_scale: space::SPACE_SCALE,
+            hanging_penalty: threats::HANGING_PENALTY,
+            loose_penalty: threats::LOOSE_PENALTY,
+            storm_bonus: king_safety::STORM_BONUS,
+            shelter_advance_penalty: king_safety::SHELTER_ADVANCE_PENALTY,
+            hole_occupied_penalty: weak_squares::HOLE_OCCUPIED_PENALTY,
+            weak_complex_penalty: weak_squares::WEAK_COMPLEX_PENALTY,
+            king_centralisation: king_activity::KING_CENTRALISATION,
+            king_passer_proximity: king_activity::KING_PASSER_PROXIMITY,
+            opposition_bonus: king_activity::OPPOSITION_BONUS,
+            doubled_rooks_bonus: coordination::DOUBLED_ROOKS_BONUS,
+            battery_bonus: coordination::BATTERY_BONUS,
+            open_line_bonus: coordination::OPEN_LINE_BONUS,
+            initiative_bonus: initiative::INITIATIVE_BONUS,
+            pawn_majority_bonus: majority::PAWN_MAJORITY_BONUS,
+            outside_passer_bonus: majority::OUTSIDE_PASSER_BONUS,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref DEFAULT_PARAMS: Arc<EvalParams> = Arc::new(EvalParams::default());
+}
+
+impl EvalParams {
+    /// The default parameters, shared between all positions that use them
+    pub fn shared_default() -> Arc<EvalParams> {
+        Arc::clone(&DEFAULT_PARAMS)
+    }
+
+    /// Visit every parameter with its name, in file order
+    fn for_each_mut(&mut self, mut visit: impl FnMut(&'static str, &mut dyn Param)) {
+        visit("piece_values", &mut self.piece_values);
+        visit("pawn_table", &mut self.pawn_table);
+        visit("knight_table", &mut self.knight_table);
+        visit("bishop_table", &mut self.bishop_table);
+        visit("rook_table", &mut self.rook_table);
+        visit("queen_table", &mut self.queen_table);
+        visit("king_table", &mut self.king_table);
+        visit("doubled_pawn_penalty", &mut self.doubled_pawn_penalty);
+        visit("isolated_pawn_penalty", &mut self.isolated_pawn_penalty);
+        visit("backward_pawn_penalty", &mut self.backward_pawn_penalty);
+        visit("passed_pawn_bonus", &mut self.passed_pawn_bonus);
+        visit("candidate_passer_bonus", &mut self.candidate_passer_bonus);
+        visit("connected_passer_bonus", &mut self.connected_passer_bonus);
+        visit("bishop_pair_bonus", &mut self.bishop_pair_bonus);
+        visit("knight_closed_bonus", &mut self.knight_closed_bonus);
+        visit("bishop_closed_penalty", &mut self.bishop_closed_penalty);
+        visit("imbalance_ours", &mut self.imbalance_ours);
+        visit("imbalance_theirs", &mut self.imbalance_theirs);
+        visit("rook_open_file_bonus", &mut self.rook_open_file_bonus);
+        visit(
+            "rook_semi_open_file_bonus",
+            &mut self.rook_semi_open_file_bonus,
+        );
+        visit("rook_seventh_rank_bonus", &mut self.rook_seventh_rank_bonus);
+        visit(
+            "rook_behind_passer_bonus",
+            &mut self.rook_behind_passer_bonus,
+        );
+        visit("bad_bishop_penalty", &mut self.bad_bishop_penalty);
+        visit(
+            "bad_bishop_centre_penalty",
+            &mut self.bad_bishop_centre_penalty,
+        );
+        visit("tropism_weights", &mut self.tropism_weights);
+        visit("trapped_bishop_penalty", &mut self.trapped_bishop_penalty);
+        visit("trapped_knight_penalty", &mut self.trapped_knight_penalty);
+        visit("trapped_rook_penalty", &mut self.trapped_rook_penalty);
+        visit("space_scale", &mut self.space_scale);
+        visit("hanging_penalty", &mut self.hanging_penalty);
+        visit("loose_penalty", &mut self.loose_penalty);
+        visit("storm_bonus", &mut self.storm_bonus);
+        visit("shelter_advance_penalty", &mut self.shelter_advance_penalty);
+        visit("hole_occupied_penalty", &mut self.hole_occupied_penalty);
+        visit("weak_complex_penalty", &mut self.weak_complex_penalty);
+        visit("king_centralisation", &mut self.king_centralisation);
+        visit("king_passer_proximity", &mut self.king_passer_proximity);
+        visit("opposition_bonus", &mut self.opposition_bonus);
+        visit("doubled_rooks_bonus", &mut self.doubled_rooks_bonus);
+        visit("battery_bonus", &mut self.battery_bonus);
+        visit("open_line_bonus", &mut self.open_line_bonus);
+        visit("initiative_bonus", &mut self.initiative_bonus);
+        visit("pawn_majority_bonus", &mut self.pawn_majority_bonus);
+        visit("outside_passer_bonus", &mut self.outside_passer_bonus);
+    }
+
+    /// Visit every scalar weight, with `Score` halves named `<name>_mg` and `<name>_eg`
+    fn for_each_scalar(&mut self, mut visit: impl FnMut(String, &mut i32)) {
+        self.for_each_mut(|name, param| param.scalars(name, &mut visit));
+    }
+
+    /// Scalar weights with their current values. Ranges are centred on the
+    /// defaults so they stay put while a tuner moves the values.
+    pub fn tunables(&self) -> Vec<Tunable> {
+        let mut defaults = Vec::new();
+        Self::default().for_each_scalar(|_, value| defaults.push(*value));
+
+        let mut tunables: Vec<Tunable> = Vec::new();
+        self.clone().for_each_scalar(|name, value| {
+            let span = (defaults[tunables.len()].abs() * 2).max(TUNABLE_SPAN);
+            tunables.push(Tunable::new(name, *value, -span, span));
+        });
+        tunables
+    }
+
+    /// Set the scalar weight called `name`, reporting whether it exists
+    pub fn set_tunable(&mut self, name: &str, value: i32) -> bool {
+        let mut found = false;
+        self.for_each_scalar(|field, slot| {
+            if field == name {
+                *slot = value;
+                found = true;
+            }
+        });
+        found
+    }
+
+    /// Piece-square table of `index`, pawn to king
+    pub fn table(&self, index: usize) -> &[i32; 64] {
+        match index {
+            0 => &self.pawn_table,
+            1 => &self.knight_table,
+            2 => &self.bishop_table,
+            3 => &self.rook_table,
+            4 => &self.queen_table,
+            _ => &self.king_table,
+        }
+    }
+
+    /// Mutable piece-square table of `index`, pawn to king
+    pub fn table_mut(&mut self, index: usize) -> &mut [i32; 64] {
+        match index {
+            0 => &mut self.pawn_table,
+            1 => &mut self.knight_table,
+            2 => &mut self.bishop_table,
+            3 => &mut self.rook_table,
+            4 => &mut self.queen_table,
+            _ => &mut self.king_table,
+        }
+    }
+
+    /// Parse a parameter file; entries it leaves out keep their default values
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let mut params = Self::default();
+        for (name, value) in parse_entries(text)? {
+            let mut found = false;
+            let mut result = Ok(());
+            params.for_each_mut(|field, param| {
+                if field == name {
+                    found = true;
+                    if param.read(&value).is_none() {
+                        result = Err(invalid(format!("wrong shape for {}", name)));
+                    }
+                }
+            });
+            if !found {
+                return Err(invalid(format!("unknown parameter {}", name)));
+            }
+            result?;
+        }
+        Ok(params)
+    }
+
+    /// Render every parameter in the format read by `from_toml`
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        self.clone().for_each_mut(|name, param| {
+            out.push_str(name);
+            out.push_str(" = ");
+            param.write(&mut out);
+            out.push('\n');
+        });
+        out
+    }
+
+    /// Load a parameter file from disk
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        Self::from_toml(&text)
+    }
+
+    /// Write every parameter to a file that `load` reads back
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        std::fs::write(path, self.to_toml()).map_err(|e| invalid(e.to_string()))
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidParams(message)
+}
+
+/// Parsed right-hand side of a parameter entry
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(i32),
+    Array(Vec<Value>),
+}
+
+/// A parameter that can be read from and written as a `Value`
+trait Param {
+    fn read(&mut self, value: &Value) -> Option<()>;
+    fn write(&self, out: &mut String);
+
+    /// Visit the scalar weights of the parameter; tables are only set from files
+    fn scalars(&mut self, _name: &str, _visit: &mut dyn FnMut(String, &mut i32)) {}
+}
+
+impl Param for i32 {
+    fn read(&mut self, value: &Value) -> Option<()> {
+        match value {
+            Value::Int(number) => {
+                *self = *number;
+                Some(())
+            }
+            Value::Array(_) => None,
+        }
+    }
+
+    fn write(&self, out: &mut String) {
+        out.push_str(&self.to_string());
+    }
+
+    fn scalars(&mut self, name: &str, visit: &mut dyn FnMut(String, &mut i32)) {
+        visit(name.to_string(), self);
+    }
+}
+
+impl Param for Score {
+    fn read(&mut self, value: &Value) -> Option<()> {
+        let mut pair = [self.mg, self.eg];
+        pair.read(value)?;
+        *self = Score::new(pair[0], pair[1]);
+        Some(())
+    }
+
+    fn write(&self, out: &mut String) {
+        [self.mg, self.eg].write(out);
+    }
+
+    fn scalars(&mut self, name: &str, visit: &mut dyn FnMut(String, &mut i32)) {
+        visit(format!("{}_mg", name), &mut self.mg);
+        visit(format!("{}_eg", name), &mut self.eg);
+    }
+}
+
+impl<T: Param, const N: usize> Param for [T; N] {
+    fn read(&mut self, value: &Value) -> Option<()> {
+        let Value::Array(items) = value else {
+            return None;
+        };
+        if items.len() != N {
+            return None;
+        }
+        for (slot, item) in self.iter_mut().zip(items) {
+            slot.read(item)?;
+        }
+        Some(())
+    }
+
+    fn write(&self, out: &mut String) {
+        // Long tables are laid out as ranks of eight
+        let rows = N > 8;
+        out.push('[');
+        for (index, item) in self.iter().enumerate() {
+            if rows && index % 8 == 0 {
+                out.push_str("\n    ");
+            } else if index > 0 {
+                out.push(' ');
+            }
+            item.write(out);
+            if index + 1 < N || rows {
+                out.push(',');
+            }
+        }
+        if rows {
+            out.push('\n');
+        }
+        out.push(']');
+    }
+}
+
+/// Split a parameter file into `(name, value)` entries
+fn parse_entries(text: &str) -> Result<Vec<(String, Value)>> {
+    let mut entries = Vec::new();
+    let mut statement = String::new();
+    let mut depth = 0i32;
+
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        depth += line.matches('[').count() as i32 - line.matches(']').count() as i32;
+        statement.push_str(line);
+        statement.push(' ');
+        if depth > 0 {
+            continue;
+        }
+        if depth < 0 {
+            return Err(invalid(format!(
+                "unbalanced brackets in: {}",
+                statement.trim()
+            )));
+        }
+
+        let entry = statement.trim();
+        if !entry.is_empty() {
+            let (name, value) = entry
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("expected name = value, found: {}", entry)))?;
+            let mut tokens = tokenize(value).into_iter().peekable();
+            let value = parse_value(&mut tokens)
+                .filter(|_| tokens.peek().is_none())
+                .ok_or_else(|| invalid(format!("malformed value: {}", value.trim())))?;
+            entries.push((name.trim().to_string(), value));
+        }
+        statement.clear();
+    }
+
+    if depth != 0 {
+        return Err(invalid("unterminated array".to_string()));
+    }
+    Ok(entries)
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let spaced = text
+        .replace('[', " [ ")
+        .replace(']', " ] ")
+        .replace(',', " , ");
+    spaced.split_whitespace().map(str::to_string).collect()
+}
+
+fn parse_value(tokens: &mut std::iter::Peekable<std::vec::IntoIter<String>>) -> Option<Value> {
+    let token = tokens.next()?;
+    if token != "[" {
+        return token.parse().ok().map(Value::Int);
+    }
+
+    let mut items = Vec::new();
+    loop {
+        if tokens.peek()? == "]" {
+            tokens.next();
+            return Some(Value::Array(items));
+        }
+        items.push(parse_value(tokens)?);
+        match tokens.next()?.as_str() {
+            "," => continue,
+            "]" => return Some(Value::Array(items)),
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut params = EvalParams::default();
+        params.piece_values[1] = 325;
+        params.king_table[63] = -7;
+        params.storm_bonus[5] = Score::new(25, 3);
+        params.imbalance_theirs[2][1] = 4;
+
+        let parsed = EvalParams::from_toml(&params.to_toml()).unwrap();
+        assert_eq!(parsed, params);
+    }
+
+    #[test]
+    fn test_partial_file_keeps_defaults() {
+        let text = "# A more aggressive personality\n\
+                    storm_bonus = [[0, 0], [0, 0], [0, 0], [10, 0],\n\
+                                   [20, 0], [40, 0], [60, 0], [0, 0]]\n\
+                    bishop_pair_bonus = 50 # trailing comment\n";
+        let params = EvalParams::from_toml(text).unwrap();
+        assert_eq!(params.bishop_pair_bonus, 50);
+        assert_eq!(params.storm_bonus[6], Score::new(60, 0));
+        assert_eq!(params.piece_values, EvalParams::default().piece_values);
+    }
+
+    #[test]
+    fn test_rejects_bad_files() {
+        assert!(EvalParams::from_toml("no_such_term = 3").is_err());
+        assert!(EvalParams::from_toml("piece_values = [1, 2]").is_err());
+        assert!(EvalParams::from_toml("space_scale = [32]").is_err());
+        assert!(EvalParams::from_toml("passed_pawn_bonus = [0, 5").is_err());
+    }
+
+    #[test]
+    fn test_tunables() {
+        let mut params = EvalParams::default();
+        let find = |params: &EvalParams, name: &str| {
+            params
+                .tunables()
+                .into_iter()
+                .find(|tunable| tunable.name == name)
+        };
+        let before = find(&params, "battery_bonus_eg").unwrap();
+        assert!(find(&params, "bishop_pair_bonus").is_some());
+        assert!(find(&params, "pawn_table").is_none());
+
+        assert!(params.set_tunable("battery_bonus_eg", before.value + 7));
+        assert!(!params.set_tunable("no_such_term", 1));
+        let after = find(&params, "battery_bonus_eg").unwrap();
+        assert_eq!(after.value, before.value + 7);
+        assert_eq!((after.min, after.max), (before.min, before.max));
+        assert_eq!(params.battery_bonus.eg, before.value + 7);
+    }
+}
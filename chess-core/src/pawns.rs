@@ -0,0 +1,419 @@
+//! Pawn structure evaluation
+
+use crate::{
+    bitboard::{adjacent_files, Bitboard, FILES, RANKS},
+    board::{Board, Color, PieceType},
+    params::EvalParams,
+};
+
+/// Penalty for each pawn standing behind another pawn of its colour on the same file
+pub(crate) const DOUBLED_PAWN_PENALTY: i32 = 15;
+
+/// Penalty for a pawn without friendly pawns on the adjacent files
+pub(crate) const ISOLATED_PAWN_PENALTY: i32 = 12;
+
+/// Penalty for a pawn that cannot be supported and whose advance is controlled
+pub(crate) const BACKWARD_PAWN_PENALTY: i32 = 8;
+
+/// Bonus for a passed pawn by relative rank
+pub(crate) const PASSED_PAWN_BONUS: [i32; 8] = [0, 5, 10, 20, 35, 60, 100, 0];
+
+/// Bonus for a candidate passer by relative rank
+pub(crate) const CANDIDATE_PASSER_BONUS: [i32; 8] = [0, 2, 5, 10, 17, 30, 0, 0];
+
+/// Extra bonus for a passed pawn with a passed neighbour beside or diagonally next to it
+pub(crate) const CONNECTED_PASSER_BONUS: i32 = 20;
+
+/// Squares attacked by `pawns` of the given colour
+pub fn pawn_attacks(pawns: Bitboard, color: Color) -> Bitboard {
+    match color {
+        Color::White => pawns.northeast() | pawns.northwest(),
+        Color::Black => pawns.southeast() | pawns.southwest(),
+    }
+}
+
+/// Union of the files containing at least one square of `squares`
+pub fn file_fill(squares: Bitboard) -> Bitboard {
+    let mut fill = squares.0;
+    fill |= fill << 8;
+    fill |= fill << 16;
+    fill |= fill << 32;
+    fill |= fill >> 8;
+    fill |= fill >> 16;
+    fill |= fill >> 32;
+    Bitboard(fill)
+}
+
+/// Files without any pawn
+pub fn open_files(board: &Board) -> Bitboard {
+    let pawns = board.piece_bitboard(Color::White, PieceType::Pawn)
+        | board.piece_bitboard(Color::Black, PieceType::Pawn);
+    !file_fill(pawns)
+}
+
+/// Files without a pawn of `color` but with an enemy pawn
+pub fn semi_open_files(board: &Board, color: Color) -> Bitboard {
+    let ours = board.piece_bitboard(color, PieceType::Pawn);
+    let theirs = board.piece_bitboard(color.opposite(), PieceType::Pawn);
+    !file_fill(ours) & file_fill(theirs)
+}
+
+/// Ranks from `rank` back towards the home rank of `color`, inclusive
+fn ranks_at_or_behind(rank: u8, color: Color) -> Bitboard {
+    match color {
+        Color::White => Bitboard(u64::MAX >> (8 * (7 - rank as u32))),
+        Color::Black => Bitboard(u64::MAX << (8 * rank as u32)),
+    }
+}
+
+/// Ranks strictly behind `rank` from the point of view of `color`
+fn ranks_behind(rank: u8, color: Color) -> Bitboard {
+    match (color, rank) {
+        (Color::White, 0) | (Color::Black, 7) => Bitboard::empty(),
+        (Color::White, _) => ranks_at_or_behind(rank - 1, color),
+        (Color::Black, _) => ranks_at_or_behind(rank + 1, color),
+    }
+}
+
+/// Pawns with another pawn of their colour behind them on the same file
+pub fn doubled_pawns(board: &Board, color: Color) -> Bitboard {
+    let pawns = board.piece_bitboard(color, PieceType::Pawn);
+    let mut doubled = Bitboard::empty();
+    for square in pawns.squares() {
+        let behind = FILES[(square % 8) as usize] & ranks_behind(square / 8, color);
+        if !(pawns & behind).is_empty() {
+            doubled.set_square(square);
+        }
+    }
+    doubled
+}
+
+/// Pawns without friendly pawns on either adjacent file
+pub fn isolated_pawns(board: &Board, color: Color) -> Bitboard {
+    let pawns = board.piece_bitboard(color, PieceType::Pawn);
+    let mut isolated = Bitboard::empty();
+    for square in pawns.squares() {
+        if (pawns & adjacent_files(square % 8)).is_empty() {
+            isolated.set_square(square);
+        }
+    }
+    isolated
+}
+
+/// Pawns that no friendly pawn can support and whose stop square an enemy pawn attacks.
+///
+/// Isolated pawns are not counted again here.
+pub fn backward_pawns(board: &Board, color: Color) -> Bitboard {
+    let pawns = board.piece_bitboard(color, PieceType::Pawn);
+    let enemy_attacks = pawn_attacks(
+        board.piece_bitboard(color.opposite(), PieceType::Pawn),
+        color.opposite(),
+    );
+
+    let mut backward = Bitboard::empty();
+    for square in pawns.squares() {
+        let neighbours = pawns & adjacent_files(square % 8);
+        if neighbours.is_empty() {
+            continue;
+        }
+
+        let supporters = neighbours & ranks_at_or_behind(square / 8, color);
+        let stop = match color {
+            Color::White => Bitboard::from_square(square).north(),
+            Color::Black => Bitboard::from_square(square).south(),
+        };
+        if supporters.is_empty() && !(stop & enemy_attacks).is_empty() {
+            backward.set_square(square);
+        }
+    }
+    backward
+}
+
+/// Pawns with no enemy pawn in front of them on their own or an adjacent file
+pub fn passed_pawns(board: &Board, color: Color) -> Bitboard {
+    let pawns = board.piece_bitboard(color, PieceType::Pawn);
+    let enemy = board.piece_bitboard(color.opposite(), PieceType::Pawn);
+    let mut passed = Bitboard::empty();
+    for square in pawns.squares() {
+        let files = FILES[(square % 8) as usize] | adjacent_files(square % 8);
+        let ahead = !ranks_at_or_behind(square / 8, color);
+        if (enemy & files & ahead).is_empty() {
+            passed.set_square(square);
+        }
+    }
+    passed
+}
+
+/// Pawns whose advance is blocked by an enemy pawn directly in front of them
+pub fn fixed_pawns(board: &Board, color: Color) -> Bitboard {
+    let pawns = board.piece_bitboard(color, PieceType::Pawn);
+    let enemy = board.piece_bitboard(color.opposite(), PieceType::Pawn);
+    match color {
+        Color::White => pawns & enemy.south(),
+        Color::Black => pawns & enemy.north(),
+    }
+}
+
+/// Pawns that are not yet passed but, with no enemy pawn in front of them on their
+/// file, have at least as many friendly helpers beside or behind them as enemy
+/// pawns guarding their path
+pub fn candidate_passers(board: &Board, color: Color) -> Bitboard {
+    let pawns = board.piece_bitboard(color, PieceType::Pawn);
+    let enemy = board.piece_bitboard(color.opposite(), PieceType::Pawn);
+    let passed = passed_pawns(board, color);
+
+    let mut candidates = Bitboard::empty();
+    for square in (pawns & !passed).squares() {
+        let (file, rank) = (square % 8, square / 8);
+        let ahead = !ranks_at_or_behind(rank, color);
+        if !(enemy & FILES[file as usize] & ahead).is_empty() {
+            continue;
+        }
+
+        let sentries = enemy & adjacent_files(file) & ahead;
+        let helpers = pawns & adjacent_files(file) & ranks_at_or_behind(rank, color);
+        if helpers.count() >= sentries.count() {
+            candidates.set_square(square);
+        }
+    }
+    candidates
+}
+
+/// Passed pawns with another passed pawn on an adjacent file at most one rank away
+pub fn connected_passers(board: &Board, color: Color) -> Bitboard {
+    let passed = passed_pawns(board, color);
+    let mut connected = Bitboard::empty();
+    for square in passed.squares() {
+        let near_ranks = RANKS[(square / 8) as usize].north()
+            | RANKS[(square / 8) as usize]
+            | RANKS[(square / 8) as usize].south();
+        if !(passed & adjacent_files(square % 8) & near_ranks).is_empty() {
+            connected.set_square(square);
+        }
+    }
+    connected
+}
+
+/// Squares that a pawn of `color` on one of `pawns` could still attack by advancing
+pub fn attack_span(pawns: Bitboard, color: Color) -> Bitboard {
+    let mut span = Bitboard::empty();
+    for square in pawns.squares() {
+        span |= adjacent_files(square % 8) & !ranks_at_or_behind(square / 8, color);
+    }
+    span
+}
+
+/// Squares on the third and fourth ranks of `color` that its pawns can no longer defend
+pub fn holes(board: &Board, color: Color) -> Bitboard {
+    let camp = match color {
+        Color::White => RANKS[2] | RANKS[3],
+        Color::Black => RANKS[5] | RANKS[4],
+    };
+    camp & !attack_span(board.piece_bitboard(color, PieceType::Pawn), color)
+}
+
+fn relative_rank(square: u8, color: Color) -> usize {
+    match color {
+        Color::White => (square / 8) as usize,
+        Color::Black => 7 - (square / 8) as usize,
+    }
+}
+
+/// Passed, candidate and connected passer bonuses of one side
+pub(crate) fn passer_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let passed = passed_pawns(board, color);
+    let mut score = 0;
+    for square in passed.squares() {
+        score += params.passed_pawn_bonus[relative_rank(square, color)];
+    }
+    for square in candidate_passers(board, color).squares() {
+        score += params.candidate_passer_bonus[relative_rank(square, color)];
+    }
+    score + connected_passers(board, color).count() as i32 * params.connected_passer_bonus
+}
+
+/// Pawn structure score of one side: weakness penalties and passed pawn bonuses
+pub(crate) fn side_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    -(doubled_pawns(board, color).count() as i32 * params.doubled_pawn_penalty
+        + isolated_pawns(board, color).count() as i32 * params.isolated_pawn_penalty
+        + backward_pawns(board, color).count() as i32 * params.backward_pawn_penalty)
+        + passer_score(board, color, params)
+}
+
+/// Pawn structure score from White's point of view
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    side_score(board, Color::White, params) - side_score(board, Color::Black, params)
+}
+
+/// Direct-mapped cache of pawn structure scores keyed by the pawn-only hash
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+    mask: usize,
+}
+
+#[derive(Clone, Copy, Default)]
+struct PawnEntry {
+    key: u64,
+    score: i32,
+}
+
+impl PawnTable {
+    /// Default number of cached pawn structures
+    pub const DEFAULT_ENTRIES: usize = 1 << 14;
+
+    /// Create a table with `entries` slots, rounded up to a power of two
+    pub fn new(entries: usize) -> Self {
+        let entries = entries.max(1).next_power_of_two();
+        Self {
+            entries: vec![PawnEntry::default(); entries],
+            mask: entries - 1,
+        }
+    }
+
+    pub fn probe(&self, key: u64) -> Option<i32> {
+        let entry = self.entries[key as usize & self.mask];
+        (entry.key == key && key != 0).then_some(entry.score)
+    }
+
+    pub fn store(&mut self, key: u64, score: i32) {
+        self.entries[key as usize & self.mask] = PawnEntry { key, score };
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(PawnEntry::default());
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_ENTRIES)
+    }
+}
+
+/// Pawn structure score through the pawn table under the pawn key `key`, computing
+/// it on a miss
+pub fn evaluate_cached(
+    board: &Board,
+    key: u64,
+    params: &EvalParams,
+    table: &mut PawnTable,
+) -> i32 {
+    if let Some(score) = table.probe(key) {
+        return score;
+    }
+
+    let score = evaluate(board, params);
+    table.store(key, score);
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::Square, position::Position, zobrist};
+
+    fn squares(names: &[&str]) -> Bitboard {
+        let mut bb = Bitboard::empty();
+        for name in names {
+            bb.set_square(Square::from_algebraic(name).unwrap().0);
+        }
+        bb
+    }
+
+    #[test]
+    fn test_doubled_and_isolated() {
+        // White: doubled c-pawns, isolated a-pawn; Black: healthy chain
+        let pos = Position::from_fen("4k3/5ppp/8/8/2P5/2P5/P2P4/4K3 w - - 0 1").unwrap();
+        let board = &pos.board;
+
+        assert_eq!(doubled_pawns(board, Color::White), squares(&["c4"]));
+        assert_eq!(isolated_pawns(board, Color::White), squares(&["a2"]));
+        assert!(doubled_pawns(board, Color::Black).is_empty());
+        assert!(isolated_pawns(board, Color::Black).is_empty());
+        assert!(evaluate(board, &EvalParams::default()) < 0);
+    }
+
+    #[test]
+    fn test_backward_pawn() {
+        // The d3 pawn cannot be supported by c4/e4 and d4 is covered by the c5 pawn
+        let pos = Position::from_fen("4k3/8/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1").unwrap();
+
+        assert_eq!(backward_pawns(&pos.board, Color::White), squares(&["d3"]));
+        assert!(backward_pawns(&pos.board, Color::Black).is_empty());
+    }
+
+    #[test]
+    fn test_passed_pawns() {
+        // The a-pawn is free, the d-pawn is held by the e-pawn in front of it
+        let pos = Position::from_fen("4k3/8/4p3/8/3P4/8/P7/4K3 w - - 0 1").unwrap();
+        assert_eq!(passed_pawns(&pos.board, Color::White), squares(&["a2"]));
+        assert!(passed_pawns(&pos.board, Color::Black).is_empty());
+    }
+
+    #[test]
+    fn test_candidate_and_connected_passers() {
+        // The c-pawn faces only the b-pawn and has the d-pawn to help it through
+        let pos = Position::from_fen("4k3/1p6/8/8/2PP4/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(candidate_passers(&pos.board, Color::White), squares(&["c4"]));
+
+        // With a second sentry the majority is gone
+        let pos = Position::from_fen("4k3/1p1p4/8/8/2P5/8/8/4K3 w - - 0 1").unwrap();
+        assert!(candidate_passers(&pos.board, Color::White).is_empty());
+
+        // Side-by-side passers support each other, a distant one does not
+        let pos = Position::from_fen("4k3/8/8/3PP3/8/8/P7/4K3 w - - 0 1").unwrap();
+        assert_eq!(connected_passers(&pos.board, Color::White), squares(&["d5", "e5"]));
+        assert_eq!(
+            passer_score(&pos.board, Color::White, &EvalParams::default()),
+            2 * PASSED_PAWN_BONUS[4] + PASSED_PAWN_BONUS[1] + 2 * CONNECTED_PASSER_BONUS
+        );
+    }
+
+    #[test]
+    fn test_fixed_pawns() {
+        // The e4 and e5 pawns block each other; d4 is free to advance
+        let pos = Position::from_fen("4k3/8/8/4p3/3PP3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(fixed_pawns(&pos.board, Color::White), squares(&["e4"]));
+        assert_eq!(fixed_pawns(&pos.board, Color::Black), squares(&["e5"]));
+    }
+
+    #[test]
+    fn test_holes() {
+        // With c4 and e4 advanced and no d-pawn, d3 and d4 can never be defended
+        let pos =
+            Position::from_fen("4k3/pppppppp/8/8/2P1P3/8/PP3PPP/4K3 w - - 0 1").unwrap();
+        assert_eq!(holes(&pos.board, Color::White), squares(&["d3", "d4"]));
+        assert!(holes(&pos.board, Color::Black).is_empty());
+    }
+
+    #[test]
+    fn test_file_occupancy() {
+        let pos = Position::from_fen("4k3/p7/8/8/8/8/1P6/4K3 w - - 0 1").unwrap();
+        let open = open_files(&pos.board);
+
+        assert!(!open.has_square(Square::from_algebraic("a4").unwrap().0));
+        assert!(open.has_square(Square::from_algebraic("c4").unwrap().0));
+        assert_eq!(semi_open_files(&pos.board, Color::White), FILES[0]);
+        assert_eq!(semi_open_files(&pos.board, Color::Black), FILES[1]);
+    }
+
+    #[test]
+    fn test_pawn_table() {
+        let pos = Position::from_fen("4k3/5ppp/8/8/2P5/2P5/P2P4/4K3 w - - 0 1").unwrap();
+        let key = pos.pawn_key();
+        assert_eq!(key, zobrist::pawn_hash(&pos.board));
+        let mut table = PawnTable::new(64);
+
+        assert_eq!(table.probe(key), None);
+        let score = evaluate_cached(&pos.board, key, &EvalParams::default(), &mut table);
+        assert_eq!(score, evaluate(&pos.board, &EvalParams::default()));
+        assert_eq!(table.probe(key), Some(score));
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        let pos = Position::new();
+        assert_eq!(evaluate(&pos.board, &EvalParams::default()), 0);
+        assert!(backward_pawns(&pos.board, Color::White).is_empty());
+    }
+}
@@ -0,0 +1,475 @@
+//! Portable Game Notation: reading games as tag pairs, a starting position and the
+//! mainline moves, and writing them back. Comments, variations and NAGs are skipped.
+
+use std::io::BufRead;
+
+use crate::{board::Color, moves::Move, position::Position, Error, Result};
+
+/// Movetext tokens that end a game
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// Tags every exported game carries, in this order, with their unknown values
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+/// Longest movetext line written
+const LINE_WIDTH: usize = 80;
+
+/// One game: its tags in file order, the position it starts from and the mainline
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub start: Position,
+    pub moves: Vec<Move>,
+    /// Result token closing the movetext, such as "1-0" or "*"
+    pub result: Option<String>,
+}
+
+impl PgnGame {
+    /// A game of `moves` from `start`, without tags or a recorded result
+    pub fn new(start: Position, moves: Vec<Move>) -> Self {
+        Self {
+            tags: Vec::new(),
+            start,
+            moves,
+            result: None,
+        }
+    }
+
+    /// The game played to reach `position`, read back from its move history
+    pub fn from_position(position: &Position) -> Self {
+        let mut start = position.clone();
+        let mut moves = Vec::with_capacity(position.history.len());
+        while let Some(undo) = start.history.last() {
+            moves.push(undo.mv);
+            start.undo_move().expect("history is not empty");
+        }
+        moves.reverse();
+        Self::new(start, moves)
+    }
+
+    /// Set the tag `name`, replacing any earlier value
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// The result token: the recorded one, else the `Result` tag, else whatever the
+    /// final position decides
+    pub fn result_token(&self) -> String {
+        if let Some(result) = self.result.as_deref().or(self.tag("Result")) {
+            return result.to_string();
+        }
+        match self.final_position().result() {
+            Some(result) => match result.winner() {
+                Some(Color::White) => "1-0",
+                Some(Color::Black) => "0-1",
+                None => "1/2-1/2",
+            },
+            None => "*",
+        }
+        .to_string()
+    }
+
+    /// The game as PGN: the Seven Tag Roster, `SetUp` and `FEN` when the game does
+    /// not start from the standard position, the other tags, then numbered SAN
+    /// moves ending in the result
+    pub fn to_pgn(&self) -> String {
+        let result = self.result_token();
+        let mut tags: Vec<(&str, String)> = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|&(name, unknown)| {
+                let value = match name {
+                    "Result" => result.clone(),
+                    _ => self.tag(name).unwrap_or(unknown).to_string(),
+                };
+                (name, value)
+            })
+            .collect();
+        let start_fen = self.start.to_fen();
+        if start_fen != Position::new().to_fen() {
+            tags.push(("SetUp", "1".to_string()));
+            tags.push(("FEN", start_fen));
+        }
+        for (name, value) in &self.tags {
+            if !tags.iter().any(|(tag, _)| tag == name) {
+                tags.push((name, value.clone()));
+            }
+        }
+
+        let mut pgn = String::new();
+        for (name, value) in tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{name} \"{value}\"]\n"));
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::with_capacity(self.moves.len() * 3 / 2 + 1);
+        let mut position = self.start.clone();
+        for (ply, mv) in self.moves.iter().enumerate() {
+            let number = position.fullmove_number;
+            match position.side_to_move {
+                Color::White => tokens.push(format!("{number}.")),
+                Color::Black if ply == 0 => tokens.push(format!("{number}...")),
+                Color::Black => {}
+            }
+            tokens.push(position.move_to_san(mv));
+            position
+                .make_move(mv)
+                .expect("exported moves must be legal");
+        }
+        tokens.push(result);
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+
+    /// Value of the tag `name`, if present
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The position after the mainline
+    pub fn final_position(&self) -> Position {
+        let mut position = self.start.clone();
+        for mv in &self.moves {
+            position
+                .make_move(mv)
+                .expect("PGN moves are checked when read");
+        }
+        position
+    }
+
+    /// Build a game from its tag pairs and movetext, replaying the moves from the
+    /// `FEN` tag or the standard start
+    fn from_parts(tags: Vec<(String, String)>, movetext: &str) -> Result<Self> {
+        let start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => Position::from_fen(fen)?,
+            None => Position::new(),
+        };
+        let mut position = start.clone();
+        let mut moves = Vec::new();
+        let mut result = None;
+
+        for token in movetext_tokens(movetext)? {
+            if result.is_some() {
+                return Err(Error::InvalidPgn(format!(
+                    "Moves after the result: {token}"
+                )));
+            }
+            if RESULTS.contains(&token) {
+                result = Some(token.to_string());
+                continue;
+            }
+            let mv = position
+                .san_to_move(token)
+                .map_err(|err| Error::InvalidPgn(format!("Ply {}: {err}", moves.len() + 1)))?;
+            position.make_move(&mv)?;
+            moves.push(mv);
+        }
+
+        Ok(Self {
+            tags,
+            start,
+            moves,
+            result,
+        })
+    }
+}
+
+impl std::fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_pgn())
+    }
+}
+
+/// Games read one at a time from a PGN stream
+pub struct PgnReader<R> {
+    lines: std::io::Lines<R>,
+    /// Tag line that began the next game while the previous one was being read
+    pending: Option<String>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            pending: None,
+        }
+    }
+
+    fn next_line(&mut self) -> Option<Result<String>> {
+        if let Some(line) = self.pending.take() {
+            return Some(Ok(line));
+        }
+        self.lines
+            .next()
+            .map(|line| line.map_err(|err| Error::InvalidPgn(err.to_string())))
+    }
+
+    fn read_game(&mut self) -> Option<Result<PgnGame>> {
+        let mut tags = Vec::new();
+        let mut movetext = String::new();
+        let mut in_comment = false;
+
+        while let Some(line) = self.next_line() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err)),
+            };
+            let trimmed = line.trim();
+            if !in_comment && trimmed.starts_with('%') {
+                continue;
+            }
+            if !in_comment && trimmed.starts_with('[') {
+                if !movetext.trim().is_empty() {
+                    self.pending = Some(line);
+                    break;
+                }
+                match parse_tag(trimmed) {
+                    Some(tag) => tags.push(tag),
+                    None => return Some(Err(Error::InvalidPgn(format!("Bad tag: {trimmed}")))),
+                }
+                continue;
+            }
+            in_comment = ends_in_comment(trimmed, in_comment);
+            movetext.push_str(trimmed);
+            movetext.push('\n');
+        }
+
+        if tags.is_empty() && movetext.trim().is_empty() {
+            None
+        } else {
+            Some(PgnGame::from_parts(tags, &movetext))
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_game()
+    }
+}
+
+/// Every game in `text`
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>> {
+    PgnReader::new(text.as_bytes()).collect()
+}
+
+/// Name and value of a `[Name "Value"]` line
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let value = value.replace("\\\"", "\"").replace("\\\\", "\\");
+    Some((name.to_string(), value))
+}
+
+/// Whether a brace comment is still open at the end of `line`
+fn ends_in_comment(line: &str, mut in_comment: bool) -> bool {
+    for c in line.chars() {
+        match c {
+            '}' if in_comment => in_comment = false,
+            '{' if !in_comment => in_comment = true,
+            ';' if !in_comment => break,
+            _ => {}
+        }
+    }
+    in_comment
+}
+
+/// Mainline move and result tokens of `movetext`, without move numbers,
+/// comments, variations or NAGs
+fn movetext_tokens(movetext: &str) -> Result<Vec<&str>> {
+    let mut tokens = Vec::new();
+    let mut variation_depth = 0usize;
+    let mut rest = movetext;
+
+    while let Some(c) = rest.chars().next() {
+        let skip = match c {
+            '{' => rest.find('}').ok_or_else(|| unclosed("comment"))? + 1,
+            ';' => rest.find('\n').unwrap_or(rest.len()),
+            '(' => {
+                variation_depth += 1;
+                1
+            }
+            ')' => {
+                variation_depth = variation_depth
+                    .checked_sub(1)
+                    .ok_or_else(|| Error::InvalidPgn("Unopened variation".to_string()))?;
+                1
+            }
+            c if c.is_whitespace() => c.len_utf8(),
+            _ => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || "{};()".contains(c))
+                    .unwrap_or(rest.len());
+                let token = strip_move_number(&rest[..end]);
+                if variation_depth == 0 && !token.is_empty() && !token.starts_with('$') {
+                    tokens.push(token);
+                }
+                end
+            }
+        };
+        rest = &rest[skip..];
+    }
+
+    if variation_depth > 0 {
+        return Err(unclosed("variation"));
+    }
+    Ok(tokens)
+}
+
+fn unclosed(what: &str) -> Error {
+    Error::InvalidPgn(format!("Unclosed {what}"))
+}
+
+/// `token` without a leading "12." or "12..." move number
+fn strip_move_number(token: &str) -> &str {
+    let digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    if digits.len() < token.len() && digits.starts_with('.') {
+        digits.trim_start_matches('.')
+    } else {
+        token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES: &str = r#"[Event "Casual"]
+[White "Anderssen"]
+[Black "Kieseritzky"]
+[Result "1-0"]
+
+1. e4 e5 2. f4 {King's Gambit} exf4 3. Bc4 Qh4+ (3... Nf6 4. Nc3) 4. Kf1 $1 b5?!
+5. Bxb5 Nf6 6. Nf3 Qh6 1-0
+
+[Event "Fool's mate"]
+[Result "0-1"]
+
+1.f3 e5 2.g4 Qh4# 0-1
+
+[FEN "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"]
+[SetUp "1"]
+
+1. O-O-O Kf7 *
+"#;
+
+    #[test]
+    fn test_read_games() {
+        let games = parse_pgn(GAMES).unwrap();
+        assert_eq!(games.len(), 3);
+
+        assert_eq!(games[0].tag("White"), Some("Anderssen"));
+        assert_eq!(games[0].moves.len(), 12);
+        assert_eq!(games[0].result.as_deref(), Some("1-0"));
+        assert_eq!(games[0].moves[6].to_algebraic(), "e1f1");
+
+        assert_eq!(games[1].moves.len(), 4);
+        assert!(games[1].final_position().is_checkmate());
+
+        assert_eq!(games[2].moves[0].to_algebraic(), "e1c1");
+        assert_eq!(games[2].result.as_deref(), Some("*"));
+        assert_eq!(
+            games[2].final_position().to_fen(),
+            "8/5k2/8/8/8/8/8/2KR4 w - - 2 2"
+        );
+    }
+
+    #[test]
+    fn test_streaming_reader() {
+        let mut reader = PgnReader::new(GAMES.as_bytes());
+        assert_eq!(reader.next().unwrap().unwrap().tag("Event"), Some("Casual"));
+        assert_eq!(
+            reader.next().unwrap().unwrap().tag("Event"),
+            Some("Fool's mate")
+        );
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_invalid_pgn() {
+        assert!(parse_pgn("1. e4 e5 2. Ke3").is_err());
+        assert!(parse_pgn("1. e4 {unclosed").is_err());
+        assert!(parse_pgn("1. e4 (1. d4").is_err());
+        assert!(parse_pgn("1. e4 1-0 e5").is_err());
+        assert!(parse_pgn("[Event \"x\"\n1. e4").is_err());
+    }
+
+    #[test]
+    fn test_write_game() {
+        let mut position = Position::new();
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            let mv = position.san_to_move(san).unwrap();
+            position.make_move(&mv).unwrap();
+        }
+        let mut game = PgnGame::from_position(&position);
+        game.set_tag("White", "Fool");
+        game.set_tag("Opening", "Barnes \"Fool's\" mate");
+
+        let pgn = game.to_pgn();
+        let expected = "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+            [White \"Fool\"]\n[Black \"?\"]\n[Result \"0-1\"]\n\
+            [Opening \"Barnes \\\"Fool's\\\" mate\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n";
+        assert_eq!(pgn, expected);
+
+        let read = &parse_pgn(&pgn).unwrap()[0];
+        assert_eq!(read.moves, game.moves);
+        assert_eq!(read.tag("Opening"), Some("Barnes \"Fool's\" mate"));
+    }
+
+    #[test]
+    fn test_write_from_fen() {
+        let start = Position::from_fen("4k3/8/8/8/8/8/8/R3K3 b Q - 0 30").unwrap();
+        let kf7 = start.san_to_move("Kf7").unwrap();
+        let mut after = start.clone();
+        after.make_move(&kf7).unwrap();
+        let moves = vec![kf7, after.san_to_move("O-O-O").unwrap()];
+
+        let pgn = PgnGame::new(start.clone(), moves.clone()).to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 0 30\"]"));
+        assert!(pgn.ends_with("\n30... Kf7 31. O-O-O *\n"));
+        assert_eq!(parse_pgn(&pgn).unwrap()[0].moves, moves);
+
+        // Long movetext wraps, and repeating the knight shuffle ends in a draw
+        let mut position = Position::new();
+        for san in ["Nf3", "Nf6", "Ng1", "Ng8"].repeat(5) {
+            let mv = position.san_to_move(san).unwrap();
+            position.make_move(&mv).unwrap();
+        }
+        let pgn = PgnGame::from_position(&position).to_pgn();
+        assert!(pgn.lines().count() > 9);
+        assert!(pgn.lines().all(|line| line.len() <= LINE_WIDTH));
+        assert!(pgn.ends_with("Ng8 1/2-1/2\n"));
+    }
+}
@@ -0,0 +1,352 @@
+//! Placement terms for individual pieces
+
+use crate::{
+    bitboard::{
+        Bitboard, DARK_SQUARES, FILES, FILE_D, FILE_E, LIGHT_SQUARES, RANK_1, RANK_2, RANK_7,
+        RANK_8,
+    },
+    board::{Board, Color, PieceType, Square},
+    evaluate::{self, Score},
+    movegen::knight_attacks,
+    params::EvalParams,
+    pawns, threats,
+};
+
+/// Bonus for a rook on a file without pawns
+pub(crate) const ROOK_OPEN_FILE_BONUS: i32 = 25;
+
+/// Bonus for a rook on a file with only enemy pawns
+pub(crate) const ROOK_SEMI_OPEN_FILE_BONUS: i32 = 12;
+
+/// Bonus for a rook on the seventh rank that cuts off the king or attacks pawns
+pub(crate) const ROOK_SEVENTH_RANK_BONUS: i32 = 20;
+
+/// Bonus for a rook behind a passed pawn of either side on its file
+pub(crate) const ROOK_BEHIND_PASSER_BONUS: i32 = 15;
+
+/// Penalty per own fixed pawn on the colour of a bishop
+pub(crate) const BAD_BISHOP_PENALTY: i32 = 4;
+
+/// Extra penalty per such pawn on the d- or e-file, where it blocks the most diagonals
+pub(crate) const BAD_BISHOP_CENTRE_PENALTY: i32 = 6;
+
+/// Bonus per step closer than the far side of the board to the enemy king, by piece
+/// type from knight to queen; attacks need pieces nearby in the middlegame
+pub(crate) const TROPISM_WEIGHTS: [Score; 4] = [
+    Score::new(3, 0),
+    Score::new(1, 0),
+    Score::new(2, 0),
+    Score::new(4, 1),
+];
+
+/// Penalty for a bishop shut in on a7/h7 by an enemy pawn on b6/g6
+pub(crate) const TRAPPED_BISHOP_PENALTY: i32 = 100;
+
+/// Penalty for a knight in an enemy corner whose exits are all covered
+pub(crate) const TRAPPED_KNIGHT_PENALTY: i32 = 50;
+
+/// Penalty for a rook locked in the corner by its own uncastled king
+pub(crate) const TRAPPED_ROOK_PENALTY: i32 = 50;
+
+/// Trapping patterns as seen by White, as (piece square, enemy pawn square)
+const TRAPPED_BISHOP_SQUARES: [(u8, u8); 2] = [(48, 41), (55, 46)];
+
+/// Enemy corners as seen by White
+const TRAPPED_KNIGHT_SQUARES: [u8; 2] = [56, 63];
+
+/// Square as seen by `color`, mirroring the ranks for Black
+fn relative_square(square: u8, color: Color) -> u8 {
+    match color {
+        Color::White => square,
+        Color::Black => square ^ 56,
+    }
+}
+
+/// Seventh and eighth ranks as seen by `color`
+fn seventh_and_eighth(color: Color) -> (Bitboard, Bitboard) {
+    match color {
+        Color::White => (RANK_7, RANK_8),
+        Color::Black => (RANK_2, RANK_1),
+    }
+}
+
+/// Number of rooks earning each rook bonus
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RookTerms {
+    pub open_file: i32,
+    pub semi_open_file: i32,
+    pub seventh_rank: i32,
+    pub behind_passer: i32,
+}
+
+pub(crate) fn rook_terms(board: &Board, color: Color) -> RookTerms {
+    let rooks = board.piece_bitboard(color, PieceType::Rook);
+    let open = pawns::open_files(board);
+    let semi_open = pawns::semi_open_files(board, color);
+
+    let mut terms = RookTerms::default();
+    for square in rooks.squares() {
+        if open.has_square(square) {
+            terms.open_file += 1;
+        } else if semi_open.has_square(square) {
+            terms.semi_open_file += 1;
+        }
+        if behind_passer(board, color, square) {
+            terms.behind_passer += 1;
+        }
+    }
+
+    // Only worth it while the enemy king is stuck behind or pawns are left to eat
+    let (seventh, eighth) = seventh_and_eighth(color);
+    let enemy = color.opposite();
+    let king_cut_off = !(board.piece_bitboard(enemy, PieceType::King) & eighth).is_empty();
+    let pawns_to_attack = !(board.piece_bitboard(enemy, PieceType::Pawn) & seventh).is_empty();
+    if king_cut_off || pawns_to_attack {
+        terms.seventh_rank = (rooks & seventh).count() as i32;
+    }
+
+    terms
+}
+
+/// Whether the rook of `color` on `square` stands behind a passed pawn on its file
+/// with nothing in between, supporting its own pawn or chasing an enemy one
+fn behind_passer(board: &Board, color: Color, square: u8) -> bool {
+    let attacks = threats::piece_attacks(board, PieceType::Rook, square, color);
+    [Color::White, Color::Black].into_iter().any(|owner| {
+        let passers = pawns::passed_pawns(board, owner) & FILES[(square % 8) as usize];
+        (attacks & passers).squares().any(|pawn| match owner {
+            Color::White => square < pawn,
+            Color::Black => square > pawn,
+        })
+    })
+}
+
+fn rook_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let terms = rook_terms(board, color);
+    terms.open_file * params.rook_open_file_bonus
+        + terms.semi_open_file * params.rook_semi_open_file_bonus
+        + terms.seventh_rank * params.rook_seventh_rank_bonus
+        + terms.behind_passer * params.rook_behind_passer_bonus
+}
+
+/// Number of own fixed pawns on the square colour of each bishop of `color`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BadBishopTerms {
+    pub blocking: i32,
+    /// Those of `blocking` on the d- and e-files
+    pub central: i32,
+}
+
+pub(crate) fn bad_bishop_terms(board: &Board, color: Color) -> BadBishopTerms {
+    let fixed = pawns::fixed_pawns(board, color);
+    let mut terms = BadBishopTerms::default();
+    for bishop in board.piece_bitboard(color, PieceType::Bishop).squares() {
+        let colour = if LIGHT_SQUARES.has_square(bishop) {
+            LIGHT_SQUARES
+        } else {
+            DARK_SQUARES
+        };
+        let blocking = fixed & colour;
+        terms.blocking += blocking.count() as i32;
+        terms.central += (blocking & (FILE_D | FILE_E)).count() as i32;
+    }
+    terms
+}
+
+/// Penalty, as a non-positive score, for bishops hemmed in by their own fixed pawns
+pub(crate) fn bad_bishop(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let terms = bad_bishop_terms(board, color);
+    -(terms.blocking * params.bad_bishop_penalty
+        + terms.central * params.bad_bishop_centre_penalty)
+}
+
+/// Closeness of the pieces of `color` to the enemy king
+pub(crate) fn tropism(board: &Board, color: Color, params: &EvalParams) -> Score {
+    let Some(king) = board
+        .piece_bitboard(color.opposite(), PieceType::King)
+        .lsb()
+    else {
+        return Score::default();
+    };
+
+    let mut score = Score::default();
+    for (weight, piece_type) in params.tropism_weights.iter().zip([
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+    ]) {
+        for square in board.piece_bitboard(color, piece_type).squares() {
+            let closeness = 7 - Square(square).distance(Square(king)) as i32;
+            score += *weight * closeness;
+        }
+    }
+    score
+}
+
+/// Penalties, as a non-positive score, for classically trapped pieces of `color`
+pub(crate) fn trapped(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let enemy = color.opposite();
+    let enemy_pawns = board.piece_bitboard(enemy, PieceType::Pawn);
+    let mut score = 0;
+
+    let bishops = board.piece_bitboard(color, PieceType::Bishop);
+    for (bishop, pawn) in TRAPPED_BISHOP_SQUARES {
+        if bishops.has_square(relative_square(bishop, color))
+            && enemy_pawns.has_square(relative_square(pawn, color))
+        {
+            score -= params.trapped_bishop_penalty;
+        }
+    }
+
+    let knights = board.piece_bitboard(color, PieceType::Knight);
+    let covered = threats::attacked_squares(board, enemy) | board.color_bitboard(color);
+    for corner in TRAPPED_KNIGHT_SQUARES {
+        let corner = relative_square(corner, color);
+        if knights.has_square(corner) && (knight_attacks(Square(corner)) & !covered).is_empty() {
+            score -= params.trapped_knight_penalty;
+        }
+    }
+
+    // A king that walked to the wing without castling shuts in the rook beside it
+    if let Some(king) = board.piece_bitboard(color, PieceType::King).lsb() {
+        let king = relative_square(king, color);
+        let (king_rank, king_file) = (king / 8, king % 8);
+        for rook in board.piece_bitboard(color, PieceType::Rook).squares() {
+            let rook = relative_square(rook, color);
+            let locked = match king_file {
+                5 | 6 => rook % 8 > king_file,
+                1 | 2 => rook % 8 < king_file,
+                _ => false,
+            };
+            if king_rank == 0 && rook / 8 <= 1 && locked {
+                score -= params.trapped_rook_penalty;
+            }
+        }
+    }
+
+    score
+}
+
+/// Piece placement score of one side
+pub(crate) fn side_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    rook_score(board, color, params)
+        + bad_bishop(board, color, params)
+        + trapped(board, color, params)
+        + tropism(board, color, params).taper(evaluate::game_phase(board))
+}
+
+/// Piece placement score from White's point of view
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    side_score(board, Color::White, params) - side_score(board, Color::Black, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_rook_files() {
+        let params = EvalParams::default();
+        // White rook on the open d-file, black rook behind its own a-pawn
+        let pos = Position::from_fen("r3k3/p7/8/8/8/8/1P6/3RK3 w - - 0 1").unwrap();
+        assert_eq!(rook_score(&pos.board, Color::White, &params), ROOK_OPEN_FILE_BONUS);
+        assert_eq!(rook_score(&pos.board, Color::Black, &params), 0);
+
+        // On the a-file the white rook only faces an enemy pawn
+        let pos = Position::from_fen("4k3/p7/8/8/8/8/1P6/R3K3 w - - 0 1").unwrap();
+        assert_eq!(
+            rook_score(&pos.board, Color::White, &params),
+            ROOK_SEMI_OPEN_FILE_BONUS
+        );
+    }
+
+    #[test]
+    fn test_rook_behind_passer() {
+        // The a1 rook supports the a5 passer from behind, the h-rook chases h4
+        let pos = Position::from_fen("6k1/8/8/P7/7p/8/8/R3K2R w - - 0 1").unwrap();
+        let terms = rook_terms(&pos.board, Color::White);
+        assert_eq!(terms.behind_passer, 1);
+
+        let chase = Position::from_fen("6k1/7R/8/P7/7p/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(rook_terms(&chase.board, Color::White).behind_passer, 1);
+
+        // A piece in between cuts the rook off
+        let blocked = Position::from_fen("6k1/8/8/P7/8/N7/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(rook_terms(&blocked.board, Color::White).behind_passer, 0);
+    }
+
+    #[test]
+    fn test_rook_on_seventh() {
+        let params = EvalParams::default();
+        // The b7 rook is on a file with a white pawn, so only the seventh rank counts
+        let pos = Position::from_fen("6k1/1R6/8/8/8/8/1P6/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            rook_score(&pos.board, Color::White, &params),
+            ROOK_SEVENTH_RANK_BONUS
+        );
+
+        // Without a king to cut off or pawns to attack there is no bonus
+        let pos = Position::from_fen("8/1R6/6k1/8/8/8/1P6/4K3 w - - 0 1").unwrap();
+        assert_eq!(rook_score(&pos.board, Color::White, &params), 0);
+    }
+
+    #[test]
+    fn test_bad_bishop() {
+        let params = EvalParams::default();
+        // French structure: the c8 bishop is stuck behind e6 and d5, fixed by e5 and d4
+        let pos =
+            Position::from_fen("2b1k3/pp3ppp/4p3/3pP3/3P4/8/PP3PPP/4KB2 w - - 0 1").unwrap();
+        let terms = bad_bishop_terms(&pos.board, Color::Black);
+        assert_eq!(terms, BadBishopTerms { blocking: 2, central: 2 });
+        assert_eq!(
+            bad_bishop(&pos.board, Color::Black, &params),
+            -2 * (BAD_BISHOP_PENALTY + BAD_BISHOP_CENTRE_PENALTY)
+        );
+
+        // White's f1 bishop is on the other colour from its fixed pawns
+        assert_eq!(bad_bishop(&pos.board, Color::White, &params), 0);
+    }
+
+    #[test]
+    fn test_tropism_is_tapered() {
+        let params = EvalParams::default();
+        // The same queen close to the king counts for more with more pieces around
+        let near = Position::from_fen("rn2k3/8/8/4Q3/8/8/8/RN2K3 w - - 0 1").unwrap();
+        let far = Position::from_fen("rn2k3/8/8/8/8/8/8/RNQ1K3 w - - 0 1").unwrap();
+        let near_tropism = tropism(&near.board, Color::White, &params);
+        assert!(near_tropism.mg > tropism(&far.board, Color::White, &params).mg);
+        assert!(near_tropism.mg > near_tropism.eg);
+
+        let phase = evaluate::game_phase(&near.board);
+        assert!(near_tropism.taper(phase) > near_tropism.taper(0));
+    }
+
+    #[test]
+    fn test_trapped_pieces() {
+        let params = EvalParams::default();
+        // Bxa7 ...b6 shuts the bishop in
+        let bishop = Position::from_fen("4k3/B1p5/1p6/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(trapped(&bishop.board, Color::White, &params), -TRAPPED_BISHOP_PENALTY);
+
+        // The same pattern for Black on h2 with a white pawn on g3
+        let bishop = Position::from_fen("4k3/8/8/8/8/6P1/7b/4K3 w - - 0 1").unwrap();
+        assert_eq!(trapped(&bishop.board, Color::Black, &params), -TRAPPED_BISHOP_PENALTY);
+
+        // Knight on h8 with f7 covered by the king and g6 by the h-pawn
+        let knight = Position::from_fen("4k2N/7p/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(trapped(&knight.board, Color::White, &params), -TRAPPED_KNIGHT_PENALTY);
+
+        // Kf1 without castling locks the h1 rook
+        let rook = Position::from_fen("4k3/8/8/8/8/8/5PPP/5K1R w - - 0 1").unwrap();
+        assert_eq!(trapped(&rook.board, Color::White, &params), -TRAPPED_ROOK_PENALTY);
+        let castled = Position::from_fen("4k3/8/8/8/8/8/5PPP/5RK1 w - - 0 1").unwrap();
+        assert_eq!(trapped(&castled.board, Color::White, &params), 0);
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        assert_eq!(evaluate(&Position::new().board, &EvalParams::default()), 0);
+    }
+}
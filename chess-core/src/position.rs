@@ -1,10 +1,18 @@
 
 use crate::{
+    bitboard::Bitboard,
     board::{Board, Color, Piece, PieceType, Square},
-    moves::Move,
+    endgame::MaterialKey,
+    epd::Epd,
+    evaluate,
+    moves::{IllegalMoveReason, Move, MoveList},
     movegen,
-    Error, Result,
+    nnue::{Accumulator, Network},
+    params::EvalParams,
+    rules::{Rules, Standard},
+    threats, zobrist, Error, Result,
 };
+use std::sync::Arc;
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,8 +21,14 @@ pub struct CastlingRights {
     pub white_queenside: bool,
     pub black_kingside: bool,
     pub black_queenside: bool,
+    /// Files of the castling rooks, by colour then kingside and queenside; h and a
+    /// in standard chess, anywhere either side of the king in Chess960
+    pub rook_files: [[u8; 2]; 2],
 }
 
+/// Rook files of standard chess, kingside then queenside
+const STANDARD_ROOK_FILES: [u8; 2] = [7, 0];
+
 impl CastlingRights {
     
     pub fn all() -> Self {
@@ -23,6 +37,7 @@ impl CastlingRights {
             white_queenside: true,
             black_kingside: true,
             black_queenside: true,
+            rook_files: [STANDARD_ROOK_FILES; 2],
         }
     }
 
@@ -33,47 +48,62 @@ impl CastlingRights {
             white_queenside: false,
             black_kingside: false,
             black_queenside: false,
+            rook_files: [STANDARD_ROOK_FILES; 2],
+        }
+    }
+
+    /// Whether `color` may still castle on the given side
+    pub fn has(&self, color: Color, kingside: bool) -> bool {
+        match (color, kingside) {
+            (Color::White, true) => self.white_kingside,
+            (Color::White, false) => self.white_queenside,
+            (Color::Black, true) => self.black_kingside,
+            (Color::Black, false) => self.black_queenside,
+        }
+    }
+
+    /// Grant or take away the right of `color` to castle on the given side
+    pub fn set(&mut self, color: Color, kingside: bool, allowed: bool) {
+        match (color, kingside) {
+            (Color::White, true) => self.white_kingside = allowed,
+            (Color::White, false) => self.white_queenside = allowed,
+            (Color::Black, true) => self.black_kingside = allowed,
+            (Color::Black, false) => self.black_queenside = allowed,
         }
     }
 
+    /// Home square of the rook `color` castles with on the given side
+    pub fn rook_square(&self, color: Color, kingside: bool) -> Square {
+        let rank = match color {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+        Square::new(self.rook_files[color as usize][!kingside as usize], rank)
+    }
+
+    /// Whether castling needs the rook files spelled out, i.e. the rooks are not on
+    /// the standard a and h files
+    pub fn is_chess960(&self) -> bool {
+        self.rook_files != [STANDARD_ROOK_FILES; 2]
+    }
+
     
     pub fn update(&mut self, mv: &Move, board: &Board) {
-        let from = mv.from();
-        let piece = board.piece_at(from);
-
-        if let Some(piece) = piece {
-            match piece.piece_type {
-                PieceType::King => {
-                    match piece.color {
-                        Color::White => {
-                            self.white_kingside = false;
-                            self.white_queenside = false;
-                        }
-                        Color::Black => {
-                            self.black_kingside = false;
-                            self.black_queenside = false;
-                        }
-                    }
-                }
-                PieceType::Rook => {
-                    match piece.color {
-                        Color::White => {
-                            if from == Square::from_algebraic("a1").unwrap() {
-                                self.white_queenside = false;
-                            } else if from == Square::from_algebraic("h1").unwrap() {
-                                self.white_kingside = false;
-                            }
-                        }
-                        Color::Black => {
-                            if from == Square::from_algebraic("a8").unwrap() {
-                                self.black_queenside = false;
-                            } else if from == Square::from_algebraic("h8").unwrap() {
-                                self.black_kingside = false;
-                            }
-                        }
-                    }
+        if let Some(piece) = board.piece_at(mv.from()) {
+            if piece.piece_type == PieceType::King {
+                self.set(piece.color, true, false);
+                self.set(piece.color, false, false);
+            }
+        }
+
+        // Moving a castling rook, or capturing it on its home square, takes away
+        // that side's castling
+        for color in [Color::White, Color::Black] {
+            for kingside in [true, false] {
+                let rook = self.rook_square(color, kingside);
+                if mv.from() == rook || mv.to() == rook {
+                    self.set(color, kingside, false);
                 }
-                _ => {}
             }
         }
     }
@@ -95,30 +125,103 @@ pub struct Position {
     
     pub fullmove_number: u32,
     
-    pub history: Vec<PositionState>,
+    pub history: Vec<UndoInfo>,
+    /// Zobrist key, updated as moves are made
+    key: u64,
+    /// Zobrist key of the pawns alone, updated as moves are made
+    pawn_key: u64,
+    /// Material plus piece-square score for White, updated as pieces move
+    material_pst: i32,
+    /// Piece counts of both sides, updated as pieces move
+    material_key: MaterialKey,
+    /// Weights of the classical evaluation
+    params: Arc<EvalParams>,
+    /// Network evaluating this position, if any
+    network: Option<Arc<Network>>,
+    /// Hidden layer sums of `network`, updated as pieces move
+    accumulator: Option<Accumulator>,
+    /// Rules the game is played under
+    rules: Arc<dyn Rules>,
 }
 
 
-#[derive(Debug, Clone)]
-pub struct PositionState {
-    pub board: Board,
+/// Plies without a capture or pawn move after which the game is drawn
+pub const FIFTY_MOVE_PLIES: u32 = 100;
+
+/// How a game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    /// The side to move is mated
+    Checkmate { winner: Color },
+    Stalemate,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    InsufficientMaterial,
+    /// Won under a variant's own rules, such as a king reaching the centre
+    VariantWin { winner: Color },
+}
+
+impl GameResult {
+    /// Whether the game ended in a draw
+    pub fn is_draw(self) -> bool {
+        self.winner().is_none()
+    }
+
+    /// Side that won, if the game was not drawn
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            GameResult::Checkmate { winner } | GameResult::VariantWin { winner } => Some(winner),
+            _ => None,
+        }
+    }
+}
+
+/// Cached subtree count of `Position::perft_hashed`
+#[derive(Debug, Clone, Copy, Default)]
+struct PerftEntry {
+    key: u64,
+    depth: u32,
+    nodes: u64,
+}
+
+/// What `undo_move` needs to take a move back
+#[derive(Debug, Clone, Copy)]
+pub struct UndoInfo {
+    pub mv: Move,
+    pub moved: Piece,
+    /// Piece taken by the move, the pawn beside the target for en passant
+    pub captured: Option<Piece>,
     pub castling_rights: CastlingRights,
     pub en_passant: Option<Square>,
     pub halfmove_clock: u32,
+    /// Zobrist key before the move, for spotting repetitions
+    pub key: u64,
 }
 
 impl Position {
     
     pub fn new() -> Self {
-        Self {
-            board: Board::starting_position(),
+        let board = Board::starting_position();
+        let mut position = Self {
+            material_pst: evaluate::material_pst(&board, &EvalParams::shared_default()),
+            material_key: MaterialKey::of(&board),
+            board,
             side_to_move: Color::White,
             castling_rights: CastlingRights::all(),
             en_passant: None,
             halfmove_clock: 0,
             fullmove_number: 1,
             history: Vec::new(),
-        }
+            params: EvalParams::shared_default(),
+            network: None,
+            accumulator: None,
+            rules: Arc::new(Standard),
+            key: 0,
+            pawn_key: 0,
+        };
+        position.key = zobrist::hash(&position);
+        position.pawn_key = zobrist::pawn_hash(&position.board);
+        position
     }
 
     
@@ -127,6 +230,9 @@ impl Position {
         if parts.len() < 4 {
             return Err(Error::InvalidFen("Not enough parts".to_string()));
         }
+        if parts.len() > 6 {
+            return Err(Error::InvalidFen("Too many parts".to_string()));
+        }
 
         let board = parse_fen_board(parts[0])?;
         let side_to_move = match parts[1] {
@@ -135,7 +241,7 @@ impl Position {
             _ => return Err(Error::InvalidFen("Invalid side to move".to_string())),
         };
 
-        let castling_rights = parse_fen_castling(parts[2])?;
+        let castling_rights = parse_fen_castling(parts[2], &board)?;
         let en_passant = if parts[3] == "-" {
             None
         } else {
@@ -145,16 +251,69 @@ impl Position {
             )
         };
 
-        let halfmove_clock = parts
-            .get(4)
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(0);
-        let fullmove_number = parts
-            .get(5)
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(1);
+        // The move counters may be left out, but not written wrong
+        let counter = |index: usize, default: u32, name: &str| match parts.get(index) {
+            Some(field) => field
+                .parse()
+                .map_err(|_| Error::InvalidFen(format!("Invalid {name}: {field}"))),
+            None => Ok(default),
+        };
+        let halfmove_clock = counter(4, 0, "halfmove clock")?;
+        let fullmove_number = counter(5, 1, "fullmove number")?;
+
+        Ok(Self::from_parts(
+            board,
+            side_to_move,
+            castling_rights,
+            en_passant,
+            halfmove_clock,
+            fullmove_number,
+        ))
+    }
+
+    /// Parse a FEN as found in the wild: the castling, en passant and counter fields
+    /// may be missing and the side to move may be upper case. Operations after the
+    /// first four fields are read as EPD, whose `hmvc` and `fmvn` set the counters,
+    /// and anything else past the counters is ignored. Missing fields take the
+    /// defaults `-`, `-`, 0 and 1.
+    pub fn from_fen_lenient(fen: &str) -> Result<Self> {
+        let mut parts: Vec<&str> = fen.split_whitespace().collect();
+        let side = match parts.get(1) {
+            Some(side) if side.eq_ignore_ascii_case("w") => "w",
+            Some(side) if side.eq_ignore_ascii_case("b") => "b",
+            None => "w",
+            Some(_) => return Err(Error::InvalidFen("Invalid side to move".to_string())),
+        };
+        parts.resize(parts.len().max(4), "-");
+        parts[1] = side;
+
+        let rest = &parts[4..];
+        let counters = rest
+            .iter()
+            .take_while(|field| field.parse::<u32>().is_ok())
+            .count()
+            .min(2);
+        if counters == 0 && !rest.is_empty() {
+            if let Ok(epd) = Epd::parse(&parts.join(" ")) {
+                return Ok(epd.position);
+            }
+        }
+        Self::from_fen(&parts[..4 + counters].join(" "))
+    }
 
-        Ok(Self {
+    /// A position with no history from its FEN fields, with the keys, material and
+    /// default rules filled in. Nothing is validated.
+    pub(crate) fn from_parts(
+        board: Board,
+        side_to_move: Color,
+        castling_rights: CastlingRights,
+        en_passant: Option<Square>,
+        halfmove_clock: u32,
+        fullmove_number: u32,
+    ) -> Self {
+        let mut position = Self {
+            material_pst: evaluate::material_pst(&board, &EvalParams::shared_default()),
+            material_key: MaterialKey::of(&board),
             board,
             side_to_move,
             castling_rights,
@@ -162,7 +321,16 @@ impl Position {
             halfmove_clock,
             fullmove_number,
             history: Vec::new(),
-        })
+            params: EvalParams::shared_default(),
+            network: None,
+            accumulator: None,
+            rules: Arc::new(Standard),
+            key: 0,
+            pawn_key: 0,
+        };
+        position.key = zobrist::hash(&position);
+        position.pawn_key = zobrist::pawn_hash(&position.board);
+        position
     }
 
     
@@ -170,11 +338,14 @@ impl Position {
         let mut fen = String::new();
 
         
+        let mut mailbox = [None; 64];
+        for (square, piece) in self.board.pieces() {
+            mailbox[square.index() as usize] = Some(piece);
+        }
         for rank in (0..8).rev() {
             let mut empty_count = 0;
             for file in 0..8 {
-                let square = Square::new(file, rank);
-                if let Some(piece) = self.board.piece_at(square) {
+                if let Some(piece) = mailbox[Square::new(file, rank).index() as usize] {
                     if empty_count > 0 {
                         fen.push_str(&empty_count.to_string());
                         empty_count = 0;
@@ -202,17 +373,13 @@ impl Position {
         
         fen.push(' ');
         let mut castling = String::new();
-        if self.castling_rights.white_kingside {
-            castling.push('K');
-        }
-        if self.castling_rights.white_queenside {
-            castling.push('Q');
-        }
-        if self.castling_rights.black_kingside {
-            castling.push('k');
-        }
-        if self.castling_rights.black_queenside {
-            castling.push('q');
+        for color in [Color::White, Color::Black] {
+            for kingside in [true, false] {
+                if self.castling_rights.has(color, kingside) {
+                    let rights = &self.castling_rights;
+                    castling.push(castling_char(&self.board, rights, color, kingside));
+                }
+            }
         }
         if castling.is_empty() {
             castling.push('-');
@@ -236,236 +403,734 @@ impl Position {
         fen
     }
 
-    
+    /// Play `mv`, recording what `undo_move` needs to take it back. On error the
+    /// position is left unchanged.
     pub fn make_move(&mut self, mv: &Move) -> Result<()> {
-        
-        let state = PositionState {
-            board: self.board.clone(),
+        let from = mv.from();
+        let to = mv.to();
+        let moved = self
+            .board
+            .piece_at(from)
+            .ok_or_else(|| illegal(mv, IllegalMoveReason::NoPiece(from)))?;
+        let rook_squares = castling_rook_squares(&self.castling_rights, to);
+        if mv.is_castling() && rook_squares.is_none() {
+            return Err(illegal(mv, IllegalMoveReason::Unreachable { from, to }));
+        }
+        let captured = if mv.is_en_passant() {
+            self.board.piece_at(en_passant_victim(to, moved.color))
+        } else if mv.is_castling() {
+            None
+        } else {
+            self.board.piece_at(to)
+        };
+
+        self.history.push(UndoInfo {
+            mv: *mv,
+            moved,
+            captured,
             castling_rights: self.castling_rights,
             en_passant: self.en_passant,
             halfmove_clock: self.halfmove_clock,
-        };
-        self.history.push(state);
+            key: self.key,
+        });
 
-        
+        self.key ^= zobrist::rights_key(&self.castling_rights, self.en_passant);
         self.castling_rights.update(mv, &self.board);
-
-        
         self.en_passant = None;
 
-        
         if mv.is_en_passant() {
-            self.make_en_passant_move(mv)?;
-        } else if mv.is_castling() {
-            self.make_castling_move(mv)?;
-        } else if mv.is_promotion() {
-            self.make_promotion_move(mv)?;
+            self.put_piece(en_passant_victim(to, moved.color), None);
+            self.put_piece(from, None);
+            self.put_piece(to, Some(moved));
+        } else if let (true, Some((rook_from, rook_to))) = (mv.is_castling(), rook_squares) {
+            let rook = self.board.piece_at(rook_from);
+            self.put_piece(from, None);
+            self.put_piece(rook_from, None);
+            self.put_piece(to, Some(moved));
+            self.put_piece(rook_to, rook);
         } else {
-            self.make_normal_move(mv);
+            let landed = match mv.promotion_piece() {
+                Some(promotion) => Piece::new(moved.color, promotion),
+                None => moved,
+            };
+            if moved.piece_type == PieceType::Pawn && from.rank().abs_diff(to.rank()) == 2 {
+                let ep_rank = (from.rank() + to.rank()) / 2;
+                self.en_passant = Some(Square::new(from.file(), ep_rank));
+            }
+            self.put_piece(from, None);
+            self.put_piece(to, Some(landed));
         }
 
-        
-        if mv.piece_type() == PieceType::Pawn || self.board.piece_at(mv.to()).is_some() {
+        if moved.piece_type == PieceType::Pawn || captured.is_some() {
             self.halfmove_clock = 0;
         } else {
             self.halfmove_clock += 1;
         }
 
-        
         if self.side_to_move == Color::Black {
             self.fullmove_number += 1;
         }
         self.side_to_move = self.side_to_move.opposite();
+        self.key ^= zobrist::rights_key(&self.castling_rights, self.en_passant);
+        self.key ^= zobrist::KEYS.side_to_move;
 
         Ok(())
     }
 
-    
+    /// Take back the last move made, reversing it piece by piece
     pub fn undo_move(&mut self) -> Result<()> {
-        if let Some(state) = self.history.pop() {
-            self.board = state.board;
-            self.castling_rights = state.castling_rights;
-            self.en_passant = state.en_passant;
-            self.halfmove_clock = state.halfmove_clock;
+        let undo = self
+            .history
+            .pop()
+            .ok_or_else(|| Error::InvalidMove("No moves to undo".to_string()))?;
+        let from = undo.mv.from();
+        let to = undo.mv.to();
+
+        if undo.mv.is_en_passant() {
+            self.put_piece(to, None);
+            self.put_piece(en_passant_victim(to, undo.moved.color), undo.captured);
+        } else if let (true, Some((rook_from, rook_to))) =
+            (undo.mv.is_castling(), castling_rook_squares(&undo.castling_rights, to))
+        {
+            let rook = self.board.piece_at(rook_to);
+            self.put_piece(to, None);
+            self.put_piece(rook_to, None);
+            self.put_piece(rook_from, rook);
+        } else {
+            self.put_piece(to, undo.captured);
+        }
+        self.put_piece(from, Some(undo.moved));
+
+        self.castling_rights = undo.castling_rights;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.key = undo.key;
+        self.side_to_move = self.side_to_move.opposite();
+        if self.side_to_move == Color::Black {
+            self.fullmove_number -= 1;
+        }
+
+        Ok(())
+    }
 
-            
-            self.side_to_move = self.side_to_move.opposite();
-            if self.side_to_move == Color::Black {
-                self.fullmove_number -= 1;
+    /// Material plus piece-square score from White's point of view.
+    ///
+    /// Maintained incrementally by `make_move` and restored by `undo_move`, so it
+    /// never has to be recomputed from the board.
+    pub fn material_pst(&self) -> i32 {
+        self.material_pst
+    }
+
+    /// Piece counts of both sides, kept up to date as moves are made and undone
+    pub fn material_key(&self) -> MaterialKey {
+        self.material_key
+    }
+
+    /// Weights the classical evaluation of this position uses
+    pub fn params(&self) -> &EvalParams {
+        &self.params
+    }
+
+    /// Evaluate this position with other weights, recomputing the material and
+    /// piece-square score under them
+    pub fn set_params(&mut self, params: Arc<EvalParams>) {
+        self.material_pst = evaluate::material_pst(&self.board, &params);
+        self.params = params;
+    }
+
+    /// Attach a network to evaluate this position with, or detach it with `None`
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.accumulator = network
+            .as_ref()
+            .map(|network| Accumulator::new(network, &self.board));
+        self.network = network;
+    }
+
+    /// The same position with the colours swapped and the board flipped top to
+    /// bottom, so White's a1 rook becomes Black's a8 rook; the side to move,
+    /// castling rights and en passant square follow. Evaluation is symmetric, so
+    /// both sides to move score the same.
+    pub fn mirrored(&self) -> Position {
+        let mut castling_rights = CastlingRights::none();
+        for color in [Color::White, Color::Black] {
+            for kingside in [true, false] {
+                let allowed = self.castling_rights.has(color, kingside);
+                castling_rights.set(color.opposite(), kingside, allowed);
             }
+        }
+        castling_rights.rook_files = [
+            self.castling_rights.rook_files[Color::Black as usize],
+            self.castling_rights.rook_files[Color::White as usize],
+        ];
+        self.transformed(Square::flip_rank, true, castling_rights)
+    }
 
-            Ok(())
-        } else {
-            Err(Error::InvalidMove("No moves to undo".to_string()))
+    /// The same position with the a and h files swapped. Castling rooks keep their
+    /// mirrored files, so kingside rights become queenside ones, though castling
+    /// still ends on the c and g files.
+    pub fn flipped_horizontally(&self) -> Position {
+        let mut castling_rights = CastlingRights::none();
+        for color in [Color::White, Color::Black] {
+            for kingside in [true, false] {
+                castling_rights.set(color, !kingside, self.castling_rights.has(color, kingside));
+            }
+            let [kingside, queenside] = self.castling_rights.rook_files[color as usize];
+            castling_rights.rook_files[color as usize] = [7 - queenside, 7 - kingside];
         }
+        self.transformed(Square::flip_file, false, castling_rights)
     }
 
-    
-    pub fn generate_moves(&self) -> Vec<Move> {
-        movegen::generate_moves(&self.board, self.side_to_move)
+    /// Copy of this position with every square moved by `map`, optionally with the
+    /// colours swapped, and no move history
+    fn transformed(
+        &self,
+        map: fn(Square) -> Square,
+        swap_colors: bool,
+        castling_rights: CastlingRights,
+    ) -> Position {
+        let color = |color: Color| if swap_colors { color.opposite() } else { color };
+        let mut board = Board::new();
+        for (square, piece) in self.board.pieces() {
+            board.put_piece(map(square), Piece::new(color(piece.color), piece.piece_type));
+        }
+
+        let mut position = Self {
+            material_pst: evaluate::material_pst(&board, &self.params),
+            material_key: MaterialKey::of(&board),
+            accumulator: None,
+            board,
+            side_to_move: color(self.side_to_move),
+            castling_rights,
+            en_passant: self.en_passant.map(map),
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            history: Vec::new(),
+            params: self.params.clone(),
+            network: None,
+            rules: self.rules.clone(),
+            key: 0,
+            pawn_key: 0,
+        };
+        position.key = zobrist::hash(&position);
+        position.pawn_key = zobrist::pawn_hash(&position.board);
+        position.set_network(self.network.clone());
+        position
     }
 
-    
-    pub fn in_check(&self) -> bool {
-        let king_square = self.find_king(self.side_to_move);
-        self.is_square_attacked(king_square, self.side_to_move.opposite())
+    /// Rules the game is played under
+    pub fn rules(&self) -> &dyn Rules {
+        self.rules.as_ref()
     }
 
-    
-    pub fn is_game_over(&self) -> bool {
-        self.generate_moves().is_empty()
+    /// Play the game under other rules
+    pub fn set_rules(&mut self, rules: Arc<dyn Rules>) {
+        self.rules = rules;
     }
 
-    
-    pub fn is_checkmate(&self) -> bool {
-        self.in_check() && self.generate_moves().is_empty()
+    /// Network evaluation from the side to move's point of view, if a network is attached
+    pub fn evaluate_nnue(&self) -> Option<i32> {
+        let network = self.network.as_ref()?;
+        let accumulator = self.accumulator.as_ref()?;
+        Some(network.evaluate(accumulator, self.side_to_move))
     }
 
-    
-    pub fn is_stalemate(&self) -> bool {
-        !self.in_check() && self.generate_moves().is_empty()
+    /// Zobrist key identifying this position
+    pub fn key(&self) -> u64 {
+        self.key
     }
 
-    
-    fn find_king(&self, color: Color) -> Square {
-        let king_bb = self.board.piece_bitboard(color, PieceType::King);
-        Square::from(king_bb.lsb().expect("King not found"))
+    /// Zobrist key of the pawn structure alone, for the pawn hash table
+    pub fn pawn_key(&self) -> u64 {
+        self.pawn_key
     }
 
-    
-    fn is_square_attacked(&self, square: Square, by_color: Color) -> bool {
-        let sq_bb = square.bitboard();
+    /// Earlier occurrences of this position with the same side to move, looking back
+    /// only as far as the last capture or pawn move, after which none can recur
+    pub fn repetition_count(&self) -> usize {
+        self.history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .filter(|undo| undo.key == self.key)
+            .count()
+    }
 
-        
-        
+    /// Whether this position occurred before, which search scores as a draw
+    pub fn is_repetition(&self) -> bool {
+        self.repetition_count() > 0
+    }
 
-        
-        let pawn_attacks = match by_color {
-            Color::White => sq_bb.southwest() | sq_bb.southeast(),
-            Color::Black => sq_bb.northwest() | sq_bb.northeast(),
+    /// Whether this position occurred twice before, a draw by threefold repetition
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 2
+    }
+
+    /// Legal moves for the side to move
+    pub fn generate_moves(&self) -> MoveList {
+        self.rules.legal_moves(self)
+    }
+
+    /// Whether the side to move has a legal move, stopping at the first one found
+    pub fn has_legal_move(&self) -> bool {
+        self.rules.has_legal_move(self)
+    }
+
+    /// Number of legal moves for the side to move
+    pub fn legal_move_count(&self) -> usize {
+        self.generate_moves().len()
+    }
+
+    /// Legal captures for the side to move, en passant included
+    pub fn generate_captures(&self) -> MoveList {
+        let mut moves =
+            movegen::generate_captures(&self.board, self.side_to_move, self.en_passant);
+        moves.retain(|mv| movegen::is_legal(&self.board, self.side_to_move, mv));
+        moves
+    }
+
+    /// Legal moves for the side to move that capture nothing, castling included
+    pub fn generate_quiets(&self) -> MoveList {
+        let mut moves =
+            movegen::generate_quiets(&self.board, self.side_to_move, &self.castling_rights);
+        moves.retain(|mv| movegen::is_legal(&self.board, self.side_to_move, mv));
+        moves
+    }
+
+    /// Whether `mv` is a move the side to move could make if its own king's safety
+    /// were ignored, e.g. for checking a hash or killer move before playing it
+    pub fn is_pseudo_legal(&self, mv: &Move) -> bool {
+        let us = self.side_to_move;
+        let from = mv.from();
+        match self.board.piece_at(from) {
+            Some(piece) if piece.color == us && piece.piece_type == mv.piece_type() => {}
+            _ => return false,
+        }
+
+        if mv.is_castling() {
+            return movegen::generate_castling_moves(&self.board, us, &self.castling_rights)
+                .contains(mv);
+        }
+        if mv.is_en_passant() {
+            return movegen::generate_en_passant_moves(&self.board, us, self.en_passant)
+                .contains(mv);
+        }
+
+        let targets = movegen::generate_piece_moves(&self.board, us, mv.piece_type(), from.index());
+        if !targets.has_square(mv.to().index()) {
+            return false;
+        }
+        let promotes = mv.piece_type() == PieceType::Pawn && matches!(mv.to().rank(), 0 | 7);
+        match mv.promotion_piece() {
+            Some(PieceType::Pawn | PieceType::King) => false,
+            Some(_) => promotes,
+            None => !mv.is_promotion() && !promotes,
+        }
+    }
+
+    /// Enemy pieces giving check to the side to move
+    pub fn checkers(&self) -> Bitboard {
+        let king = self.find_king(self.side_to_move);
+        self.board.attackers_to(king, self.board.occupied)
+            & self.board.color_bitboard(self.side_to_move.opposite())
+    }
+
+    /// Pieces of `color` pinned to their king
+    pub fn pinned(&self, color: Color) -> Bitboard {
+        movegen::pinned_pieces(&self.board, color)
+    }
+
+    /// Pieces of the side to move whose moving off the line gives discovered check
+    pub fn discovered_checkers(&self) -> Bitboard {
+        movegen::discovered_checkers(&self.board, self.side_to_move)
+    }
+
+    /// Whether `mv` checks the opponent, directly or by uncovering a slider, worked out
+    /// without making the move
+    pub fn gives_check(&self, mv: &Move) -> bool {
+        let us = self.side_to_move;
+        let Some(king) = self.board.piece_bitboard(us.opposite(), PieceType::King).lsb() else {
+            return false;
         };
-        if (pawn_attacks & self.board.piece_bitboard(by_color, PieceType::Pawn)).0 != 0 {
-            return true;
+        let (from, to) = (mv.from(), mv.to());
+
+        // The piece that may give check directly, and the squares it and any
+        // castling rook leave and enter
+        let mut checker = (mv.promotion_piece().unwrap_or(mv.piece_type()), to);
+        let mut vacated = from.bitboard();
+        let mut entered = to.bitboard();
+        if mv.is_en_passant() {
+            vacated |= en_passant_victim(to, us).bitboard();
+        } else if let (true, Some((rook_from, rook_to))) =
+            (mv.is_castling(), castling_rook_squares(&self.castling_rights, to))
+        {
+            checker = (PieceType::Rook, rook_to);
+            vacated |= rook_from.bitboard();
+            entered |= rook_to.bitboard();
         }
+        let occupied = (self.board.occupied & !vacated) | entered;
 
-        
-        let knight_attacks = movegen::generate_knight_moves(&self.board, by_color, square);
-        if (knight_attacks & self.board.piece_bitboard(by_color, PieceType::Knight)).0 != 0 {
+        let (piece_type, square) = checker;
+        if movegen::piece_attacks(piece_type, us, square, occupied).has_square(king) {
             return true;
         }
 
-        
-        let king_attacks = movegen::generate_king_moves(&self.board, by_color, square);
-        if (king_attacks & self.board.piece_bitboard(by_color, PieceType::King)).0 != 0 {
-            return true;
+        // Our other sliders now seeing the king through the vacated squares
+        let ours = self.board.color_bitboard(us) & !vacated;
+        let queens = self.board.piece_bitboard(us, PieceType::Queen);
+        let diagonal = (self.board.piece_bitboard(us, PieceType::Bishop) | queens) & ours;
+        let straight = (self.board.piece_bitboard(us, PieceType::Rook) | queens) & ours;
+        let king = Square(king);
+        let bishop_rays = movegen::piece_attacks(PieceType::Bishop, us, king, occupied);
+        let rook_rays = movegen::piece_attacks(PieceType::Rook, us, king, occupied);
+        !(bishop_rays & diagonal).is_empty() || !(rook_rays & straight).is_empty()
+    }
+
+    /// Whether `mv` is legal for the side to move
+    pub fn is_legal(&self, mv: &Move) -> bool {
+        self.rules.is_legal(self, mv)
+    }
+
+    /// The legal move written in UCI notation, such as "e2e4", "e1g1" or "e7e8q",
+    /// taken from the generated moves so it carries its castling and en passant flags
+    pub fn parse_uci_move(&self, uci: &str) -> Result<Move> {
+        self.parse_uci_move_with(uci, false)
+    }
+
+    /// `mv` in UCI notation. Castling is written as the king's move to its target
+    /// square, or with `chess960` as the king taking its own rook, "e1h1"
+    pub fn move_to_uci(&self, mv: &Move, chess960: bool) -> String {
+        if !(chess960 && mv.is_castling()) {
+            return mv.to_algebraic();
         }
+        let kingside = mv.to().file() == 6;
+        let rook = self.castling_rights.rook_square(self.side_to_move, kingside);
+        format!("{}{}", mv.from(), rook)
+    }
 
-        
-        let bishop_attacks = movegen::generate_bishop_attacks(&self.board, square);
-        if (bishop_attacks & (self.board.piece_bitboard(by_color, PieceType::Bishop)
-            | self.board.piece_bitboard(by_color, PieceType::Queen))).0 != 0
+    /// Like `parse_uci_move`, reading castling the way `move_to_uci` writes it
+    pub fn parse_uci_move_with(&self, uci: &str, chess960: bool) -> Result<Move> {
+        let uci = uci.trim().to_ascii_lowercase();
+        let well_formed = uci.is_ascii()
+            && matches!(uci.len(), 4 | 5)
+            && Square::from_algebraic(&uci[..2]).is_some()
+            && Square::from_algebraic(&uci[2..4]).is_some();
+        if !well_formed {
+            return Err(Error::InvalidMove(format!("Malformed UCI move: {uci}")));
+        }
+        if let Some(mv) = self
+            .generate_moves()
+            .into_iter()
+            .find(|mv| self.move_to_uci(mv, chess960) == uci)
         {
-            return true;
+            return Ok(mv);
         }
 
-        let rook_attacks = movegen::generate_rook_attacks(&self.board, square);
-        if (rook_attacks & (self.board.piece_bitboard(by_color, PieceType::Rook)
-            | self.board.piece_bitboard(by_color, PieceType::Queen))).0 != 0
+        let from = Square::from_algebraic(&uci[..2]).unwrap();
+        let to = Square::from_algebraic(&uci[2..4]).unwrap();
+        let king = Piece::new(self.side_to_move, PieceType::King);
+        let rook = Piece::new(self.side_to_move, PieceType::Rook);
+        if chess960
+            && self.board.piece_at(from) == Some(king)
+            && self.board.piece_at(to) == Some(rook)
+            && from.rank() == to.rank()
         {
-            return true;
+            // The king taking its own rook stands for castling towards it
+            let file = if to.file() > from.file() { 6 } else { 2 };
+            let mv = Move::new_castling(from, Square::new(file, from.rank()), self.side_to_move);
+            let reason = self.illegal_reason(&mv).unwrap_or(IllegalMoveReason::ForbiddenByRules);
+            return Err(Error::IllegalMove { mv: uci, reason });
         }
+        let promotion = match uci.as_bytes().get(4) {
+            None => None,
+            Some(b'n') => Some(PieceType::Knight),
+            Some(b'b') => Some(PieceType::Bishop),
+            Some(b'r') => Some(PieceType::Rook),
+            Some(b'q') => Some(PieceType::Queen),
+            Some(_) => return Err(Error::InvalidMove(format!("Malformed UCI move: {uci}"))),
+        };
+        let mv = self.candidate_move(from, to, promotion);
+        let reason = if chess960 && mv.is_castling() {
+            // Castling is only written as the king taking its rook
+            IllegalMoveReason::Unreachable { from, to }
+        } else {
+            self.illegal_reason(&mv).unwrap_or(IllegalMoveReason::ForbiddenByRules)
+        };
+        Err(Error::IllegalMove { mv: uci, reason })
+    }
 
-        false
+    /// The move the piece on `from` would make to `to`, flagged as castling or en
+    /// passant the way the generator would flag it, legal or not
+    fn candidate_move(&self, from: Square, to: Square, promotion: Option<PieceType>) -> Move {
+        let piece_type = self
+            .board
+            .piece_at(from)
+            .map_or(PieceType::Pawn, |piece| piece.piece_type);
+        let file_distance = from.file().abs_diff(to.file());
+        match (piece_type, promotion) {
+            (_, Some(promotion)) => Move::new_promotion(from, to, piece_type, promotion),
+            (PieceType::King, None) if file_distance == 2 && from.rank() == to.rank() => {
+                Move::new_castling(from, to, self.side_to_move)
+            }
+            (PieceType::Pawn, None)
+                if file_distance == 1 && self.board.piece_at(to).is_none() =>
+            {
+                Move::new_en_passant(from, to)
+            }
+            _ => Move::new(from, to, piece_type),
+        }
     }
 
-    
-    fn make_normal_move(&mut self, mv: &Move) {
-        let from = mv.from();
-        let to = mv.to();
-        let piece = self.board.piece_at(from).expect("No piece at from square");
+    /// Why `mv` cannot be played by the side to move, or `None` if it is legal
+    pub fn illegal_reason(&self, mv: &Move) -> Option<IllegalMoveReason> {
+        use IllegalMoveReason::*;
+
+        let us = self.side_to_move;
+        let (from, to) = (mv.from(), mv.to());
+        let piece = match self.board.piece_at(from) {
+            None => return Some(NoPiece(from)),
+            Some(piece) if piece.color != us => return Some(WrongColor(from)),
+            Some(piece) if piece.piece_type != mv.piece_type() => return Some(PieceMismatch(from)),
+            Some(piece) => piece,
+        };
 
-        
-        if piece.piece_type == PieceType::Pawn {
-            let rank_diff = (to.rank() as i8 - from.rank() as i8).abs();
-            if rank_diff == 2 {
-                
-                let ep_rank = if piece.color == Color::White { from.rank() + 1 } else { from.rank() - 1 };
-                self.en_passant = Some(Square::new(from.file(), ep_rank));
+        if mv.is_castling() {
+            let kingside = match to.file() {
+                6 => true,
+                2 => false,
+                _ => return Some(Unreachable { from, to }),
+            };
+            if !self.castling_rights.has(us, kingside) {
+                return Some(NoCastlingRight);
+            }
+            if !movegen::generate_castling_moves(&self.board, us, &self.castling_rights)
+                .contains(mv)
+            {
+                return Some(CastlingBlocked);
+            }
+            if self.in_check() {
+                return Some(CastlingOutOfCheck);
+            }
+            if !movegen::is_legal(&self.board, us, mv) {
+                return Some(CastlingThroughCheck);
+            }
+        } else {
+            if self.board.color_bitboard(us).has_square(to.index()) {
+                return Some(OwnPieceOnTarget(to));
+            }
+            if mv.is_en_passant() {
+                if !movegen::generate_en_passant_moves(&self.board, us, self.en_passant)
+                    .contains(mv)
+                {
+                    return Some(NoEnPassant(to));
+                }
+            } else {
+                let targets =
+                    movegen::generate_piece_moves(&self.board, us, piece.piece_type, from.index());
+                if !targets.has_square(to.index()) {
+                    return Some(Unreachable { from, to });
+                }
+                let promotes = piece.piece_type == PieceType::Pawn && matches!(to.rank(), 0 | 7);
+                match mv.promotion_piece() {
+                    Some(PieceType::Pawn | PieceType::King) => return Some(InvalidPromotion),
+                    Some(_) if !promotes => return Some(InvalidPromotion),
+                    None if promotes => return Some(MissingPromotion),
+                    _ => {}
+                }
+            }
+            if !movegen::is_legal(&self.board, us, mv) {
+                return Some(LeavesKingInCheck);
             }
         }
 
-        
-        self.board.set_piece(from, None);
-        self.board.set_piece(to, Some(piece));
+        if !self.is_legal(mv) {
+            return Some(ForbiddenByRules);
+        }
+        None
     }
 
     
-    fn make_en_passant_move(&mut self, mv: &Move) -> Result<()> {
-        let from = mv.from();
-        let to = mv.to();
-        let piece = self.board.piece_at(from).expect("No piece at from square");
+    pub fn in_check(&self) -> bool {
+        self.king_attacked(self.side_to_move)
+    }
 
-        
-        self.board.set_piece(from, None);
-        self.board.set_piece(to, Some(piece));
+    /// Whether the king of `color` is attacked, regardless of the side to move
+    pub fn king_attacked(&self, color: Color) -> bool {
+        let king_square = self.find_king(color);
+        self.is_square_attacked(king_square, color.opposite())
+    }
 
-        
-        let captured_pawn_square = match piece.color {
-            Color::White => Square::new(to.file(), to.rank() - 1),
-            Color::Black => Square::new(to.file(), to.rank() + 1),
-        };
-        self.board.set_piece(captured_pawn_square, None);
+    /// How the game has ended in this position, if it has: mate and stalemate, or a
+    /// draw by the fifty-move rule, threefold repetition or insufficient material
+    pub fn result(&self) -> Option<GameResult> {
+        self.rules.result(self)
+    }
 
-        Ok(())
+    /// Whether the game has ended, by mate, stalemate or a draw
+    pub fn is_game_over(&self) -> bool {
+        self.result().is_some()
     }
 
     
-    fn make_castling_move(&mut self, mv: &Move) -> Result<()> {
-        let from = mv.from();
-        let to = mv.to();
-        let piece = self.board.piece_at(from).expect("No piece at from square");
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check() && !self.has_legal_move()
+    }
 
-        
-        self.board.set_piece(from, None);
-        self.board.set_piece(to, Some(piece));
+    
+    pub fn is_stalemate(&self) -> bool {
+        !self.in_check() && !self.has_legal_move()
+    }
 
-        
-        let (rook_from, rook_to) = match to {
-            
-            sq if sq == Square::from_algebraic("g1").unwrap() => {
-                (Square::from_algebraic("h1").unwrap(), Square::from_algebraic("f1").unwrap())
-            }
-            sq if sq == Square::from_algebraic("c1").unwrap() => {
-                (Square::from_algebraic("a1").unwrap(), Square::from_algebraic("d1").unwrap())
+    /// A move that checkmates at once, if the side to move has one. Only checking
+    /// moves are tried, so this is far cheaper than a search.
+    pub fn mate_in_one(&self) -> Option<Move> {
+        let mut position = self.clone();
+        self.generate_moves().into_iter().find(|mv| {
+            if !self.gives_check(mv) || position.make_move(mv).is_err() {
+                return false;
             }
-            sq if sq == Square::from_algebraic("g8").unwrap() => {
-                (Square::from_algebraic("h8").unwrap(), Square::from_algebraic("f8").unwrap())
-            }
-            sq if sq == Square::from_algebraic("c8").unwrap() => {
-                (Square::from_algebraic("a8").unwrap(), Square::from_algebraic("d8").unwrap())
-            }
-            _ => return Err(Error::InvalidMove("Invalid castling move".to_string())),
+            let mate = position.is_checkmate();
+            position.undo_move().expect("Move was just made");
+            mate
+        })
+    }
+
+    /// Material the side to move has en prise, see `threats::hanging_material`
+    pub fn hanging_material(&self) -> i32 {
+        threats::hanging_material(&self.board, self.side_to_move)
+    }
+
+    /// Number of leaf nodes of the legal move tree `depth` plies deep. The last ply
+    /// is counted from the generated moves without playing them.
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.generate_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for mv in moves {
+            self.make_move(&mv).expect("generated moves are legal");
+            nodes += self.perft(depth - 1);
+            self.undo_move().expect("a move was made");
+        }
+        nodes
+    }
+
+    /// `perft` with subtree counts cached in a table of `entries` slots, indexed by
+    /// Zobrist key, so transpositions are only counted once
+    pub fn perft_hashed(&mut self, depth: u32, entries: usize) -> u64 {
+        let mut table = vec![PerftEntry::default(); entries.max(1)];
+        self.perft_cached(depth, &mut table)
+    }
+
+    fn perft_cached(&mut self, depth: u32, table: &mut [PerftEntry]) -> u64 {
+        if depth <= 1 {
+            return self.perft(depth);
+        }
+        let slot = (self.key % table.len() as u64) as usize;
+        let entry = table[slot];
+        if entry.key == self.key && entry.depth == depth {
+            return entry.nodes;
+        }
+
+        let mut nodes = 0;
+        for mv in self.generate_moves() {
+            self.make_move(&mv).expect("generated moves are legal");
+            nodes += self.perft_cached(depth - 1, table);
+            self.undo_move().expect("a move was made");
+        }
+        table[slot] = PerftEntry {
+            key: self.key,
+            depth,
+            nodes,
         };
+        nodes
+    }
 
-        let rook = self.board.piece_at(rook_from).expect("No rook at castling square");
-        self.board.set_piece(rook_from, None);
-        self.board.set_piece(rook_to, Some(rook));
+    /// Perft node count below each root move, for finding which move a generator
+    /// bug hides under
+    pub fn perft_divide(&mut self, depth: u32) -> Vec<(Move, u64)> {
+        let mut divide = Vec::new();
+        for mv in self.generate_moves() {
+            self.make_move(&mv).expect("generated moves are legal");
+            divide.push((mv, self.perft(depth.saturating_sub(1))));
+            self.undo_move().expect("a move was made");
+        }
+        divide
+    }
 
-        Ok(())
+    
+    fn find_king(&self, color: Color) -> Square {
+        let king_bb = self.board.piece_bitboard(color, PieceType::King);
+        Square(king_bb.lsb().expect("King not found"))
+    }
+
+    /// Whether any piece of `by_color` attacks `square`
+    pub fn is_square_attacked(&self, square: Square, by_color: Color) -> bool {
+        movegen::is_square_attacked(&self.board, square, by_color)
     }
 
     
-    fn make_promotion_move(&mut self, mv: &Move) -> Result<()> {
-        let from = mv.from();
-        let to = mv.to();
-        let piece = self.board.piece_at(from).expect("No piece at from square");
-        let promotion_piece = mv.promotion_piece().expect("No promotion piece specified");
+    /// Place or remove a piece, keeping the keys, material and piece-square score in step
+    fn put_piece(&mut self, square: Square, piece: Option<Piece>) {
+        if let Some(old) = self.board.piece_at(square) {
+            self.key ^= zobrist::piece_key(old, square);
+            if old.piece_type == PieceType::Pawn {
+                self.pawn_key ^= zobrist::piece_key(old, square);
+            }
+            self.material_pst -= evaluate::piece_value_pst(old, square.index(), &self.params);
+            self.material_key.remove(old);
+            if let (Some(network), Some(accumulator)) = (&self.network, &mut self.accumulator) {
+                accumulator.remove(network, old, square.index());
+            }
+        }
+        if let Some(new) = piece {
+            self.key ^= zobrist::piece_key(new, square);
+            if new.piece_type == PieceType::Pawn {
+                self.pawn_key ^= zobrist::piece_key(new, square);
+            }
+            self.material_pst += evaluate::piece_value_pst(new, square.index(), &self.params);
+            self.material_key.add(new);
+            if let (Some(network), Some(accumulator)) = (&self.network, &mut self.accumulator) {
+                accumulator.add(network, new, square.index());
+            }
+        }
+        self.board.set_piece(square, piece);
+    }
+}
 
-        
-        self.board.set_piece(from, None);
-        self.board.set_piece(to, Some(Piece::new(piece.color, promotion_piece)));
+/// Rook origin and destination of a castling move landing the king on `king_to`,
+/// the rook's origin taken from `rights` as held before the move
+fn castling_rook_squares(rights: &CastlingRights, king_to: Square) -> Option<(Square, Square)> {
+    let color = match king_to.rank() {
+        0 => Color::White,
+        7 => Color::Black,
+        _ => return None,
+    };
+    let (kingside, rook_to) = match king_to.file() {
+        6 => (true, 5),
+        2 => (false, 3),
+        _ => return None,
+    };
+    Some((rights.rook_square(color, kingside), Square::new(rook_to, king_to.rank())))
+}
 
-        Ok(())
+fn illegal(mv: &Move, reason: IllegalMoveReason) -> Error {
+    Error::IllegalMove {
+        mv: mv.to_algebraic(),
+        reason,
+    }
+}
+
+/// Square of the pawn taken by an en passant capture of `color` onto `to`
+fn en_passant_victim(to: Square, color: Color) -> Square {
+    match color {
+        Color::White => Square::new(to.file(), to.rank() - 1),
+        Color::Black => Square::new(to.file(), to.rank() + 1),
     }
 }
 
@@ -514,7 +1179,9 @@ fn parse_fen_board(fen: &str) -> Result<Board> {
 }
 
 
-fn parse_fen_castling(fen: &str) -> Result<CastlingRights> {
+/// Castling field of a FEN, in X-FEN or Shredder-FEN form: "K" and "Q" castle with
+/// the outermost rook on that side of the king, a file letter with the rook on it
+fn parse_fen_castling(fen: &str, board: &Board) -> Result<CastlingRights> {
     let mut rights = CastlingRights::none();
 
     if fen == "-" {
@@ -522,18 +1189,69 @@ fn parse_fen_castling(fen: &str) -> Result<CastlingRights> {
     }
 
     for ch in fen.chars() {
-        match ch {
-            'K' => rights.white_kingside = true,
-            'Q' => rights.white_queenside = true,
-            'k' => rights.black_kingside = true,
-            'q' => rights.black_queenside = true,
+        let color = if ch.is_ascii_uppercase() { Color::White } else { Color::Black };
+        let (kingside, file) = match ch.to_ascii_lowercase() {
+            'k' => (true, outermost_rook_file(board, color, true).unwrap_or(7)),
+            'q' => (false, outermost_rook_file(board, color, false).unwrap_or(0)),
+            letter @ 'a'..='h' => {
+                let file = letter as u8 - b'a';
+                (file > back_rank_king_file(board, color).unwrap_or(4), file)
+            }
             _ => return Err(Error::InvalidFen("Invalid castling character".to_string())),
-        }
+        };
+        rights.set(color, kingside, true);
+        rights.rook_files[color as usize][!kingside as usize] = file;
     }
 
     Ok(rights)
 }
 
+/// FEN character for a castling right: "K" or "Q" when the rook is the outermost one
+/// on its side, as X-FEN writes it, and otherwise the rook's file
+fn castling_char(board: &Board, rights: &CastlingRights, color: Color, kingside: bool) -> char {
+    let file = rights.rook_square(color, kingside).file();
+    let standard = if kingside { 7 } else { 0 };
+    let ch = match outermost_rook_file(board, color, kingside) {
+        Some(outermost) if outermost != file => (b'a' + file) as char,
+        None if file != standard => (b'a' + file) as char,
+        _ if kingside => 'k',
+        _ => 'q',
+    };
+    match color {
+        Color::White => ch.to_ascii_uppercase(),
+        Color::Black => ch,
+    }
+}
+
+/// File of the king of `color` if it stands on its back rank
+fn back_rank_king_file(board: &Board, color: Color) -> Option<u8> {
+    let rank = if color == Color::White { 0 } else { 7 };
+    board
+        .piece_bitboard(color, PieceType::King)
+        .squares()
+        .map(Square)
+        .find(|square| square.rank() == rank)
+        .map(|square| square.file())
+}
+
+/// File of the rook of `color` furthest from its king on the back rank, on the
+/// kingside or queenside
+fn outermost_rook_file(board: &Board, color: Color, kingside: bool) -> Option<u8> {
+    let rank = if color == Color::White { 0 } else { 7 };
+    let king = back_rank_king_file(board, color)?;
+    let files = board
+        .piece_bitboard(color, PieceType::Rook)
+        .squares()
+        .map(Square)
+        .filter(|square| square.rank() == rank)
+        .map(|square| square.file());
+    if kingside {
+        files.filter(|&file| file > king).max()
+    } else {
+        files.filter(|&file| file < king).min()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -550,6 +1268,32 @@ mod tests {
         assert!(pos.castling_rights.black_queenside);
     }
 
+    #[test]
+    fn test_material_pst_is_incremental() {
+        // Promotion with capture, then a king move
+        let mut pos = Position::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let initial = pos.material_pst();
+        let initial_key = pos.material_key();
+
+        for mv_str in ["a7b8q", "e8d7"] {
+            let mv = pos
+                .generate_moves()
+                .into_iter()
+                .find(|mv| mv.to_algebraic().starts_with(mv_str))
+                .unwrap();
+            pos.make_move(&mv).unwrap();
+            assert_eq!(pos.material_pst(), evaluate::material_pst(&pos.board, pos.params()));
+            assert_eq!(pos.material_key(), MaterialKey::of(&pos.board));
+        }
+        assert_eq!(pos.material_key().count(Color::White, PieceType::Queen), 1);
+        assert_eq!(pos.material_key().count(Color::White, PieceType::Pawn), 0);
+
+        pos.undo_move().unwrap();
+        pos.undo_move().unwrap();
+        assert_eq!(pos.material_pst(), initial);
+        assert_eq!(pos.material_key(), initial_key);
+    }
+
     #[test]
     fn test_fen_roundtrip() {
         let start_pos = Position::new();
@@ -561,6 +1305,31 @@ mod tests {
         assert_eq!(start_pos.en_passant, parsed_pos.en_passant);
     }
 
+    #[test]
+    fn test_strict_and_lenient_fen() {
+        let full = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        assert!(Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").is_ok());
+        assert!(Position::from_fen(&format!("{full} extra")).is_err());
+        let bad_counter = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 x 1";
+        assert!(Position::from_fen(bad_counter).is_err());
+
+        let lenient = |fen: &str| Position::from_fen_lenient(fen).unwrap().to_fen();
+        assert_eq!(lenient(&format!("  {full}\t\n")), full);
+        assert_eq!(
+            lenient("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR B KQkq e3"),
+            full
+        );
+        assert_eq!(lenient("4k3/8/8/8/8/8/8/4K3"), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(lenient("4k3/8/8/8/8/8/8/4K3 b"), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(
+            lenient("4k3/8/8/8/8/8/8/4K3 w - - bm Kd2; hmvc 7; fmvn 30;"),
+            "4k3/8/8/8/8/8/8/4K3 w - - 7 30"
+        );
+        assert_eq!(lenient(&format!("{full} 1-0")), full);
+        assert!(Position::from_fen_lenient("4k3/8/8/8/8/8/8/4K3 x").is_err());
+        assert!(Position::from_fen_lenient("4k3/8/8/8/8/8/4K3 w").is_err());
+    }
+
     #[test]
     fn test_make_move() {
         let mut pos = Position::new();
@@ -580,4 +1349,412 @@ mod tests {
         assert_eq!(pos.side_to_move, Color::White);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_undo_restores_every_move() {
+        // Castling both ways, en passant, promotions with and without capture
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+            "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+        ] {
+            let mut pos = Position::from_fen(fen).unwrap();
+            let initial = pos.material_pst();
+            for mv in pos.generate_moves() {
+                pos.make_move(&mv).unwrap();
+                assert_eq!(pos.material_pst(), evaluate::material_pst(&pos.board, pos.params()));
+                pos.undo_move().unwrap();
+                assert_eq!(pos.to_fen(), fen, "after undoing {}", mv.to_algebraic());
+                assert_eq!(pos.material_pst(), initial);
+            }
+            assert!(pos.history.is_empty());
+        }
+    }
+
+    /// Standard perft positions with their expected node counts by depth
+    const PERFT_POSITIONS: [(&str, &[u64]); 6] = [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[20, 400, 8902],
+        ),
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862],
+        ),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812, 43238]),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        ),
+        (
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        ),
+        (
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            &[46, 2079],
+        ),
+    ];
+
+    #[test]
+    fn test_perft() {
+        for (fen, counts) in PERFT_POSITIONS {
+            let mut pos = Position::from_fen(fen).unwrap();
+            for (depth, &expected) in counts.iter().enumerate() {
+                assert_eq!(pos.perft(depth as u32 + 1), expected, "{fen} depth {}", depth + 1);
+            }
+            assert_eq!(pos.to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn test_perft_hashed() {
+        for (fen, counts) in PERFT_POSITIONS {
+            let mut pos = Position::from_fen(fen).unwrap();
+            let depth = counts.len() as u32;
+            assert_eq!(pos.perft_hashed(depth, 1 << 12), counts[counts.len() - 1], "{fen}");
+            // A table of one slot still counts correctly
+            assert_eq!(pos.perft_hashed(2, 1), counts[1], "{fen}");
+            assert_eq!(pos.to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn test_perft_divide() {
+        let mut pos = Position::new();
+        let divide = pos.perft_divide(3);
+        assert_eq!(divide.len(), 20);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+        let e2e4 = divide.iter().find(|(mv, _)| mv.to_algebraic() == "e2e4").unwrap();
+        assert_eq!(e2e4.1, 600);
+    }
+
+    #[test]
+    fn test_legality_predicates() {
+        // The e2 knight is pinned by the e8 rook; c5 can be taken en passant
+        let pos = Position::from_fen("4r1k1/8/8/2pP4/8/8/4N3/R3K2R w KQ c6 0 1").unwrap();
+        for mv in pos.generate_moves() {
+            assert!(pos.is_legal(&mv), "{}", mv.to_algebraic());
+        }
+
+        let sq = |name| Square::from_algebraic(name).unwrap();
+        let pinned = Move::new(sq("e2"), sq("c3"), PieceType::Knight);
+        assert!(pos.is_pseudo_legal(&pinned));
+        assert!(!pos.is_legal(&pinned));
+
+        // Wrong piece type, an enemy piece, a blocked slide and a pawn push
+        // missing its promotion are all rejected
+        assert!(!pos.is_pseudo_legal(&Move::new(sq("e2"), sq("c3"), PieceType::Bishop)));
+        assert!(!pos.is_pseudo_legal(&Move::new(sq("g8"), sq("g7"), PieceType::King)));
+        assert!(!pos.is_pseudo_legal(&Move::new(sq("a1"), sq("a1"), PieceType::Rook)));
+        assert!(!pos.is_pseudo_legal(&Move::new(sq("h1"), sq("e1"), PieceType::Rook)));
+        assert!(pos.is_legal(&Move::new_en_passant(sq("d5"), sq("c6"))));
+        assert!(!pos.is_legal(&Move::new_en_passant(sq("d5"), sq("e6"))));
+
+        assert!(pos.is_legal(&Move::new_castling(sq("e1"), sq("g1"), Color::White)));
+        assert!(!pos.is_legal(&Move::new_castling(sq("e1"), sq("g8"), Color::White)));
+
+        let promotion = Position::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(promotion.is_legal(&Move::new_promotion(
+            sq("a7"),
+            sq("a8"),
+            PieceType::Pawn,
+            PieceType::Queen
+        )));
+        assert!(!promotion.is_legal(&Move::new(sq("a7"), sq("a8"), PieceType::Pawn)));
+    }
+
+    #[test]
+    fn test_chess960_perft() {
+        for (fen, counts) in [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                [20, 479, 10471],
+            ),
+            (
+                "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+                [28, 1120, 31058],
+            ),
+        ] {
+            let mut pos = Position::from_fen(fen).unwrap();
+            for (depth, expected) in counts.into_iter().enumerate() {
+                assert_eq!(pos.perft(depth as u32 + 1), expected, "{fen} depth {}", depth + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+        // King on b1 castles with the g1 rook or the a1 rook
+        let fen = "4k3/8/8/8/8/8/8/RK4R1 w GA - 0 1";
+        let mut pos = Position::from_fen(fen).unwrap();
+        assert!(pos.castling_rights.is_chess960());
+        assert_eq!(pos.castling_rights.rook_files[Color::White as usize], [6, 0]);
+        assert_eq!(pos.to_fen(), "4k3/8/8/8/8/8/8/RK4R1 w KQ - 0 1");
+
+        let castles: Vec<Move> =
+            pos.generate_moves().into_iter().filter(|mv| mv.is_castling()).collect();
+        assert_eq!(castles.len(), 2);
+        for (mv, after) in castles.iter().zip(["R4RK1", "2KR2R1"]) {
+            pos.make_move(mv).unwrap();
+            assert!(pos.to_fen().starts_with(&format!("4k3/8/8/8/8/8/8/{after} b - ")));
+            pos.undo_move().unwrap();
+            assert_eq!(pos.to_fen(), "4k3/8/8/8/8/8/8/RK4R1 w KQ - 0 1");
+        }
+
+        // An inner rook is written by its file
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/RRK4R w B - 0 1").unwrap();
+        assert_eq!(pos.to_fen(), "4k3/8/8/8/8/8/8/RRK4R w B - 0 1");
+
+        // The b1 rook screens the king from the a1 queen only until it castles
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/qRK5 w B - 0 1").unwrap();
+        assert!(!pos.generate_moves().iter().any(|mv| mv.is_castling()));
+    }
+
+    #[test]
+    fn test_chess960_uci_notation() {
+        // King on b1 with rooks on a1 and g1; castling queenside lands it on c1
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/RK4R1 w GA - 0 1").unwrap();
+        let long = pos.parse_uci_move_with("b1a1", true).unwrap();
+        assert!(long.is_castling());
+        assert_eq!(long.to(), Square::C1);
+        assert_eq!(pos.move_to_uci(&long, true), "b1a1");
+        assert_eq!(pos.move_to_uci(&long, false), "b1c1");
+        let short = pos.parse_uci_move_with("B1G1", true).unwrap();
+        assert_eq!(pos.move_to_uci(&short, true), "b1g1");
+
+        // Plain king moves keep their notation and never stand for castling
+        let step = pos.parse_uci_move_with("b1c1", true).unwrap();
+        assert!(!step.is_castling());
+
+        let pos = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let castle = pos.parse_uci_move_with("e1h1", true).unwrap();
+        assert_eq!(castle, pos.parse_uci_move("e1g1").unwrap());
+        assert!(matches!(
+            pos.parse_uci_move_with("e1g1", true),
+            Err(Error::IllegalMove { .. })
+        ));
+        let pos = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w kq - 0 1").unwrap();
+        assert!(matches!(
+            pos.parse_uci_move_with("e1h1", true),
+            Err(Error::IllegalMove {
+                reason: IllegalMoveReason::NoCastlingRight,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_repetition() {
+        let mut pos = Position::new();
+        let play = |pos: &mut Position, line: &[&str]| {
+            for mv_str in line {
+                let mv = pos
+                    .generate_moves()
+                    .into_iter()
+                    .find(|mv| mv.to_algebraic() == *mv_str)
+                    .unwrap();
+                pos.make_move(&mv).unwrap();
+            }
+        };
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+        play(&mut pos, &shuffle);
+        assert_eq!(pos.repetition_count(), 1);
+        assert!(pos.is_repetition() && !pos.is_threefold_repetition());
+        play(&mut pos, &shuffle);
+        assert!(pos.is_threefold_repetition());
+
+        // A pawn move makes every earlier position unreachable
+        play(&mut pos, &["e2e3", "e7e6"]);
+        assert!(!pos.is_repetition());
+        play(&mut pos, &shuffle);
+        assert_eq!(pos.repetition_count(), 1);
+    }
+
+    #[test]
+    fn test_game_result() {
+        let result = |fen| Position::from_fen(fen).unwrap().result();
+        assert_eq!(result("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), None);
+        assert_eq!(
+            result("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"),
+            Some(GameResult::Checkmate { winner: Color::Black })
+        );
+        assert_eq!(result("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some(GameResult::Stalemate));
+        assert_eq!(result("k7/8/1K6/8/8/8/8/7N b - - 0 1"), Some(GameResult::InsufficientMaterial));
+        assert_eq!(
+            result("k7/8/1K6/8/8/8/8/7R b - - 100 80"),
+            Some(GameResult::FiftyMoveRule)
+        );
+        // Mate on the hundredth ply still wins
+        assert_eq!(
+            result("k7/1Q6/1K6/8/8/8/8/8 b - - 100 80"),
+            Some(GameResult::Checkmate { winner: Color::White })
+        );
+
+        let mut pos = Position::new();
+        for _ in 0..2 {
+            for mv_str in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                let mv = pos.generate_moves().into_iter().find(|mv| mv.to_algebraic() == mv_str);
+                pos.make_move(&mv.unwrap()).unwrap();
+            }
+        }
+        assert_eq!(pos.result(), Some(GameResult::ThreefoldRepetition));
+        assert!(pos.result().unwrap().is_draw());
+    }
+
+    #[test]
+    fn test_gives_check() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            // Castling with check, discovered check and en passant uncovering a bishop
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1",
+            "6k1/8/8/3pP3/8/8/B7/4K3 w - d6 0 1",
+        ] {
+            let mut pos = Position::from_fen(fen).unwrap();
+            for mv in pos.generate_moves() {
+                let predicted = pos.gives_check(&mv);
+                pos.make_move(&mv).unwrap();
+                assert_eq!(predicted, pos.in_check(), "{fen} {}", mv.to_algebraic());
+                pos.undo_move().unwrap();
+            }
+        }
+
+        let pos = Position::from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let castle = pos.generate_moves().into_iter().find(|mv| mv.is_castling()).unwrap();
+        assert!(pos.gives_check(&castle));
+    }
+
+    #[test]
+    fn test_checkers() {
+        assert!(Position::new().checkers().is_empty());
+        // Double check from the e8 rook and the d3 knight
+        let pos = Position::from_fen("4r1k1/8/8/8/8/3n4/8/4K3 w - - 0 1").unwrap();
+        let expected = Square::from_algebraic("e8").unwrap().bitboard()
+            | Square::from_algebraic("d3").unwrap().bitboard();
+        assert_eq!(pos.checkers(), expected);
+    }
+
+    #[test]
+    fn test_parse_uci_move() {
+        let fen = "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+        let pos = Position::from_fen(fen).unwrap();
+        assert!(pos.parse_uci_move("e1g1").unwrap().is_castling());
+        assert!(pos.parse_uci_move("E1C1").unwrap().is_castling());
+        assert!(pos.parse_uci_move("e5d6").unwrap().is_en_passant());
+        assert!(!pos.parse_uci_move("e5e6").unwrap().is_castling());
+        assert!(pos.parse_uci_move("e1e3").is_err());
+        assert!(pos.parse_uci_move("e1").is_err());
+        assert!(pos.parse_uci_move("e1g9").is_err());
+
+        let pos = Position::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mv = pos.parse_uci_move("a7a8n").unwrap();
+        assert_eq!(mv.promotion_piece(), Some(PieceType::Knight));
+        assert!(pos.parse_uci_move("a7a8k").is_err());
+    }
+
+    #[test]
+    fn test_illegal_move_reasons() {
+        use IllegalMoveReason::*;
+
+        let reason = |pos: &Position, uci: &str| match pos.parse_uci_move(uci) {
+            Err(Error::IllegalMove { reason, .. }) => Some(reason),
+            _ => None,
+        };
+        let pos = Position::new();
+        assert_eq!(reason(&pos, "e3e4"), Some(NoPiece(Square::E3)));
+        assert_eq!(reason(&pos, "e7e5"), Some(WrongColor(Square::E7)));
+        assert_eq!(reason(&pos, "d1d2"), Some(OwnPieceOnTarget(Square::D2)));
+        assert_eq!(reason(&pos, "e1g1"), Some(CastlingBlocked));
+        assert_eq!(reason(&pos, "e2d3"), Some(NoEnPassant(Square::D3)));
+        let unreachable = Unreachable {
+            from: Square::E2,
+            to: Square::E5,
+        };
+        assert_eq!(reason(&pos, "e2e5"), Some(unreachable));
+        assert_eq!(
+            pos.parse_uci_move("e2e5").unwrap_err().to_string(),
+            "Illegal move e2e5: the piece on e2 cannot reach e5"
+        );
+
+        let pos = Position::from_fen("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(reason(&pos, "e1g1"), Some(CastlingOutOfCheck));
+        assert_eq!(reason(&pos, "a1a2"), Some(LeavesKingInCheck));
+        let pos = Position::from_fen("4k3/8/8/8/8/8/5r2/R3K2R w Q - 0 1").unwrap();
+        assert_eq!(reason(&pos, "e1g1"), Some(NoCastlingRight));
+        let pos = Position::from_fen("4k3/8/8/8/8/8/5r2/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(reason(&pos, "e1g1"), Some(CastlingThroughCheck));
+        assert_eq!(pos.illegal_reason(&pos.parse_uci_move("e1c1").unwrap()), None);
+
+        let pos = Position::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(reason(&pos, "a7a8"), Some(MissingPromotion));
+        let king = Move::new_promotion(Square::A7, Square::A8, PieceType::Pawn, PieceType::King);
+        assert_eq!(pos.illegal_reason(&king), Some(InvalidPromotion));
+
+        let mut pos = Position::new();
+        let err = pos.make_move(&Move::new(Square::E4, Square::E5, PieceType::Pawn));
+        assert!(matches!(
+            err,
+            Err(Error::IllegalMove {
+                reason: NoPiece(Square::E4),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_mirrored_and_flipped() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut pos = Position::from_fen(fen).unwrap();
+        let mut mirrored = pos.mirrored();
+        assert_eq!(
+            mirrored.to_fen(),
+            "r3k2r/pppbbppp/2n2q1P/1P2p3/3pn3/BN2PNP1/P1PPQPB1/R3K2R b KQkq - 0 1"
+        );
+        assert_eq!(mirrored.mirrored().to_fen(), fen);
+        assert_eq!(evaluate::evaluate(&mirrored), evaluate::evaluate(&pos));
+        assert_eq!(mirrored.perft(2), pos.perft(2));
+
+        let pos = Position::from_fen("4k3/8/8/8/3pP3/8/8/R3K2R b KQ e3 0 1").unwrap();
+        assert_eq!(
+            pos.mirrored().to_fen(),
+            "r3k2r/8/8/3Pp3/8/8/8/4K3 w kq e6 0 1"
+        );
+
+        let flipped = pos.flipped_horizontally();
+        assert_eq!(flipped.to_fen(), "3k4/8/8/8/3Pp3/8/8/R2K3R b KQ d3 0 1");
+        assert!(flipped.castling_rights.has(Color::White, false));
+        assert_eq!(flipped.flipped_horizontally().to_fen(), pos.to_fen());
+
+        // Castling still ends on the c and g files, so compare move counts without it
+        let mut pos = Position::from_fen("4k3/8/8/8/3pP3/8/8/R3K2R b - e3 0 1").unwrap();
+        assert_eq!(pos.flipped_horizontally().perft(3), pos.perft(3));
+    }
+
+    #[test]
+    fn test_mate_in_one_and_hanging_material() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mate = pos.mate_in_one().unwrap();
+        assert_eq!(mate.to_algebraic(), "a1a8");
+        assert_eq!(pos.key(), Position::from_fen(&pos.to_fen()).unwrap().key());
+        assert!(Position::new().mate_in_one().is_none());
+        assert!(Position::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1")
+            .unwrap()
+            .mate_in_one()
+            .is_none());
+
+        assert_eq!(Position::new().hanging_material(), 0);
+        let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2";
+        assert_eq!(Position::from_fen(fen).unwrap().hanging_material(), 100);
+    }
+}
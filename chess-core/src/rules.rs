@@ -0,0 +1,230 @@
+//! Rule sets a `Position` is played under. Move generation, game end detection
+//! and the score of a finished game go through the position's `Rules`, so a
+//! variant plugs into move generation and search by implementing the trait.
+
+use crate::{
+    bitboard::{DARK_SQUARES, LIGHT_SQUARES},
+    board::{Board, Color, PieceType},
+    movegen,
+    moves::{Move, MoveList},
+    position::{GameResult, Position, FIFTY_MOVE_PLIES},
+    search::MATE_SCORE,
+};
+use std::sync::Arc;
+
+/// Names of the rule sets `by_name` provides
+pub const VARIANTS: [&str; 1] = ["chess"];
+
+/// The rule set called `name`, matched ignoring case as GUIs may send it
+pub fn by_name(name: &str) -> Option<Arc<dyn Rules>> {
+    match name.to_ascii_lowercase().as_str() {
+        "chess" => Some(Arc::new(Standard)),
+        _ => None,
+    }
+}
+
+/// Legality, terminal detection and scoring of one rule set
+pub trait Rules: std::fmt::Debug + Send + Sync {
+    /// Name of the rule set, as given to `UCI_Variant`
+    fn name(&self) -> &'static str;
+
+    /// Legal moves for the side to move
+    fn legal_moves(&self, position: &Position) -> MoveList;
+
+    /// Whether `mv` is legal for the side to move
+    fn is_legal(&self, position: &Position, mv: &Move) -> bool {
+        self.legal_moves(position).contains(mv)
+    }
+
+    /// Whether the side to move has a legal move
+    fn has_legal_move(&self, position: &Position) -> bool {
+        !self.legal_moves(position).is_empty()
+    }
+
+    /// How the game ended, or `None` while it goes on
+    fn result(&self, position: &Position) -> Option<GameResult>;
+
+    /// Score of the finished game for the side to move, `ply` plies from the root
+    /// so that quicker wins score higher
+    fn game_over_score(&self, position: &Position, result: GameResult, ply: u32) -> i32 {
+        match result.winner() {
+            Some(winner) if winner == position.side_to_move => MATE_SCORE - ply as i32,
+            Some(_) => -MATE_SCORE + ply as i32,
+            None => 0,
+        }
+    }
+}
+
+/// Standard chess. Castling follows the position's rook files, so this covers
+/// Chess960 too.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Standard;
+
+impl Rules for Standard {
+    fn name(&self) -> &'static str {
+        "chess"
+    }
+
+    fn legal_moves(&self, position: &Position) -> MoveList {
+        movegen::generate_legal_moves(
+            &position.board,
+            position.side_to_move,
+            &position.castling_rights,
+            position.en_passant,
+        )
+    }
+
+    fn is_legal(&self, position: &Position, mv: &Move) -> bool {
+        position.is_pseudo_legal(mv)
+            && movegen::is_legal(&position.board, position.side_to_move, mv)
+    }
+
+    fn result(&self, position: &Position) -> Option<GameResult> {
+        if !self.has_legal_move(position) {
+            return Some(if position.in_check() {
+                GameResult::Checkmate {
+                    winner: position.side_to_move.opposite(),
+                }
+            } else {
+                GameResult::Stalemate
+            });
+        }
+        if is_dead_material(&position.board) {
+            Some(GameResult::InsufficientMaterial)
+        } else if position.halfmove_clock >= FIFTY_MOVE_PLIES {
+            Some(GameResult::FiftyMoveRule)
+        } else if position.is_threefold_repetition() {
+            Some(GameResult::ThreefoldRepetition)
+        } else {
+            None
+        }
+    }
+}
+
+/// Material with which neither side can ever checkmate, however the other plays: a
+/// single minor piece at most, or only bishops that all stand on one square colour.
+/// Unlike `evaluate::is_insufficient_material`, two knights play on, as mate with
+/// them can still happen.
+fn is_dead_material(board: &Board) -> bool {
+    let both = |piece_type| {
+        board.piece_bitboard(Color::White, piece_type)
+            | board.piece_bitboard(Color::Black, piece_type)
+    };
+    let knights = both(PieceType::Knight);
+    let bishops = both(PieceType::Bishop);
+    let minors = knights | bishops;
+    if !(board.occupied & !(minors | both(PieceType::King))).is_empty() {
+        return false;
+    }
+
+    minors.count() <= 1
+        || (knights.is_empty()
+            && ((bishops & LIGHT_SQUARES).is_empty() || (bishops & DARK_SQUARES).is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::{PieceType, Square},
+        search::{self, SearchParams},
+    };
+
+    /// King of the hill: standard chess, also won by bringing the king to the centre
+    #[derive(Debug)]
+    struct KingOfTheHill;
+
+    impl Rules for KingOfTheHill {
+        fn name(&self) -> &'static str {
+            "kingofthehill"
+        }
+
+        fn legal_moves(&self, position: &Position) -> MoveList {
+            if self.result(position).is_some() {
+                return MoveList::new();
+            }
+            Standard.legal_moves(position)
+        }
+
+        fn result(&self, position: &Position) -> Option<GameResult> {
+            let mover = position.side_to_move.opposite();
+            let king = position.board.piece_bitboard(mover, PieceType::King);
+            let hill = [Square::D4, Square::E4, Square::D5, Square::E5];
+            if hill.iter().any(|square| king.has_square(square.0)) {
+                return Some(GameResult::VariantWin { winner: mover });
+            }
+            Standard.result(position)
+        }
+    }
+
+    #[test]
+    fn test_standard_rules() {
+        let position = Position::new();
+        assert_eq!(position.rules().name(), "chess");
+        assert_eq!(Standard.legal_moves(&position).len(), 20);
+
+        let fen = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+        let mated = Position::from_fen(fen).unwrap();
+        let result = Standard.result(&mated).unwrap();
+        assert_eq!(Standard.game_over_score(&mated, result, 3), -MATE_SCORE + 3);
+    }
+
+    #[test]
+    fn test_dead_material() {
+        let result = |fen| Standard.result(&Position::from_fen(fen).unwrap());
+        assert_eq!(
+            result("8/8/4k3/8/8/3K4/8/8 w - - 0 1"),
+            Some(GameResult::InsufficientMaterial)
+        );
+        assert_eq!(
+            result("8/8/4k3/8/8/3K4/8/5B2 w - - 0 1"),
+            Some(GameResult::InsufficientMaterial)
+        );
+        // Bishops on one square colour, whoever owns them
+        assert_eq!(
+            result("8/1b6/4k3/8/8/3K4/8/5B2 w - - 0 1"),
+            Some(GameResult::InsufficientMaterial)
+        );
+
+        // Two knights cannot force mate but may still deliver one
+        assert_eq!(result("8/8/4k3/8/8/3K4/8/4NN2 w - - 0 1"), None);
+        assert_eq!(result("8/8/4k3/8/8/3K4/8/4NB2 w - - 0 1"), None);
+        assert_eq!(result("8/2b5/4k3/8/8/3K4/8/5B2 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn test_rules_by_name() {
+        for name in VARIANTS {
+            assert_eq!(by_name(name).unwrap().name(), name);
+        }
+        assert_eq!(by_name("Chess").unwrap().name(), "chess");
+        assert!(by_name("crazyhouse").is_none());
+    }
+
+    #[test]
+    fn test_variant_rules_reach_search() {
+        let mut position = Position::from_fen("7k/8/8/8/8/4K3/8/r7 w - - 0 1").unwrap();
+        position.set_rules(Arc::new(KingOfTheHill));
+        assert_eq!(position.rules().name(), "kingofthehill");
+
+        let params = SearchParams {
+            depth: 2,
+            ..Default::default()
+        };
+        let result = search::search(&position, &params).unwrap();
+        let best = result.best_move.unwrap();
+        assert_eq!(best.from(), Square::E3);
+        assert!([Square::D4, Square::E4].contains(&best.to()));
+
+        position.make_move(&best).unwrap();
+        assert!(position.generate_moves().is_empty());
+        assert_eq!(
+            position.result(),
+            Some(GameResult::VariantWin {
+                winner: crate::board::Color::White
+            })
+        );
+        // Rules survive copies made for search and mirroring
+        assert_eq!(position.mirrored().rules().name(), "kingofthehill");
+    }
+}
@@ -0,0 +1,204 @@
+//! Standard Algebraic Notation: writing and reading moves such as "Nbd7", "exd6",
+//! "e8=Q+" or "O-O-O"
+
+use crate::{
+    board::{Color, PieceType, Square},
+    moves::Move,
+    position::Position,
+    Error, Result,
+};
+
+/// Annotation characters that may trail a SAN move
+const SUFFIXES: &[char] = &['+', '#', '!', '?'];
+
+impl Position {
+    /// SAN of the legal move `mv`, disambiguated against the other legal moves and
+    /// followed by "+" or "#" when it gives check or mate
+    pub fn move_to_san(&self, mv: &Move) -> String {
+        let mut san = self.san_without_suffix(mv, &self.generate_moves());
+
+        if self.gives_check(mv) {
+            let mut after = self.clone();
+            let mate = after.make_move(mv).is_ok() && !after.has_legal_move();
+            san.push(if mate { '#' } else { '+' });
+        }
+        san
+    }
+
+    /// The legal move written as `san`. Check and annotation suffixes are ignored,
+    /// and castling may be written with zeros.
+    pub fn san_to_move(&self, san: &str) -> Result<Move> {
+        let invalid = |reason: &str| Error::InvalidMove(format!("{reason}: {san}"));
+        let text = san.trim().trim_end_matches(SUFFIXES);
+        let moves = self.generate_moves();
+
+        if matches!(text, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+            let file = if text.len() == 3 { 6 } else { 2 };
+            return moves
+                .into_iter()
+                .find(|mv| mv.is_castling() && mv.to().file() == file)
+                .ok_or_else(|| invalid("Illegal castling"));
+        }
+
+        let mut chars: Vec<char> = text
+            .chars()
+            .filter(|&c| !matches!(c, 'x' | ':' | '-'))
+            .collect();
+        let piece_type = match chars.first() {
+            Some(&c) if c.is_ascii_uppercase() => {
+                chars.remove(0);
+                PieceType::from_char(c).ok_or_else(|| invalid("Unknown piece"))?
+            }
+            _ => PieceType::Pawn,
+        };
+        let promotion = match chars.last() {
+            Some(&c) if "QRBNqrbn".contains(c) && chars.len() > 2 => {
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+                PieceType::from_char(c)
+            }
+            _ => None,
+        };
+        if chars.len() < 2 {
+            return Err(invalid("Missing target square"));
+        }
+        let target: String = chars.split_off(chars.len() - 2).into_iter().collect();
+        let to = Square::from_algebraic(&target).ok_or_else(|| invalid("Invalid square"))?;
+
+        let (mut file, mut rank) = (None, None);
+        for c in chars {
+            match c {
+                'a'..='h' => file = Some(c as u8 - b'a'),
+                '1'..='8' => rank = Some(c as u8 - b'1'),
+                _ => return Err(invalid("Invalid disambiguation")),
+            }
+        }
+
+        let mut candidates = moves.into_iter().filter(|mv| {
+            !mv.is_castling()
+                && mv.piece_type() == piece_type
+                && mv.to() == to
+                && mv.promotion_piece() == promotion
+                && file.is_none_or(|file| mv.from().file() == file)
+                && rank.is_none_or(|rank| mv.from().rank() == rank)
+        });
+        match (candidates.next(), candidates.next()) {
+            (Some(mv), None) => Ok(mv),
+            (None, _) => Err(invalid("Illegal move")),
+            (Some(_), Some(_)) => Err(invalid("Ambiguous move")),
+        }
+    }
+
+    /// SAN of `mv` without the check suffix, disambiguated against `legal`
+    fn san_without_suffix(&self, mv: &Move, legal: &[Move]) -> String {
+        if mv.is_castling() {
+            return if mv.to().file() == 6 { "O-O" } else { "O-O-O" }.to_string();
+        }
+
+        let (from, to) = (mv.from(), mv.to());
+        let capture = mv.is_capture(&self.board);
+        let mut san = String::new();
+        if mv.piece_type() == PieceType::Pawn {
+            if capture {
+                san.push((b'a' + from.file()) as char);
+            }
+        } else {
+            san.push(mv.piece_type().to_char(Color::White));
+            let rivals: Vec<Square> = legal
+                .iter()
+                .filter(|other| {
+                    other.piece_type() == mv.piece_type()
+                        && other.to() == to
+                        && other.from() != from
+                })
+                .map(|other| other.from())
+                .collect();
+            if !rivals.is_empty() {
+                let file = rivals.iter().all(|square| square.file() != from.file());
+                let rank = rivals.iter().all(|square| square.rank() != from.rank());
+                let from_name = from.to_algebraic();
+                match (file, rank) {
+                    (true, _) => san.push_str(&from_name[..1]),
+                    (false, true) => san.push_str(&from_name[1..]),
+                    (false, false) => san.push_str(&from_name),
+                }
+            }
+        }
+
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&to.to_algebraic());
+        if let Some(promotion) = mv.promotion_piece() {
+            san.push('=');
+            san.push(promotion.to_char(Color::White));
+        }
+        san
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san_of(fen: &str, uci: &str) -> String {
+        let pos = Position::from_fen(fen).unwrap();
+        let mv = pos
+            .generate_moves()
+            .into_iter()
+            .find(|mv| mv.to_algebraic() == uci)
+            .unwrap();
+        pos.move_to_san(&mv)
+    }
+
+    #[test]
+    fn test_move_to_san() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san_of(start, "e2e4"), "e4");
+        assert_eq!(san_of(start, "g1f3"), "Nf3");
+
+        // Knights on b1 and f3 both reach d2; rooks on a1 and a5 both reach a3
+        let fen = "4k3/8/8/R7/8/8/8/RN2KN2 w - - 0 1";
+        assert_eq!(san_of(fen, "b1d2"), "Nbd2");
+        assert_eq!(san_of(fen, "a1a3"), "R1a3");
+        assert_eq!(san_of(fen, "e1d1"), "Kd1");
+
+        assert_eq!(san_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+        assert_eq!(
+            san_of("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q"),
+            "axb8=Q+"
+        );
+        assert_eq!(san_of("r3k3/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1"), "O-O");
+        // Fool's mate
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2";
+        assert_eq!(san_of(fen, "d8h4"), "Qh4#");
+    }
+
+    #[test]
+    fn test_san_to_move() {
+        let pos = Position::from_fen("4k3/8/8/R7/8/8/8/RN2KN2 w - - 0 1").unwrap();
+        let uci = |san| pos.san_to_move(san).unwrap().to_algebraic();
+        assert_eq!(uci("Nbd2"), "b1d2");
+        assert_eq!(uci("Nf1-d2"), "f1d2");
+        assert_eq!(uci("R1a3"), "a1a3");
+        assert_eq!(uci("Rb5+"), "a5b5");
+        assert!(pos.san_to_move("Nd2").is_err());
+        assert!(pos.san_to_move("Qd2").is_err());
+        assert!(pos.san_to_move("O-O").is_err());
+
+        let pos = Position::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(pos.san_to_move("axb8=N").unwrap().to_algebraic(), "a7b8n");
+        assert_eq!(pos.san_to_move("a8Q").unwrap().to_algebraic(), "a7a8q");
+    }
+
+    #[test]
+    fn test_san_roundtrip() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let pos = Position::from_fen(fen).unwrap();
+        for mv in pos.generate_moves() {
+            assert_eq!(pos.san_to_move(&pos.move_to_san(&mv)).unwrap(), mv);
+        }
+    }
+}
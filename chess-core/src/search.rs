@@ -1,11 +1,64 @@
 //! Search algorithms for chess engine
 
 use crate::{
-    evaluate,
-    moves::Move,
+    board::Color,
+    evaluate::{EvalCache, Evaluator, Nnue},
+    mcts,
+    moves::{Move, MoveList, MAX_MOVES},
+    params::Tunable,
     position::Position,
-    Error, Result,
+    search_trace::{CutoffReason, NodeKind, SearchTrace},
+    time::TimeManager,
+    tt::{Bound, TranspositionTable, TtEntry},
+    Result,
 };
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Score of being checkmated at the root; a mate in `n` plies scores `MATE_SCORE - n`
+pub const MATE_SCORE: i32 = 20000;
+
+/// Maximum search ply, which also bounds the range of mate scores
+pub const MAX_PLY: u32 = 128;
+
+/// Scores at or beyond this magnitude are mate scores
+pub const MATE_BOUND: i32 = MATE_SCORE - MAX_PLY as i32;
+
+/// Number of nodes between checks of the clock
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+/// Minimum time between progress reports to the observer
+const PROGRESS_INTERVAL_MS: u64 = 1000;
+
+/// Iterations the best move must survive unchanged before stopping early
+const STABLE_ITERATIONS: u32 = 4;
+
+/// Percentage of the soft limit after which a stable best move ends the search
+const STABLE_TIME_PERCENT: u64 = 50;
+
+/// Minimum depth at which multi-cut is tried
+const MULTI_CUT_DEPTH: u32 = 3;
+
+/// Extra depth reduction for the multi-cut verification searches
+const MULTI_CUT_REDUCTION: u32 = 1;
+
+/// Number of leading moves tried by multi-cut
+const MULTI_CUT_MOVES: usize = 6;
+
+/// Fail-highs among those moves needed to prune the node
+const MULTI_CUT_REQUIRED: usize = 3;
+
+/// Size of the table allocated for searches that are not given one
+const LOCAL_TT_SIZE_MB: usize = 1;
+
+/// Highest skill level, which disables strength limiting
+pub const MAX_SKILL_LEVEL: u8 = 20;
+
+/// Elo range covered by the skill levels
+pub const MIN_ELO: u32 = 1350;
+pub const MAX_ELO: u32 = 2850;
 
 /// Search statistics
 #[derive(Debug, Default, Clone)]
@@ -13,7 +66,15 @@ pub struct SearchStats {
     pub nodes_searched: u64,
     pub qnodes_searched: u64,
     pub cutoffs: u64,
+    /// Nodes pruned by multi-cut
+    pub multi_cuts: u64,
     pub depth: u32,
+    /// Deepest ply reached, including quiescence
+    pub seldepth: u32,
+    /// Wall-clock time spent in this search
+    pub elapsed_ms: u64,
+    /// Time spent on each completed iterative deepening iteration
+    pub iteration_times_ms: Vec<u64>,
 }
 
 /// Search result
@@ -23,6 +84,70 @@ pub struct SearchResult {
     pub score: i32,
     pub depth: u32,
     pub stats: SearchStats,
+    /// Visited nodes, present when `SearchParams::trace` was set
+    pub trace: Option<SearchTrace>,
+}
+
+/// Tree search used by `think`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchAlgorithm {
+    #[default]
+    AlphaBeta,
+    /// Monte Carlo tree search, see `mcts`
+    Mcts,
+}
+
+/// Search constants that external tuners may adjust
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchTuning {
+    pub stable_iterations: u32,
+    pub stable_time_percent: u64,
+    pub multi_cut_depth: u32,
+    pub multi_cut_reduction: u32,
+    pub multi_cut_moves: usize,
+    pub multi_cut_required: usize,
+}
+
+impl Default for SearchTuning {
+    fn default() -> Self {
+        Self {
+            stable_iterations: STABLE_ITERATIONS,
+            stable_time_percent: STABLE_TIME_PERCENT,
+            multi_cut_depth: MULTI_CUT_DEPTH,
+            multi_cut_reduction: MULTI_CUT_REDUCTION,
+            multi_cut_moves: MULTI_CUT_MOVES,
+            multi_cut_required: MULTI_CUT_REQUIRED,
+        }
+    }
+}
+
+impl SearchTuning {
+    /// Every constant with its current value and the range a tuner may explore
+    pub fn tunables(&self) -> Vec<Tunable> {
+        vec![
+            Tunable::new("stable_iterations", self.stable_iterations as i32, 1, 10),
+            Tunable::new("stable_time_percent", self.stable_time_percent as i32, 10, 100),
+            Tunable::new("multi_cut_depth", self.multi_cut_depth as i32, 2, 8),
+            Tunable::new("multi_cut_reduction", self.multi_cut_reduction as i32, 0, 3),
+            Tunable::new("multi_cut_moves", self.multi_cut_moves as i32, 1, 16),
+            Tunable::new("multi_cut_required", self.multi_cut_required as i32, 1, 8),
+        ]
+    }
+
+    /// Set the constant called `name`, reporting whether it exists
+    pub fn set_tunable(&mut self, name: &str, value: i32) -> bool {
+        let value = value.max(0);
+        match name {
+            "stable_iterations" => self.stable_iterations = value as u32,
+            "stable_time_percent" => self.stable_time_percent = value as u64,
+            "multi_cut_depth" => self.multi_cut_depth = value as u32,
+            "multi_cut_reduction" => self.multi_cut_reduction = value as u32,
+            "multi_cut_moves" => self.multi_cut_moves = value as usize,
+            "multi_cut_required" => self.multi_cut_required = value as usize,
+            _ => return false,
+        }
+        true
+    }
 }
 
 /// Search parameters
@@ -30,7 +155,36 @@ pub struct SearchResult {
 pub struct SearchParams {
     pub depth: u32,
     pub time_limit_ms: Option<u64>,
+    /// Time left on White's and Black's clocks (UCI `wtime` and `btime`)
+    pub wtime_ms: Option<u64>,
+    pub btime_ms: Option<u64>,
+    /// Increment per move for White and Black (UCI `winc` and `binc`)
+    pub winc_ms: u64,
+    pub binc_ms: u64,
+    /// Moves left until the next time control (UCI `movestogo`)
+    pub moves_to_go: Option<u32>,
     pub nodes_limit: Option<u64>,
+    /// Look for a forced mate in this many moves before the regular search
+    pub mate: Option<u32>,
+    /// Record every visited node into `SearchResult::trace`
+    pub trace: bool,
+    /// Restrict the root to these moves (UCI `searchmoves`)
+    pub search_moves: Option<Vec<Move>>,
+    /// Raised to abort the search; shared with whoever controls it
+    pub stop: Arc<AtomicBool>,
+    /// Raised while the search ponders on the opponent's time. Time limits only
+    /// apply once it is lowered again (UCI `ponderhit`), counted from the start.
+    pub ponder: Arc<AtomicBool>,
+    /// Weaken play to this level (0-20) in `think`; `None` plays at full strength
+    pub skill_level: Option<u8>,
+    /// Shared transposition table; a small private one is used when absent
+    pub tt: Option<Arc<TranspositionTable>>,
+    /// Only honoured by `think`; `search` always uses alpha-beta
+    pub algorithm: SearchAlgorithm,
+    /// Pruning and time management constants
+    pub tuning: SearchTuning,
+    /// Backend scoring the leaves
+    pub evaluator: Arc<dyn Evaluator>,
 }
 
 impl Default for SearchParams {
@@ -38,125 +192,784 @@ impl Default for SearchParams {
         Self {
             depth: 4,
             time_limit_ms: None,
+            wtime_ms: None,
+            btime_ms: None,
+            winc_ms: 0,
+            binc_ms: 0,
+            moves_to_go: None,
             nodes_limit: None,
+            mate: None,
+            trace: false,
+            search_moves: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            ponder: Arc::new(AtomicBool::new(false)),
+            skill_level: None,
+            tt: None,
+            algorithm: SearchAlgorithm::AlphaBeta,
+            tuning: SearchTuning::default(),
+            evaluator: Arc::new(Nnue),
+        }
+    }
+}
+
+impl SearchParams {
+    /// Time limits for `side` to move: a fixed `time_limit_ms` takes precedence over
+    /// the clock, and without either the search is unlimited
+    pub fn time_manager(&self, side: Color) -> TimeManager {
+        let (remaining, increment) = match side {
+            Color::White => (self.wtime_ms, self.winc_ms),
+            Color::Black => (self.btime_ms, self.binc_ms),
+        };
+        match (self.time_limit_ms, remaining) {
+            (Some(ms), _) => TimeManager::new(Some(ms)),
+            (None, Some(ms)) => TimeManager::for_clock(ms, increment, self.moves_to_go),
+            (None, None) => TimeManager::unlimited(),
+        }
+    }
+
+    /// Whether the search is bounded by a fixed time or the clock of either side
+    pub fn has_time_limit(&self) -> bool {
+        self.time_limit_ms.is_some() || self.wtime_ms.is_some() || self.btime_ms.is_some()
+    }
+}
+
+/// Outcome of a completed iterative deepening iteration
+#[derive(Debug, Clone)]
+pub struct IterationInfo {
+    pub depth: u32,
+    pub seldepth: u32,
+    pub score: i32,
+    pub best_move: Option<Move>,
+    /// Principal variation, starting with the best move
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+    pub elapsed_ms: u64,
+}
+
+/// Receives events while a search runs, e.g. to stream UCI `info` lines.
+///
+/// Every method has an empty default so observers only implement what they need.
+pub trait SearchObserver {
+    /// An iteration finished searching every root move
+    fn on_iteration(&mut self, _info: &IterationInfo) {}
+
+    /// The root is about to search `mv`, the `number`th move (1-based) at `depth`
+    fn on_current_move(&mut self, _mv: Move, _number: usize, _depth: u32) {}
+
+    /// Periodic report of the total nodes searched so far
+    fn on_progress(&mut self, _nodes: u64, _elapsed_ms: u64) {}
+}
+
+/// Observer that ignores every event
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullObserver;
+
+impl SearchObserver for NullObserver {}
+
+/// Convert a mate score into signed moves to mate (positive when the side to move mates)
+pub fn mate_in(score: i32) -> Option<i32> {
+    if (MATE_BOUND..=MATE_SCORE).contains(&score) {
+        Some((MATE_SCORE - score + 1) / 2)
+    } else if (-MATE_SCORE..=-MATE_BOUND).contains(&score) {
+        Some(-(MATE_SCORE + score) / 2)
+    } else {
+        None
+    }
+}
+
+/// Map a UCI_Elo rating onto a skill level
+pub fn skill_from_elo(elo: u32) -> u8 {
+    let elo = elo.clamp(MIN_ELO, MAX_ELO);
+    ((elo - MIN_ELO) * MAX_SKILL_LEVEL as u32 / (MAX_ELO - MIN_ELO)) as u8
+}
+
+/// Node budget for a weakened search at the given skill level
+fn skill_nodes_limit(skill: u8) -> u64 {
+    let skill = skill as u64 + 1;
+    1000 * skill * skill
+}
+
+/// Score margin within which a weakened search may pick a worse root move
+fn skill_margin(skill: u8) -> i32 {
+    (MAX_SKILL_LEVEL.saturating_sub(skill)) as i32 * 10
+}
+
+/// A root move with the outcome of the last iteration that searched it
+#[derive(Debug, Clone, Copy)]
+struct RootMove {
+    mv: Move,
+    score: i32,
+    nodes: u64,
+}
+
+/// Mutable state threaded through a single search
+struct SearchContext<'a> {
+    stats: SearchStats,
+    trace: Option<SearchTrace>,
+    /// Root moves, kept across iterations to order the next one
+    root_moves: Vec<RootMove>,
+    tt: Arc<TranspositionTable>,
+    eval_cache: EvalCache,
+    evaluator: Arc<dyn Evaluator>,
+    time: TimeManager,
+    stop: Arc<AtomicBool>,
+    ponder: Arc<AtomicBool>,
+    nodes_limit: Option<u64>,
+    /// Limits are ignored until at least one root move has been fully searched
+    can_abort: bool,
+    stopped: bool,
+    observer: &'a mut dyn SearchObserver,
+    last_progress_ms: u64,
+    tuning: SearchTuning,
+}
+
+impl<'a> SearchContext<'a> {
+    fn new(params: &SearchParams, observer: &'a mut dyn SearchObserver) -> Self {
+        let ctx = Self::sharing_generation(params, observer);
+        ctx.tt.new_search();
+        ctx
+    }
+
+    /// A context storing into the table under its current generation, for helper
+    /// threads joining a search that has already started one
+    fn sharing_generation(params: &SearchParams, observer: &'a mut dyn SearchObserver) -> Self {
+        let skill_limit = params
+            .skill_level
+            .filter(|&skill| skill < MAX_SKILL_LEVEL)
+            .map(skill_nodes_limit);
+        let nodes_limit = match (params.nodes_limit, skill_limit) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        let tt = params
+            .tt
+            .clone()
+            .unwrap_or_else(|| Arc::new(TranspositionTable::new(LOCAL_TT_SIZE_MB)));
+
+        Self {
+            stats: SearchStats::default(),
+            trace: params.trace.then(SearchTrace::new),
+            root_moves: Vec::new(),
+            tt,
+            eval_cache: EvalCache::default(),
+            evaluator: Arc::clone(&params.evaluator),
+            time: TimeManager::new(params.time_limit_ms),
+            stop: Arc::clone(&params.stop),
+            ponder: Arc::clone(&params.ponder),
+            nodes_limit,
+            can_abort: false,
+            stopped: false,
+            observer,
+            last_progress_ms: 0,
+            tuning: params.tuning,
+        }
+    }
+
+    fn nodes(&self) -> u64 {
+        self.stats.nodes_searched + self.stats.qnodes_searched
+    }
+
+    /// Whether the search ponders, ignoring its time limits for now
+    fn pondering(&self) -> bool {
+        self.ponder.load(Ordering::Relaxed)
+    }
+
+    /// Poll the external stop flag and the limits, latching `stopped` once any fires.
+    /// Progress is reported to the observer from here as well.
+    ///
+    /// Limits only stop this search; the shared flag is left untouched so the same
+    /// parameters can be reused for the next search.
+    fn should_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+
+        let nodes = self.nodes();
+        if nodes.is_multiple_of(TIME_CHECK_INTERVAL) {
+            let elapsed = self.time.elapsed_ms();
+            if elapsed >= self.last_progress_ms + PROGRESS_INTERVAL_MS {
+                self.last_progress_ms = elapsed;
+                self.observer.on_progress(nodes, elapsed);
+            }
+        }
+
+        if !self.can_abort {
+            return false;
+        }
+
+        self.stopped = self.stop.load(Ordering::Relaxed)
+            || self.nodes_limit.is_some_and(|limit| nodes >= limit)
+            || (nodes.is_multiple_of(TIME_CHECK_INTERVAL)
+                && self.time.hard_limit_reached()
+                && !self.pondering());
+        self.stopped
+    }
+
+    fn trace_enter(
+        &mut self,
+        kind: NodeKind,
+        ply: u32,
+        depth: u32,
+        alpha: i32,
+        beta: i32,
+    ) -> Option<usize> {
+        self.trace
+            .as_mut()
+            .and_then(|trace| trace.enter(kind, ply, depth, alpha, beta))
+    }
+
+    /// Record a node's outcome and pass its score through
+    fn trace_exit(
+        &mut self,
+        id: Option<usize>,
+        score: i32,
+        best_move: Option<Move>,
+        cutoff: CutoffReason,
+    ) -> i32 {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.exit(id, score, best_move, cutoff);
+        }
+        score
+    }
+
+    fn into_result(mut self, best_move: Option<Move>, score: i32, depth: u32) -> SearchResult {
+        self.stats.depth = depth;
+        self.stats.elapsed_ms = self.time.elapsed_ms();
+        SearchResult {
+            best_move,
+            score,
+            depth,
+            stats: self.stats,
+            trace: self.trace,
         }
     }
 }
 
 /// Search for the best move in a position
 pub fn search(position: &Position, params: &SearchParams) -> Result<SearchResult> {
-    let mut stats = SearchStats::default();
-    stats.depth = params.depth;
+    let mut observer = NullObserver;
+    let mut ctx = SearchContext::new(params, &mut observer);
+    ctx.time = params.time_manager(position.side_to_move);
 
-    // Check for immediate game over
-    if position.is_game_over() {
-        return Ok(SearchResult {
-            best_move: None,
-            score: evaluate_game_over(position),
-            depth: 0,
-            stats,
-        });
+    if let Some(moves) = params.mate {
+        if let Some((mv, plies)) = find_mate(position, moves, params, &mut ctx)? {
+            return Ok(ctx.into_result(Some(mv), MATE_SCORE - plies as i32, plies));
+        }
+    }
+
+    // Check for immediate game over; a draw the opponent may not claim is still played
+    if !position.has_legal_move() {
+        let score = evaluate_game_over(position, 0);
+        return Ok(ctx.into_result(None, score, 0));
+    }
+
+    // All moves are made and unmade on this single copy
+    let mut position = position.clone();
+    let (best_move, best_score, _) = search_root(&mut position, params, params.depth, &mut ctx)?;
+    Ok(ctx.into_result(best_move, best_score, params.depth))
+}
+
+/// Iterative deepening up to `params.depth`, honouring time, node and stop limits.
+///
+/// A new iteration is only started while the soft time limit has not passed and
+/// the next iteration is expected to finish; the hard limit aborts mid-iteration,
+/// in which case the result of the last completed iteration is returned. Timed
+/// searches also end early on a single legal move or a long-stable best move.
+pub fn think(position: &Position, params: &SearchParams) -> Result<SearchResult> {
+    think_with_observer(position, params, &mut NullObserver)
+}
+
+/// Like `think`, reporting iterations, root moves and progress to `observer`
+pub fn think_with_observer(
+    position: &Position,
+    params: &SearchParams,
+    observer: &mut dyn SearchObserver,
+) -> Result<SearchResult> {
+    think_with_context(position, params, SearchContext::new(params, observer))
+}
+
+/// Like `think_with_observer` under the table's current generation, for the main
+/// thread of a parallel search whose pool has already started a new one, see `smp`
+pub(crate) fn think_sharing_generation(
+    position: &Position,
+    params: &SearchParams,
+    observer: &mut dyn SearchObserver,
+) -> Result<SearchResult> {
+    think_with_context(position, params, SearchContext::sharing_generation(params, observer))
+}
+
+/// The mate search, MCTS or iterative deepening `params` asks for, run with `ctx`
+fn think_with_context(
+    position: &Position,
+    params: &SearchParams,
+    mut ctx: SearchContext,
+) -> Result<SearchResult> {
+    ctx.time = params.time_manager(position.side_to_move);
+
+    if let Some(moves) = params.mate {
+        if let Some((mv, plies)) = find_mate(position, moves, params, &mut ctx)? {
+            let score = MATE_SCORE - plies as i32;
+            let info = IterationInfo {
+                depth: plies,
+                seldepth: ctx.stats.seldepth,
+                score,
+                best_move: Some(mv),
+                pv: vec![mv],
+                nodes: ctx.nodes(),
+                elapsed_ms: ctx.time.elapsed_ms(),
+            };
+            ctx.observer.on_iteration(&info);
+            return Ok(ctx.into_result(Some(mv), score, plies));
+        }
     }
 
+    if params.algorithm == SearchAlgorithm::Mcts {
+        return mcts::search(position, params, ctx.observer);
+    }
+
+    deepen(position, params, ctx)
+}
+
+/// Iterative deepening for a helper thread of a parallel search, see `smp`. It
+/// shares the main search's table and generation and reports to no observer.
+pub(crate) fn think_helper(position: &Position, params: &SearchParams) -> Result<SearchResult> {
+    let mut observer = NullObserver;
+    let mut ctx = SearchContext::sharing_generation(params, &mut observer);
+    ctx.time = params.time_manager(position.side_to_move);
+    deepen(position, params, ctx)
+}
+
+/// The iterative deepening loop of `think`, run with `ctx` on the clock it started
+fn deepen(
+    position: &Position,
+    params: &SearchParams,
+    mut ctx: SearchContext,
+) -> Result<SearchResult> {
+    if !position.has_legal_move() {
+        let score = evaluate_game_over(position, 0);
+        return Ok(ctx.into_result(None, score, 0));
+    }
+
+    let mut best: Option<(Option<Move>, i32, u32)> = None;
+    let mut root_moves = Vec::new();
+    let mut iteration_start = ctx.time.elapsed_ms();
+    let mut root = position.clone();
+    let mut stable_iterations = 0;
+
+    // With a single legal reply there is nothing to think about on the clock
+    let mut legal = legal_moves(&root);
+    if let Some(allowed) = &params.search_moves {
+        legal.retain(|mv| allowed.iter().any(|other| same_move(mv, other)));
+    }
+    let single_move = ctx.time.is_limited() && legal.len() == 1;
+
+    for depth in 1..=params.depth.max(1) {
+        // Only the first iteration has to produce a move before limits apply
+        ctx.can_abort = best.is_some();
+
+        let (best_move, score, completed) = search_root(&mut root, params, depth, &mut ctx)?;
+        if !completed {
+            if best.is_none() {
+                best = Some((best_move, score, depth));
+            }
+            break;
+        }
+
+        let now = ctx.time.elapsed_ms();
+        ctx.stats.iteration_times_ms.push(now - iteration_start);
+        iteration_start = now;
+        if best.is_some_and(|(previous, _, _)| previous == best_move) {
+            stable_iterations += 1;
+        } else {
+            stable_iterations = 0;
+        }
+        best = Some((best_move, score, depth));
+        root_moves = ctx.root_moves.clone();
+
+        let info = IterationInfo {
+            depth,
+            seldepth: ctx.stats.seldepth,
+            score,
+            best_move,
+            pv: principal_variation(&mut root, &ctx.tt, depth as usize),
+            nodes: ctx.nodes(),
+            elapsed_ms: now,
+        };
+        ctx.observer.on_iteration(&info);
+
+        // If we found a checkmate, we can stop early
+        if score.abs() >= MATE_BOUND {
+            break;
+        }
+        if ctx.pondering() {
+            continue;
+        }
+        if ctx.time.soft_limit_reached() || single_move {
+            break;
+        }
+
+        if stable_iterations >= ctx.tuning.stable_iterations
+            && ctx.time.soft_limit_percent_reached(ctx.tuning.stable_time_percent)
+        {
+            break;
+        }
+
+        let times = &ctx.stats.iteration_times_ms;
+        let last = times[times.len() - 1];
+        let previous = times.len().checked_sub(2).map(|i| times[i]);
+        if !ctx.time.can_finish_next_iteration(last, previous) {
+            break;
+        }
+    }
+
+    let (mut best_move, mut score, depth) = best.expect("at least one iteration is searched");
+
+    if let Some(skill) = params.skill_level.filter(|&skill| skill < MAX_SKILL_LEVEL) {
+        if let Some((mv, weakened_score)) = pick_weakened_move(position, &root_moves, skill) {
+            best_move = Some(mv);
+            score = weakened_score;
+        }
+    }
+
+    Ok(ctx.into_result(best_move, score, depth))
+}
+
+/// Pick a root move among those scoring within the skill margin of the best.
+///
+/// The choice is pseudo-random but seeded from the position and skill level, so
+/// the same position is always answered with the same move.
+fn pick_weakened_move(
+    position: &Position,
+    root_moves: &[RootMove],
+    skill: u8,
+) -> Option<(Move, i32)> {
+    let best = root_moves.iter().map(|root| root.score).max()?;
+    let candidates: Vec<(Move, i32)> = root_moves
+        .iter()
+        .filter(|root| root.score >= best - skill_margin(skill))
+        .map(|root| (root.mv, root.score))
+        .collect();
+
+    // FNV-1a over the FEN, then one xorshift round
+    let mut seed = position
+        .to_fen()
+        .bytes()
+        .chain(std::iter::once(skill))
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    seed ^= seed << 13;
+    seed ^= seed >> 7;
+    seed ^= seed << 17;
+
+    candidates.get((seed % candidates.len() as u64) as usize).copied()
+}
+
+/// Search all root moves to `depth`, returning the best move, its score and
+/// whether the iteration completed before the search was stopped.
+///
+/// The first iteration orders root moves with the static heuristics; later ones
+/// search the previous best move first, followed by the others in order of their
+/// previous score and then the effort spent on them.
+fn search_root(
+    position: &mut Position,
+    params: &SearchParams,
+    depth: u32,
+    ctx: &mut SearchContext,
+) -> Result<(Option<Move>, i32, bool)> {
     let mut best_move = None;
     let mut best_score = i32::MIN + 1;
+    let root_id = ctx.trace_enter(NodeKind::Main, 0, depth, i32::MIN + 1, i32::MAX - 1);
 
-    // Generate all moves
-    let moves = position.generate_moves();
+    let key = position.key();
+    if ctx.root_moves.is_empty() {
+        // Generate all moves, keeping only the allowed root moves
+        let mut moves = position.generate_moves();
+        if let Some(allowed) = &params.search_moves {
+            moves.retain(|mv| allowed.iter().any(|other| same_move(mv, other)));
+        }
 
-    for mv in moves {
-        let mut new_pos = position.clone();
-        new_pos.make_move(&mv)?;
+        let tt_move = ctx.tt.probe(key).and_then(|entry| entry.best_move);
+        ctx.root_moves = order_moves(position, moves, tt_move)
+            .into_iter()
+            .map(|mv| RootMove {
+                mv,
+                score: i32::MIN + 1,
+                nodes: 0,
+            })
+            .collect();
+    } else {
+        // The previous best move was moved to the front when its iteration completed
+        ctx.root_moves[1..].sort_by(|a, b| b.score.cmp(&a.score).then(b.nodes.cmp(&a.nodes)));
+    }
 
-        let score = -alpha_beta(
-            &new_pos,
-            params.depth - 1,
-            i32::MIN + 1,
-            i32::MAX - 1,
-            &mut stats,
-        );
+    for i in 0..ctx.root_moves.len() {
+        let mv = ctx.root_moves[i].mv;
+        let nodes_before = ctx.nodes();
+        ctx.observer.on_current_move(mv, i + 1, depth);
+
+        if !make(position, &mv) {
+            continue;
+        }
+        // Only the first root move is expected to be a PV node
+        let cut_node = i > 0;
+        let score = -alpha_beta(position, depth - 1, 1, i32::MIN + 1, i32::MAX - 1, cut_node, ctx);
+        unmake(position);
+
+        if ctx.stopped {
+            ctx.trace_exit(root_id, best_score, best_move, CutoffReason::None);
+            return Ok((best_move, best_score, false));
+        }
+
+        let nodes = ctx.nodes() - nodes_before;
+        let root_move = &mut ctx.root_moves[i];
+        root_move.score = score;
+        root_move.nodes = nodes;
 
         if score > best_score {
             best_score = score;
             best_move = Some(mv);
         }
+        ctx.can_abort = true;
     }
 
-    Ok(SearchResult {
-        best_move,
-        score: best_score,
-        depth: params.depth,
-        stats,
-    })
+    if let Some(best) = ctx.root_moves.iter().position(|root| Some(root.mv) == best_move) {
+        ctx.root_moves[..=best].rotate_right(1);
+    }
+
+    ctx.tt.store(
+        key,
+        TtEntry {
+            best_move,
+            score: best_score,
+            depth: depth.min(u8::MAX as u32) as u8,
+            bound: Bound::Exact,
+        },
+    );
+
+    ctx.trace_exit(root_id, best_score, best_move, CutoffReason::None);
+    Ok((best_move, best_score, true))
 }
 
-/// Alpha-beta search algorithm
+/// Alpha-beta search algorithm.
+///
+/// `cut_node` marks nodes expected to fail high; their children are expected
+/// not to, and vice versa.
 fn alpha_beta(
-    position: &Position,
+    position: &mut Position,
     depth: u32,
+    ply: u32,
     mut alpha: i32,
     beta: i32,
-    stats: &mut SearchStats,
+    cut_node: bool,
+    ctx: &mut SearchContext,
 ) -> i32 {
-    stats.nodes_searched += 1;
+    ctx.stats.nodes_searched += 1;
+    ctx.stats.seldepth = ctx.stats.seldepth.max(ply);
+
+    if ctx.should_stop() {
+        return 0;
+    }
 
     // Check for terminal node
     if depth == 0 {
-        return quiescence_search(position, alpha, beta, stats);
+        return quiescence_search(position, ply, alpha, beta, ctx);
+    }
+
+    let id = ctx.trace_enter(NodeKind::Main, ply, depth, alpha, beta);
+
+    // Repeating a position gains nothing, so score it as the draw it can become
+    if position.is_repetition() {
+        return ctx.trace_exit(id, 0, None, CutoffReason::Terminal);
     }
 
     if position.is_game_over() {
-        return evaluate_game_over(position);
+        let score = evaluate_game_over(position, ply);
+        return ctx.trace_exit(id, score, None, CutoffReason::Terminal);
     }
 
-    let moves = position.generate_moves();
+    let key = position.key();
+    let mut tt_move = None;
+    if let Some(entry) = ctx.tt.probe(key) {
+        tt_move = entry.best_move;
+        if entry.depth as u32 >= depth {
+            let score = score_from_tt(entry.score, ply);
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if usable {
+                let score = score.clamp(alpha, beta);
+                return ctx.trace_exit(id, score, entry.best_move, CutoffReason::TtHit);
+            }
+        }
+    }
 
-    // Sort moves (basic implementation - could be improved with move ordering)
-    let mut scored_moves: Vec<(Move, i32)> = moves
-        .into_iter()
-        .map(|mv| {
-            let score = move_score(position, &mv);
-            (mv, score)
-        })
-        .collect();
+    let original_alpha = alpha;
+    let moves = order_moves(position, position.generate_moves(), tt_move);
+    let mut best_move = None;
+    let mut legal_moves = 0;
 
-    // Sort by score (highest first for maximizing player)
-    scored_moves.sort_by(|a, b| b.1.cmp(&a.1));
+    if cut_node
+        && depth >= ctx.tuning.multi_cut_depth
+        && beta.abs() < MATE_BOUND
+        && !position.in_check()
+        && multi_cut(position, &moves, depth, ply, beta, ctx)
+    {
+        ctx.stats.multi_cuts += 1;
+        return ctx.trace_exit(id, beta, None, CutoffReason::MultiCut);
+    }
 
-    for (mv, _) in scored_moves {
-        let mut new_pos = position.clone();
-        if new_pos.make_move(&mv).is_err() {
-            continue; // Skip illegal moves
+    for mv in moves {
+        if !make(position, &mv) {
+            continue;
         }
+        legal_moves += 1;
+        let score = -alpha_beta(position, depth - 1, ply + 1, -beta, -alpha, !cut_node, ctx);
+        unmake(position);
 
-        let score = -alpha_beta(&new_pos, depth - 1, -beta, -alpha, stats);
+        if ctx.stopped {
+            return 0;
+        }
 
         if score >= beta {
-            stats.cutoffs += 1;
-            return beta; // Beta cutoff
+            ctx.stats.cutoffs += 1;
+            store_tt(ctx, key, depth, ply, beta, Bound::Lower, Some(mv));
+            return ctx.trace_exit(id, beta, Some(mv), CutoffReason::BetaCutoff);
         }
 
         if score > alpha {
             alpha = score;
+            best_move = Some(mv);
         }
     }
 
-    alpha
+    // No legal move: checkmate or stalemate
+    if legal_moves == 0 {
+        let score = if position.in_check() {
+            -MATE_SCORE + ply as i32
+        } else {
+            0
+        };
+        return ctx.trace_exit(id, score, None, CutoffReason::Terminal);
+    }
+
+    let bound = if alpha > original_alpha {
+        Bound::Exact
+    } else {
+        Bound::Upper
+    };
+    store_tt(ctx, key, depth, ply, alpha, bound, best_move);
+
+    ctx.trace_exit(id, alpha, best_move, CutoffReason::None)
+}
+
+/// Search the first moves at reduced depth with a null window around `beta`,
+/// reporting whether enough of them fail high to prune the node.
+fn multi_cut(
+    position: &mut Position,
+    moves: &[Move],
+    depth: u32,
+    ply: u32,
+    beta: i32,
+    ctx: &mut SearchContext,
+) -> bool {
+    let reduced = depth.saturating_sub(1 + ctx.tuning.multi_cut_reduction);
+    let mut tried = 0;
+    let mut cuts = 0;
+
+    for mv in moves {
+        if tried == ctx.tuning.multi_cut_moves {
+            break;
+        }
+        if !make(position, mv) {
+            continue;
+        }
+        tried += 1;
+        // The children of an expected cut node are expected all-nodes
+        let score = -alpha_beta(position, reduced, ply + 1, -beta, -beta + 1, false, ctx);
+        unmake(position);
+
+        if ctx.stopped {
+            return false;
+        }
+        if score >= beta {
+            cuts += 1;
+            if cuts == ctx.tuning.multi_cut_required {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+fn store_tt(
+    ctx: &SearchContext,
+    key: u64,
+    depth: u32,
+    ply: u32,
+    score: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+) {
+    ctx.tt.store(
+        key,
+        TtEntry {
+            best_move,
+            score: score_to_tt(score, ply),
+            depth: depth.min(u8::MAX as u32) as u8,
+            bound,
+        },
+    );
+}
+
+/// Make mate scores relative to the stored node rather than the root
+fn score_to_tt(score: i32, ply: u32) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// Inverse of `score_to_tt` for a node at `ply`
+fn score_from_tt(score: i32, ply: u32) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
 }
 
 /// Quiescence search to avoid horizon effect
 fn quiescence_search(
-    position: &Position,
+    position: &mut Position,
+    ply: u32,
     mut alpha: i32,
     beta: i32,
-    stats: &mut SearchStats,
+    ctx: &mut SearchContext,
 ) -> i32 {
-    stats.qnodes_searched += 1;
+    ctx.stats.qnodes_searched += 1;
+    ctx.stats.seldepth = ctx.stats.seldepth.max(ply);
+
+    if ctx.should_stop() {
+        return 0;
+    }
+    let id = ctx.trace_enter(NodeKind::Quiescence, ply, 0, alpha, beta);
 
-    let stand_pat = evaluate::evaluate(position);
+    let stand_pat = ctx
+        .evaluator
+        .evaluate(position, position.key(), alpha, beta, &mut ctx.eval_cache);
 
     if stand_pat >= beta {
-        return beta;
+        return ctx.trace_exit(id, beta, None, CutoffReason::StandPat);
     }
 
     if stand_pat > alpha {
@@ -164,11 +977,7 @@ fn quiescence_search(
     }
 
     // Only consider capture moves in quiescence search
-    let capture_moves: Vec<Move> = position
-        .generate_moves()
-        .into_iter()
-        .filter(|mv| mv.is_capture(&position.board))
-        .collect();
+    let capture_moves = position.generate_captures();
 
     // Sort captures by MVV-LVA (Most Valuable Victim - Least Valuable Attacker)
     let mut scored_captures: Vec<(Move, i32)> = capture_moves
@@ -179,40 +988,237 @@ fn quiescence_search(
         })
         .collect();
 
-    scored_captures.sort_by(|a, b| b.1.cmp(&a.1));
+    scored_captures.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+
+    let mut best_move = None;
 
     for (mv, _) in scored_captures {
-        let mut new_pos = position.clone();
-        if new_pos.make_move(&mv).is_err() {
+        if !make(position, &mv) {
             continue;
         }
+        let score = -quiescence_search(position, ply + 1, -beta, -alpha, ctx);
+        unmake(position);
 
-        let score = -quiescence_search(&new_pos, -beta, -alpha, stats);
+        if ctx.stopped {
+            return 0;
+        }
 
         if score >= beta {
-            return beta;
+            return ctx.trace_exit(id, beta, Some(mv), CutoffReason::BetaCutoff);
         }
 
         if score > alpha {
             alpha = score;
+            best_move = Some(mv);
         }
     }
 
-    alpha
+    ctx.trace_exit(id, alpha, best_move, CutoffReason::None)
 }
 
-/// Evaluate game over positions
-fn evaluate_game_over(position: &Position) -> i32 {
-    if position.is_checkmate() {
-        // Very negative score for checkmate (but not the absolute minimum)
-        -20000 + (position.fullmove_number as i32) // Prefer later checkmates
-    } else if position.is_stalemate() {
-        // Draw
-        0
-    } else {
-        // Other draws (50-move rule, repetition, insufficient material)
-        0
+/// Evaluate game over positions by the position's rules, ply-relative so that
+/// shorter mates score higher for the winning side
+fn evaluate_game_over(position: &Position, ply: u32) -> i32 {
+    position
+        .result()
+        .map_or(0, |result| position.rules().game_over_score(position, result, ply))
+}
+
+/// Prove or disprove a forced mate for the side to move within `moves` moves.
+///
+/// Only the attacker's moves are tried selectively: on the final attacking ply
+/// just checking moves can mate, so quiet moves are pruned there. The result has
+/// no best move when no mate exists within the horizon or the limits of `params`
+/// ran out first.
+pub fn mate_search(position: &Position, moves: u32, params: &SearchParams) -> Result<SearchResult> {
+    let mut observer = NullObserver;
+    let mut ctx = SearchContext::sharing_generation(params, &mut observer);
+    ctx.time = params.time_manager(position.side_to_move);
+
+    Ok(match find_mate(position, moves, params, &mut ctx)? {
+        Some((mv, plies)) => ctx.into_result(Some(mv), MATE_SCORE - plies as i32, plies),
+        None => {
+            let depth = ctx.stats.depth;
+            ctx.into_result(None, 0, depth)
+        }
+    })
+}
+
+/// The mate search proper: the mating move among the allowed root moves and its
+/// distance in plies.
+///
+/// The stop flag and the time and node limits are polled like in `alpha_beta`, an
+/// aborted search finding no mate. The context is left ready for a regular search
+/// to take over on the same clock.
+fn find_mate(
+    position: &Position,
+    moves: u32,
+    params: &SearchParams,
+    ctx: &mut SearchContext,
+) -> Result<Option<(Move, u32)>> {
+    let mut position = position.clone();
+    let mut root = legal_moves(&position);
+    if let Some(allowed) = &params.search_moves {
+        root.retain(|mv| allowed.iter().any(|other| same_move(mv, other)));
+    }
+
+    ctx.can_abort = true;
+    let mut mate = None;
+    'deepen: for n in 1..=moves {
+        let plies = 2 * n - 1;
+        ctx.stats.depth = plies;
+
+        for mv in &root {
+            position.make_move(mv)?;
+            let mates = (plies > 1 || position.in_check())
+                && mate_defend(&mut position, plies - 1, ctx);
+            unmake(&mut position);
+
+            if ctx.stopped {
+                break 'deepen;
+            }
+            if mates {
+                mate = Some((*mv, plies));
+                break 'deepen;
+            }
+        }
+    }
+
+    ctx.can_abort = false;
+    ctx.stopped = false;
+    Ok(mate)
+}
+
+/// Attacker node of the mate search: true if some move forces mate within `depth` plies
+fn mate_attack(position: &mut Position, depth: u32, ctx: &mut SearchContext) -> bool {
+    ctx.stats.nodes_searched += 1;
+    if ctx.should_stop() {
+        return false;
+    }
+
+    for mv in legal_moves(position) {
+        if !make(position, &mv) {
+            continue;
+        }
+
+        // A quiet final move can never deliver mate
+        let mates = (depth > 1 || position.in_check()) && mate_defend(position, depth - 1, ctx);
+        unmake(position);
+
+        if mates {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Defender node of the mate search: true if every reply still loses to mate.
+/// A stopped search proves nothing, so it never claims a mate.
+fn mate_defend(position: &mut Position, depth: u32, ctx: &mut SearchContext) -> bool {
+    ctx.stats.nodes_searched += 1;
+    if ctx.should_stop() {
+        return false;
+    }
+
+    let moves = legal_moves(position);
+    if moves.is_empty() {
+        return position.in_check();
+    }
+    if depth == 0 {
+        return false;
+    }
+
+    moves.into_iter().all(|mv| {
+        if !make(position, &mv) {
+            return false;
+        }
+        let mated = mate_attack(position, depth - 1, ctx);
+        unmake(position);
+        mated
+    })
+}
+
+/// Legal moves of the side to move, as generated by the position's rules
+pub(crate) fn legal_moves(position: &Position) -> MoveList {
+    position.generate_moves()
+}
+
+/// Play a legal `mv`, returning false and leaving the position unchanged if it is
+/// rejected. Moves come from the legal generators, so the mover's king is never
+/// left in check.
+pub(crate) fn make(position: &mut Position, mv: &Move) -> bool {
+    let us = position.side_to_move;
+    if position.make_move(mv).is_err() {
+        return false;
     }
+    debug_assert!(!position.king_attacked(us), "illegal move {mv:?} was generated");
+    true
+}
+
+/// Take back the last move played with `make`
+pub(crate) fn unmake(position: &mut Position) {
+    position.undo_move().expect("a move was made");
+}
+
+/// Follow best moves stored in the transposition table from `position`.
+///
+/// Each stored move is checked against the generated moves, so a key collision
+/// can only cut the line short, never produce an illegal move.
+fn principal_variation(
+    position: &mut Position,
+    tt: &TranspositionTable,
+    max_len: usize,
+) -> Vec<Move> {
+    let mut pv = Vec::new();
+
+    while pv.len() < max_len {
+        let Some(stored) = tt.probe(position.key()).and_then(|entry| entry.best_move) else {
+            break;
+        };
+        let Some(mv) = position
+            .generate_moves()
+            .into_iter()
+            .find(|mv| same_move(mv, &stored))
+        else {
+            break;
+        };
+        if !make(position, &mv) {
+            break;
+        }
+        pv.push(mv);
+    }
+
+    for _ in 0..pv.len() {
+        unmake(position);
+    }
+    pv
+}
+
+/// Sort moves best-first by the move ordering heuristics, hash move first
+fn order_moves(position: &Position, mut moves: MoveList, tt_move: Option<Move>) -> MoveList {
+    let mut scored = [(Move::from_bits(0), 0); MAX_MOVES];
+    for (slot, &mv) in scored.iter_mut().zip(moves.iter()) {
+        let score = if Some(mv) == tt_move {
+            i32::MAX
+        } else {
+            move_score(position, &mv)
+        };
+        *slot = (mv, score);
+    }
+
+    // Sort by score (highest first for maximizing player)
+    let scored = &mut scored[..moves.len()];
+    scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    for (mv, &(sorted, _)) in moves.iter_mut().zip(scored.iter()) {
+        *mv = sorted;
+    }
+    moves
+}
+
+/// Whether two moves describe the same squares and promotion, ignoring flags
+pub(crate) fn same_move(a: &Move, b: &Move) -> bool {
+    a.from() == b.from() && a.to() == b.to() && a.promotion_piece() == b.promotion_piece()
 }
 
 /// Score a move for move ordering
@@ -280,43 +1286,94 @@ pub fn iterative_deepening(
     max_depth: u32,
     time_limit_ms: Option<u64>,
 ) -> Result<SearchResult> {
-    let mut best_result = None;
+    let params = SearchParams {
+        depth: max_depth,
+        time_limit_ms,
+        ..Default::default()
+    };
 
-    for depth in 1..=max_depth {
-        let params = SearchParams {
-            depth,
-            time_limit_ms,
-            nodes_limit: None,
-        };
+    think(position, &params)
+}
 
-        let result = search(position, &params)?;
+/// Depth searched by `bench`
+pub const BENCH_DEPTH: u32 = 5;
 
-        // Update best result
-        best_result = Some(result.clone());
+/// Positions searched by `bench`, covering opening, middlegame and endgame
+const BENCH_POSITIONS: [&str; 8] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r2q1rk1/pp2bppp/2n1pn2/3p4/3P4/2NBPN2/PP3PPP/R2QK2R w KQ - 0 9",
+    "2r3k1/1q3ppp/p3p3/1p1nP3/3P4/P2Q1N2/1P3PPP/2R3K1 b - - 0 24",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+];
 
-        // Check time limit (basic implementation)
-        // In a real engine, you'd check elapsed time here
+/// Node count and speed of a bench run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchResult {
+    /// Total nodes over all positions; the reproducible signature of the search
+    pub nodes: u64,
+    pub elapsed_ms: u64,
+    pub nps: u64,
+}
 
-        // If we found a checkmate, we can stop early
-        if result.score.abs() > 10000 {
-            break;
-        }
+/// Search the bench positions to `BENCH_DEPTH`
+pub fn bench() -> Result<BenchResult> {
+    bench_to_depth(BENCH_DEPTH)
+}
+
+/// Search every bench position to `depth`, each with a fresh transposition table
+pub fn bench_to_depth(depth: u32) -> Result<BenchResult> {
+    let time = TimeManager::unlimited();
+    let mut nodes = 0;
+
+    for fen in BENCH_POSITIONS {
+        let position = Position::from_fen(fen)?;
+        let params = SearchParams {
+            depth,
+            ..Default::default()
+        };
+        let result = think(&position, &params)?;
+        nodes += result.stats.nodes_searched + result.stats.qnodes_searched;
     }
 
-    best_result.ok_or_else(|| Error::InvalidMove("No moves found".to_string()))
+    let elapsed_ms = time.elapsed_ms();
+    Ok(BenchResult {
+        nodes,
+        elapsed_ms,
+        nps: nodes * 1000 / elapsed_ms.max(1),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::board::{Color, PieceType};
+    use std::time::Instant;
+
+    /// Values nothing but a white pawn on h4
+    #[derive(Debug)]
+    struct PawnOnH4;
+
+    impl Evaluator for PawnOnH4 {
+        fn evaluate(&self, position: &Position, _: u64, _: i32, _: i32, _: &mut EvalCache) -> i32 {
+            let pawns = position.board.piece_bitboard(Color::White, PieceType::Pawn);
+            let score = if pawns.has_square(31) { 100 } else { 0 };
+            match position.side_to_move {
+                Color::White => score,
+                Color::Black => -score,
+            }
+        }
+    }
 
     #[test]
     fn test_search_starting_position() {
         let pos = Position::new();
         let params = SearchParams {
             depth: 3,
-            time_limit_ms: None,
-            nodes_limit: None,
+            ..Default::default()
         };
 
         let result = search(&pos, &params).unwrap();
@@ -325,6 +1382,19 @@ mod tests {
         assert!(result.stats.nodes_searched > 0);
     }
 
+    #[test]
+    fn test_search_uses_given_evaluator() {
+        let params = SearchParams {
+            depth: 1,
+            evaluator: Arc::new(PawnOnH4),
+            ..Default::default()
+        };
+
+        let result = search(&Position::new(), &params).unwrap();
+        assert_eq!(result.best_move.unwrap().to_algebraic(), "h2h4");
+        assert_eq!(result.score, 100);
+    }
+
     #[test]
     fn test_checkmate_search() {
         // Fool's mate position - black to move and deliver checkmate
@@ -333,8 +1403,7 @@ mod tests {
 
         let params = SearchParams {
             depth: 1,
-            time_limit_ms: None,
-            nodes_limit: None,
+            ..Default::default()
         };
 
         let result = search(&pos, &params).unwrap();
@@ -349,5 +1418,341 @@ mod tests {
         assert!(result.best_move.is_some());
         assert_eq!(result.depth, 3);
         assert!(result.stats.nodes_searched > 0);
+        assert_eq!(result.stats.iteration_times_ms.len(), 3);
+    }
+
+    #[test]
+    fn test_seldepth_includes_quiescence() {
+        // White can start a capture sequence on e5 that extends past the nominal depth
+        let fen = "4k3/8/3p4/4p3/3P4/2N5/8/4K3 w - - 0 1";
+        let pos = Position::from_fen(fen).unwrap();
+        let params = SearchParams {
+            depth: 1,
+            ..Default::default()
+        };
+
+        let result = search(&pos, &params).unwrap();
+        assert!(result.stats.seldepth > 1);
+    }
+
+    #[test]
+    fn test_mate_search_finds_back_rank_mate() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+
+        let result = mate_search(&pos, 1, &SearchParams::default()).unwrap();
+        assert_eq!(result.best_move.unwrap().to_algebraic(), "a1a8");
+        assert_eq!(mate_in(result.score), Some(1));
+    }
+
+    #[test]
+    fn test_mate_search_mate_in_two() {
+        let fen = "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1";
+        let pos = Position::from_fen(fen).unwrap();
+
+        let result = mate_search(&pos, 2, &SearchParams::default()).unwrap();
+        assert_eq!(result.best_move.unwrap().to_algebraic(), "d5f6");
+        assert_eq!(mate_in(result.score), Some(2));
+    }
+
+    #[test]
+    fn test_mate_search_disproves_mate() {
+        let pos = Position::new();
+        let result = mate_search(&pos, 1, &SearchParams::default()).unwrap();
+        assert!(result.best_move.is_none());
+    }
+
+    #[test]
+    fn test_mate_search_honours_search_moves() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let params = SearchParams {
+            search_moves: Some(vec![Move::from_algebraic("g1f2", PieceType::King).unwrap()]),
+            ..Default::default()
+        };
+
+        let result = mate_search(&pos, 1, &params).unwrap();
+        assert!(result.best_move.is_none());
+    }
+
+    #[test]
+    fn test_stop_flag_aborts_mate_search() {
+        let pos = Position::new();
+        let params = SearchParams {
+            depth: MAX_PLY,
+            mate: Some(6),
+            ..Default::default()
+        };
+        let stop = Arc::clone(&params.stop);
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            stop.store(true, Ordering::Relaxed);
+        });
+
+        // No mate is proven, so the regular search answers once stopped
+        let start = Instant::now();
+        let result = think(&pos, &params).unwrap();
+        stopper.join().unwrap();
+        assert!(result.best_move.is_some());
+        assert!(mate_in(result.score).is_none());
+        assert!(start.elapsed().as_millis() < 1000);
+    }
+
+    #[test]
+    fn test_search_trace() {
+        let pos = Position::new();
+        let params = SearchParams {
+            depth: 2,
+            trace: true,
+            ..Default::default()
+        };
+
+        let result = search(&pos, &params).unwrap();
+        let trace = result.trace.expect("trace requested");
+        let root = &trace.nodes()[0];
+        assert_eq!(root.ply, 0);
+        assert_eq!(root.best_move, result.best_move);
+        assert!(trace.nodes().iter().any(|node| node.kind == NodeKind::Quiescence));
+        assert_eq!(trace.to_text().lines().count(), trace.nodes().len());
+
+        let untraced = search(&pos, &SearchParams { depth: 2, ..Default::default() }).unwrap();
+        assert!(untraced.trace.is_none());
+    }
+
+    #[test]
+    fn test_search_moves_restricts_root() {
+        let pos = Position::new();
+        let allowed = vec![
+            Move::from_algebraic("a2a3", PieceType::Pawn).unwrap(),
+            Move::from_algebraic("h2h3", PieceType::Pawn).unwrap(),
+        ];
+        let params = SearchParams {
+            depth: 2,
+            search_moves: Some(allowed.clone()),
+            ..Default::default()
+        };
+
+        let result = search(&pos, &params).unwrap();
+        let best = result.best_move.unwrap();
+        assert!(allowed.iter().any(|mv| same_move(mv, &best)));
+    }
+
+    #[test]
+    fn test_think_respects_time_limit() {
+        let pos = Position::new();
+        let params = SearchParams {
+            depth: MAX_PLY,
+            time_limit_ms: Some(100),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let result = think(&pos, &params).unwrap();
+        assert!(result.best_move.is_some());
+        assert!(result.depth < MAX_PLY);
+        assert!(start.elapsed().as_millis() < 1000);
+    }
+
+    #[test]
+    fn test_stop_flag_aborts_search() {
+        let pos = Position::new();
+        let params = SearchParams {
+            depth: MAX_PLY,
+            ..Default::default()
+        };
+        params.stop.store(true, Ordering::Relaxed);
+
+        // The first iteration still produces a move before the flag is honoured
+        let result = think(&pos, &params).unwrap();
+        assert!(result.best_move.is_some());
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn test_pondering_ignores_time_limit() {
+        let pos = Position::new();
+        let params = SearchParams {
+            depth: MAX_PLY,
+            time_limit_ms: Some(10),
+            ..Default::default()
+        };
+        params.ponder.store(true, Ordering::Relaxed);
+        let ponder = Arc::clone(&params.ponder);
+        let ponderhit = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            ponder.store(false, Ordering::Relaxed);
+        });
+
+        // The search runs on past its limit until the ponder hit, then ends at once
+        let start = Instant::now();
+        let result = think(&pos, &params).unwrap();
+        ponderhit.join().unwrap();
+        assert!(result.best_move.is_some());
+        assert!(start.elapsed().as_millis() >= 200);
+        assert!(start.elapsed().as_millis() < 2000);
+    }
+
+    #[test]
+    fn test_skill_level_weakens_deterministically() {
+        let pos = Position::new();
+        let params = SearchParams {
+            depth: 3,
+            skill_level: Some(0),
+            ..Default::default()
+        };
+
+        let first = think(&pos, &params).unwrap();
+        let second = think(&pos, &params).unwrap();
+        assert_eq!(first.best_move, second.best_move);
+        assert!(first.stats.nodes_searched <= skill_nodes_limit(0) + 1);
+    }
+
+    #[test]
+    fn test_skill_from_elo() {
+        assert_eq!(skill_from_elo(0), 0);
+        assert_eq!(skill_from_elo(MIN_ELO), 0);
+        assert_eq!(skill_from_elo(2100), 10);
+        assert_eq!(skill_from_elo(MAX_ELO), MAX_SKILL_LEVEL);
+    }
+
+    #[test]
+    fn test_shared_tt_is_filled() {
+        let pos = Position::new();
+        let tt = Arc::new(TranspositionTable::with_entries(1 << 12));
+        let params = SearchParams {
+            depth: 3,
+            tt: Some(Arc::clone(&tt)),
+            ..Default::default()
+        };
+
+        let result = search(&pos, &params).unwrap();
+        let entry = tt.probe(pos.key()).expect("root is stored");
+        assert_eq!(entry.best_move, result.best_move);
+        assert_eq!(entry.bound, Bound::Exact);
+    }
+
+    #[test]
+    fn test_tt_mate_score_adjustment() {
+        let score = MATE_SCORE - 5;
+        assert_eq!(score_from_tt(score_to_tt(score, 3), 3), score);
+        assert_eq!(score_to_tt(-MATE_SCORE + 4, 2), -MATE_SCORE + 2);
+        assert_eq!(score_to_tt(120, 7), 120);
+    }
+
+    #[test]
+    fn test_search_restores_position() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let mut pos = Position::from_fen(fen).unwrap();
+        let params = SearchParams {
+            depth: 3,
+            ..Default::default()
+        };
+        let mut observer = NullObserver;
+        let mut ctx = SearchContext::new(&params, &mut observer);
+
+        search_root(&mut pos, &params, 3, &mut ctx).unwrap();
+        assert_eq!(pos.to_fen(), fen);
+        assert!(pos.history.is_empty());
+    }
+
+    #[test]
+    fn test_root_order_carries_between_iterations() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let mut pos = Position::from_fen(fen).unwrap();
+        let params = SearchParams::default();
+        let mut observer = NullObserver;
+        let mut ctx = SearchContext::new(&params, &mut observer);
+
+        let (first_best, _, _) = search_root(&mut pos, &params, 1, &mut ctx).unwrap();
+        assert_eq!(Some(ctx.root_moves[0].mv), first_best);
+
+        let (second_best, _, _) = search_root(&mut pos, &params, 2, &mut ctx).unwrap();
+        assert_eq!(Some(ctx.root_moves[0].mv), second_best);
+        assert!(ctx.root_moves.iter().all(|root| root.nodes > 0));
+    }
+
+    #[test]
+    fn test_observer_receives_iterations() {
+        #[derive(Default)]
+        struct Recorder {
+            iterations: Vec<IterationInfo>,
+            current_moves: usize,
+        }
+
+        impl SearchObserver for Recorder {
+            fn on_iteration(&mut self, info: &IterationInfo) {
+                self.iterations.push(info.clone());
+            }
+
+            fn on_current_move(&mut self, _mv: Move, number: usize, _depth: u32) {
+                assert!(number >= 1);
+                self.current_moves += 1;
+            }
+        }
+
+        let pos = Position::new();
+        let params = SearchParams {
+            depth: 3,
+            ..Default::default()
+        };
+        let mut recorder = Recorder::default();
+        let result = think_with_observer(&pos, &params, &mut recorder).unwrap();
+
+        let depths: Vec<u32> = recorder.iterations.iter().map(|info| info.depth).collect();
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(recorder.current_moves, 3 * 20);
+
+        let last = recorder.iterations.last().unwrap();
+        assert_eq!(last.best_move, result.best_move);
+        assert_eq!(last.pv.first().copied(), result.best_move);
+        assert!(!last.pv.is_empty() && last.pv.len() <= 3);
+    }
+
+    #[test]
+    fn test_single_legal_move_returns_immediately() {
+        // Kxb2 is the only legal move
+        let pos = Position::from_fen("k7/8/8/8/8/8/1r6/K7 w - - 0 1").unwrap();
+        let params = SearchParams {
+            depth: MAX_PLY,
+            time_limit_ms: Some(60_000),
+            ..Default::default()
+        };
+        let result = think(&pos, &params).unwrap();
+
+        assert_eq!(result.best_move.unwrap().to_algebraic(), "a1b2");
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn test_bench_is_reproducible() {
+        let first = bench_to_depth(1).unwrap();
+        let second = bench_to_depth(1).unwrap();
+
+        assert!(first.nodes > 0);
+        assert_eq!(first.nodes, second.nodes);
+    }
+
+    #[test]
+    fn test_multi_cut_prunes_cut_nodes() {
+        // White is a queen up, so most moves fail high against beta = 0
+        let mut pos = Position::from_fen("4k3/pppp4/8/8/8/8/PPPP4/3QK3 w - - 0 1").unwrap();
+        let params = SearchParams::default();
+        let mut observer = NullObserver;
+        let mut ctx = SearchContext::new(&params, &mut observer);
+
+        let score = alpha_beta(&mut pos, 3, 1, -1, 0, true, &mut ctx);
+        assert_eq!(score, 0);
+        assert_eq!(ctx.stats.multi_cuts, 1);
+
+        // The same node is searched normally when it is not an expected cut node
+        let mut ctx = SearchContext::new(&params, &mut observer);
+        alpha_beta(&mut pos, 3, 1, -1, 0, false, &mut ctx);
+        assert_eq!(ctx.stats.multi_cuts, 0);
+    }
+
+    #[test]
+    fn test_mate_in_conversion() {
+        assert_eq!(mate_in(MATE_SCORE - 1), Some(1));
+        assert_eq!(mate_in(MATE_SCORE - 3), Some(2));
+        assert_eq!(mate_in(-MATE_SCORE + 2), Some(-1));
+        assert_eq!(mate_in(150), None);
     }
 }
\ No newline at end of file
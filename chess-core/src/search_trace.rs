@@ -0,0 +1,226 @@
+//! Opt-in recording of visited search nodes for debugging search behaviour
+
+use crate::moves::Move;
+use std::fmt::Write;
+
+/// Which search routine visited a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Main,
+    Quiescence,
+}
+
+/// Why a node stopped searching its moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutoffReason {
+    /// All moves were searched
+    None,
+    /// A move failed high
+    BetaCutoff,
+    /// The static evaluation already reached beta in quiescence
+    StandPat,
+    /// Checkmate or stalemate
+    Terminal,
+    /// A transposition table entry decided the node
+    TtHit,
+    /// Several moves failed high in reduced multi-cut searches
+    MultiCut,
+}
+
+impl CutoffReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            CutoffReason::None => "none",
+            CutoffReason::BetaCutoff => "beta",
+            CutoffReason::StandPat => "standpat",
+            CutoffReason::Terminal => "terminal",
+            CutoffReason::TtHit => "tt",
+            CutoffReason::MultiCut => "multicut",
+        }
+    }
+}
+
+/// A single visited node, recorded in pre-order
+#[derive(Debug, Clone)]
+pub struct TraceNode {
+    pub kind: NodeKind,
+    pub ply: u32,
+    pub depth: u32,
+    pub alpha: i32,
+    pub beta: i32,
+    pub score: i32,
+    pub best_move: Option<Move>,
+    pub cutoff: CutoffReason,
+}
+
+/// Recorded search tree, capped at a maximum number of nodes
+#[derive(Debug, Clone)]
+pub struct SearchTrace {
+    nodes: Vec<TraceNode>,
+    limit: usize,
+    /// Set once a node is dropped because the trace was full
+    truncated: bool,
+}
+
+impl SearchTrace {
+    /// Default cap on recorded nodes
+    pub const DEFAULT_LIMIT: usize = 100_000;
+
+    pub fn new() -> Self {
+        Self::with_limit(Self::DEFAULT_LIMIT)
+    }
+
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            nodes: Vec::new(),
+            limit,
+            truncated: false,
+        }
+    }
+
+    /// Record entry into a node, returning its id unless the trace is full
+    pub fn enter(
+        &mut self,
+        kind: NodeKind,
+        ply: u32,
+        depth: u32,
+        alpha: i32,
+        beta: i32,
+    ) -> Option<usize> {
+        if self.nodes.len() >= self.limit {
+            self.truncated = true;
+            return None;
+        }
+        self.nodes.push(TraceNode {
+            kind,
+            ply,
+            depth,
+            alpha,
+            beta,
+            score: 0,
+            best_move: None,
+            cutoff: CutoffReason::None,
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Fill in the outcome of a node previously returned by `enter`
+    pub fn exit(
+        &mut self,
+        id: Option<usize>,
+        score: i32,
+        best_move: Option<Move>,
+        cutoff: CutoffReason,
+    ) {
+        if let Some(node) = id.and_then(|id| self.nodes.get_mut(id)) {
+            node.score = score;
+            node.best_move = best_move;
+            node.cutoff = cutoff;
+        }
+    }
+
+    pub fn nodes(&self) -> &[TraceNode] {
+        &self.nodes
+    }
+
+    /// Whether nodes were dropped because the limit was reached
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Dump the trace as a JSON document
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"nodes\":[");
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let kind = match node.kind {
+                NodeKind::Main => "main",
+                NodeKind::Quiescence => "qsearch",
+            };
+            let best_move = match node.best_move {
+                Some(mv) => format!("\"{}\"", mv),
+                None => "null".to_string(),
+            };
+            let _ = write!(
+                out,
+                "{{\"kind\":\"{}\",\"ply\":{},\"depth\":{},\"alpha\":{},\"beta\":{},\"score\":{},\"best_move\":{},\"cutoff\":\"{}\"}}",
+                kind,
+                node.ply,
+                node.depth,
+                node.alpha,
+                node.beta,
+                node.score,
+                best_move,
+                node.cutoff.as_str()
+            );
+        }
+        let _ = write!(out, "],\"truncated\":{}}}", self.is_truncated());
+        out
+    }
+
+    /// Dump the trace as an indented text tree, one node per line
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for node in &self.nodes {
+            let indent = "  ".repeat(node.ply as usize);
+            let kind = match node.kind {
+                NodeKind::Main => 'd',
+                NodeKind::Quiescence => 'q',
+            };
+            let best_move = node
+                .best_move
+                .map_or_else(|| "-".to_string(), |mv| mv.to_string());
+            let _ = writeln!(
+                out,
+                "{}{}{} [{}, {}] -> {} {} ({})",
+                indent,
+                kind,
+                node.depth,
+                node.alpha,
+                node.beta,
+                node.score,
+                best_move,
+                node.cutoff.as_str()
+            );
+        }
+        out
+    }
+}
+
+impl Default for SearchTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_limit() {
+        let mut trace = SearchTrace::with_limit(1);
+        let first = trace.enter(NodeKind::Main, 0, 2, -100, 100);
+        assert_eq!(first, Some(0));
+        // Exactly full, with nothing dropped yet
+        assert!(!trace.is_truncated());
+        assert!(trace.to_json().ends_with("\"truncated\":false}"));
+
+        let second = trace.enter(NodeKind::Main, 1, 1, -100, 100);
+        assert_eq!(second, None);
+        assert!(trace.is_truncated());
+        assert!(trace.to_json().ends_with("\"truncated\":true}"));
+    }
+
+    #[test]
+    fn test_trace_dump() {
+        let mut trace = SearchTrace::new();
+        let id = trace.enter(NodeKind::Quiescence, 1, 0, -50, 50);
+        trace.exit(id, 50, None, CutoffReason::StandPat);
+
+        assert_eq!(trace.to_text(), "  q0 [-50, 50] -> 50 - (standpat)\n");
+        assert!(trace.to_json().contains("\"cutoff\":\"standpat\""));
+    }
+}
@@ -0,0 +1,199 @@
+//! Lazy SMP: helper threads search the same position as the main search and share
+//! its transposition table, so what they find speeds up the main thread
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+};
+
+use crate::{
+    position::Position,
+    search::{self, SearchAlgorithm, SearchObserver, SearchParams, SearchResult, MAX_PLY},
+    tt::TranspositionTable,
+    Result,
+};
+
+/// Most search threads the engine offers
+pub const MAX_THREADS: usize = 256;
+
+/// Stack size of a search thread, matching the main thread's usual 8 MB
+pub const SEARCH_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// A position to search and the parameters to search it with
+type Job = (Position, SearchParams);
+
+#[derive(Debug)]
+struct Worker {
+    jobs: mpsc::Sender<Job>,
+    /// Nodes searched by each finished job
+    nodes: mpsc::Receiver<u64>,
+    handle: thread::JoinHandle<()>,
+}
+
+/// Helper threads kept alive between searches. A pool of `n` helpers searches with
+/// `n + 1` threads; dropping it ends the helpers.
+#[derive(Debug, Default)]
+pub struct WorkerPool {
+    workers: Vec<Worker>,
+}
+
+impl WorkerPool {
+    pub fn new(helpers: usize) -> Self {
+        let workers = (0..helpers)
+            .map(|index| {
+                let (jobs, job_receiver) = mpsc::channel::<Job>();
+                let (node_sender, nodes) = mpsc::channel();
+                let handle = thread::Builder::new()
+                    .name(format!("search-helper-{index}"))
+                    .stack_size(SEARCH_STACK_SIZE)
+                    .spawn(move || {
+                        for (position, params) in job_receiver {
+                            let nodes = search::think_helper(&position, &params)
+                                .map_or(0, |result| {
+                                    result.stats.nodes_searched + result.stats.qnodes_searched
+                                });
+                            if node_sender.send(nodes).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .expect("Failed to spawn a search helper thread");
+                Worker {
+                    jobs,
+                    nodes,
+                    handle,
+                }
+            })
+            .collect();
+        Self { workers }
+    }
+
+    /// Threads a search uses, the calling thread included
+    pub fn threads(&self) -> usize {
+        self.workers.len() + 1
+    }
+
+    /// `search::think_with_observer` on the calling thread while the helpers search
+    /// the same position without limits until it returns. The result is the main
+    /// thread's, with the helpers' nodes added to its node count. MCTS searches run
+    /// on the calling thread alone.
+    pub fn think(
+        &self,
+        position: &Position,
+        params: &SearchParams,
+        observer: &mut dyn SearchObserver,
+    ) -> Result<SearchResult> {
+        if self.workers.is_empty() || params.algorithm != SearchAlgorithm::AlphaBeta {
+            return search::think_with_observer(position, params, observer);
+        }
+
+        let tt = params
+            .tt
+            .clone()
+            .unwrap_or_else(|| Arc::new(TranspositionTable::default()));
+        // One generation for all threads, started before any of them stores
+        tt.new_search();
+        let main = SearchParams {
+            tt: Some(Arc::clone(&tt)),
+            ..params.clone()
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let helper = SearchParams {
+            depth: MAX_PLY,
+            time_limit_ms: None,
+            wtime_ms: None,
+            btime_ms: None,
+            moves_to_go: None,
+            nodes_limit: None,
+            mate: None,
+            trace: false,
+            stop: Arc::clone(&stop),
+            skill_level: None,
+            tt: Some(tt),
+            ..params.clone()
+        };
+        for worker in &self.workers {
+            worker
+                .jobs
+                .send((position.clone(), helper.clone()))
+                .expect("Helper threads live as long as the pool");
+        }
+
+        let result = search::think_sharing_generation(position, &main, observer);
+        stop.store(true, Ordering::Relaxed);
+        let helper_nodes: u64 = self
+            .workers
+            .iter()
+            .map(|worker| worker.nodes.recv().unwrap_or(0))
+            .sum();
+
+        let mut result = result?;
+        result.stats.nodes_searched += helper_nodes;
+        Ok(result)
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        for worker in self.workers.drain(..) {
+            // Closing the job channel ends the helper's loop
+            drop(worker.jobs);
+            let _ = worker.handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::NullObserver;
+
+    #[test]
+    fn test_parallel_search() {
+        let position = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let params = SearchParams {
+            depth: 3,
+            tt: Some(Arc::new(TranspositionTable::new(1))),
+            ..Default::default()
+        };
+        let single = search::think(&position, &params).unwrap();
+
+        let pool = WorkerPool::new(3);
+        assert_eq!(pool.threads(), 4);
+        let generation = params.tt.as_ref().unwrap().generation();
+        let parallel = pool.think(&position, &params, &mut NullObserver).unwrap();
+        // All threads share the one generation the pool started
+        assert_eq!(params.tt.as_ref().unwrap().generation(), generation + 1);
+        assert_eq!(parallel.best_move.unwrap().to_algebraic(), "d1d8");
+        assert_eq!(parallel.best_move, single.best_move);
+
+        // The pool is reused by the next search
+        let again = pool
+            .think(&Position::new(), &params, &mut NullObserver)
+            .unwrap();
+        assert!(again.best_move.is_some());
+    }
+
+    #[test]
+    fn test_empty_pool_searches_alone() {
+        let pool = WorkerPool::default();
+        assert_eq!(pool.threads(), 1);
+        let params = SearchParams {
+            depth: 2,
+            ..Default::default()
+        };
+        let result = pool
+            .think(&Position::new(), &params, &mut NullObserver)
+            .unwrap();
+        assert_eq!(
+            result.stats.nodes_searched,
+            search::think(&Position::new(), &params)
+                .unwrap()
+                .stats
+                .nodes_searched
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Space advantage: safe central squares claimed by the pawn chain
+
+use crate::{
+    bitboard::{
+        Bitboard, FILE_C, FILE_D, FILE_E, FILE_F, RANK_2, RANK_3, RANK_4, RANK_5, RANK_6, RANK_7,
+    },
+    board::{Board, Color, PieceType},
+    params::EvalParams,
+    pawns,
+};
+
+/// Divisor of `area * pieces^2`, so space matters more with more pieces to use it
+pub(crate) const SPACE_SCALE: i32 = 32;
+
+const CENTRE_FILES: Bitboard = Bitboard(FILE_C.0 | FILE_D.0 | FILE_E.0 | FILE_F.0);
+
+/// Central squares on the second to fourth ranks as seen by `color`, where pieces
+/// can manoeuvre behind the pawns
+fn space_mask(color: Color) -> Bitboard {
+    match color {
+        Color::White => CENTRE_FILES & (RANK_2 | RANK_3 | RANK_4),
+        Color::Black => CENTRE_FILES & (RANK_7 | RANK_6 | RANK_5),
+    }
+}
+
+/// Up to three squares behind each of `pawns`
+fn behind(pawns: Bitboard, color: Color) -> Bitboard {
+    let p = pawns.0;
+    match color {
+        Color::White => Bitboard((p >> 8) | (p >> 16) | (p >> 24)),
+        Color::Black => Bitboard((p << 8) | (p << 16) | (p << 24)),
+    }
+}
+
+/// Safe squares in the space area, with those behind friendly pawns counting twice
+pub fn space_area(board: &Board, color: Color) -> i32 {
+    let ours = board.piece_bitboard(color, PieceType::Pawn);
+    let enemy_attacks = pawns::pawn_attacks(
+        board.piece_bitboard(color.opposite(), PieceType::Pawn),
+        color.opposite(),
+    );
+
+    let safe = space_mask(color) & !ours & !enemy_attacks;
+    (safe.count() + (safe & behind(ours, color)).count()) as i32
+}
+
+/// Space score of one side
+pub(crate) fn side_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let pieces = (board.color_bitboard(color).count()
+        - board.piece_bitboard(color, PieceType::Pawn).count()
+        - 1) as i32;
+    space_area(board, color) * pieces * pieces / params.space_scale
+}
+
+/// Space score from White's point of view
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    side_score(board, Color::White, params) - side_score(board, Color::Black, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_advanced_chain_gains_space() {
+        // White's d4/e5 chain claims the squares behind it
+        let pos =
+            Position::from_fen("r1bqkbnr/pp3ppp/2n1p3/2ppP3/3P4/5N2/PPP2PPP/RNBQKB1R w KQkq - 0 1")
+                .unwrap();
+        assert!(space_area(&pos.board, Color::White) > space_area(&pos.board, Color::Black));
+        assert!(evaluate(&pos.board, &EvalParams::default()) > 0);
+    }
+
+    #[test]
+    fn test_space_needs_pieces() {
+        let pos = Position::from_fen("4k3/pp3ppp/4p3/3pP3/3P4/8/PPP2PPP/4K3 w - - 0 1").unwrap();
+        assert!(space_area(&pos.board, Color::White) > 0);
+        assert_eq!(
+            side_score(&pos.board, Color::White, &EvalParams::default()),
+            0
+        );
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        assert_eq!(evaluate(&Position::new().board, &EvalParams::default()), 0);
+    }
+}
@@ -0,0 +1,170 @@
+//! Attack maps and penalties for pieces left en prise
+
+use crate::{
+    bitboard::Bitboard,
+    board::{Board, Color, PieceType, Square},
+    evaluate, magic_simple as magic, movegen,
+    params::EvalParams,
+    pawns,
+};
+
+/// Penalty for a piece that is attacked and not defended, pawn to queen
+pub(crate) const HANGING_PENALTY: [i32; 5] = [10, 30, 30, 50, 90];
+
+/// Penalty for a defended piece that has more attackers than defenders, pawn to queen
+pub(crate) const LOOSE_PENALTY: [i32; 5] = [5, 15, 15, 25, 45];
+
+/// Squares attacked by a piece of `piece_type` on `square` with the current occupancy
+pub fn piece_attacks(board: &Board, piece_type: PieceType, square: u8, color: Color) -> Bitboard {
+    match piece_type {
+        PieceType::Pawn => pawns::pawn_attacks(Bitboard::from_square(square), color),
+        PieceType::Knight => movegen::knight_attacks(Square(square)),
+        PieceType::Bishop => magic::get_bishop_attacks(square, board.occupied),
+        PieceType::Rook => magic::get_rook_attacks(square, board.occupied),
+        PieceType::Queen => magic::get_queen_attacks(square, board.occupied),
+        PieceType::King => movegen::king_attacks(Square(square)),
+    }
+}
+
+/// Squares attacked by any piece of `color`
+pub fn attacked_squares(board: &Board, color: Color) -> Bitboard {
+    board.attacks_by(color, board.occupied)
+}
+
+/// Number of pieces of `color` attacking each square
+pub fn attack_counts(board: &Board, color: Color) -> [u8; 64] {
+    let mut counts = [0; 64];
+    for piece_type in [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+        PieceType::King,
+    ] {
+        for square in board.piece_bitboard(color, piece_type).squares() {
+            for target in piece_attacks(board, piece_type, square, color).squares() {
+                counts[target as usize] += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Penalty, as a non-positive score, for the pieces of `color` that hang or are loose
+pub(crate) fn side_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let defenders = attack_counts(board, color);
+    let attackers = attack_counts(board, color.opposite());
+
+    let mut score = 0;
+    for piece_type in [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+    ] {
+        for square in board.piece_bitboard(color, piece_type).squares() {
+            let attacked = attackers[square as usize];
+            let defended = defenders[square as usize];
+            if attacked == 0 {
+                continue;
+            }
+            if defended == 0 {
+                score -= params.hanging_penalty[piece_type as usize];
+            } else if defended < attacked {
+                score -= params.loose_penalty[piece_type as usize];
+            }
+        }
+    }
+    score
+}
+
+/// Material of `color` en prise: pieces attacked with no defender, or attacked by a
+/// cheaper piece. The value counted for a piece is what the cheapest capture wins.
+pub fn hanging_material(board: &Board, color: Color) -> i32 {
+    let defenders = attacked_squares(board, color);
+    let enemy = color.opposite();
+    let attacks_by_type = PieceType::ALL.map(|piece_type| {
+        board
+            .piece_bitboard(enemy, piece_type)
+            .squares()
+            .fold(Bitboard::empty(), |attacks, square| {
+                attacks | piece_attacks(board, piece_type, square, enemy)
+            })
+    });
+
+    let mut material = 0;
+    for (square, piece) in board.pieces_of_color(color) {
+        if piece.piece_type == PieceType::King {
+            continue;
+        }
+        let Some(attacker) = PieceType::ALL
+            .into_iter()
+            .find(|&attacker| attacks_by_type[attacker as usize].has_square(square.0))
+        else {
+            continue;
+        };
+        let value = evaluate::PIECE_VALUES[piece.piece_type as usize];
+        if !defenders.has_square(square.0) {
+            material += value;
+        } else if (attacker as usize) < piece.piece_type as usize {
+            material += value - evaluate::PIECE_VALUES[attacker as usize];
+        }
+    }
+    material
+}
+
+/// Hanging and loose piece score from White's point of view
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    side_score(board, Color::White, params) - side_score(board, Color::Black, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_attack_counts() {
+        // d5 is hit by the e4 pawn, the c3 knight and the d1 rook
+        let pos = Position::from_fen("4k3/8/8/8/4P3/2N5/8/3RK3 w - - 0 1").unwrap();
+        let counts = attack_counts(&pos.board, Color::White);
+        let d5 = Square::from_algebraic("d5").unwrap().index() as usize;
+        assert_eq!(counts[d5], 3);
+    }
+
+    #[test]
+    fn test_hanging_and_loose_pieces() {
+        // The knight on d5 is attacked by the e6 pawn with no defender
+        let hanging = Position::from_fen("4k3/8/4p3/3N4/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            side_score(&hanging.board, Color::White, &EvalParams::default()),
+            -HANGING_PENALTY[PieceType::Knight as usize]
+        );
+
+        // Defended once by the c4 pawn but attacked by the e6 pawn and f6 knight
+        let loose = Position::from_fen("4k3/8/4pn2/3N4/2P5/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            side_score(&loose.board, Color::White, &EvalParams::default()),
+            -LOOSE_PENALTY[PieceType::Knight as usize]
+        );
+    }
+
+    #[test]
+    fn test_hanging_material() {
+        // The undefended d5 knight, and the b4 queen which it defends but the a5 pawn
+        // attacks, losing the queen for a pawn
+        let fen = "4k3/8/4p3/p2N4/1Q6/8/8/4K3 w - - 0 1";
+        let pos = Position::from_fen(fen).unwrap();
+        assert_eq!(hanging_material(&pos.board, Color::White), 300 + 800);
+        // The a5 pawn is defended by nothing but attacked by the queen
+        assert_eq!(hanging_material(&pos.board, Color::Black), 100);
+        assert_eq!(hanging_material(&Position::new().board, Color::White), 0);
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        assert_eq!(evaluate(&Position::new().board, &EvalParams::default()), 0);
+    }
+}
@@ -0,0 +1,184 @@
+//! Time management for iterative deepening
+
+use std::time::{Duration, Instant};
+
+/// Fraction of the hard limit after which no new iteration is started
+const SOFT_LIMIT_PERCENT: u64 = 60;
+
+/// Branching factor assumed until two iterations have been timed
+const DEFAULT_BRANCHING_FACTOR: u64 = 4;
+
+/// Moves the remaining clock time is spread over when the GUI sends no `movestogo`
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+/// Time kept back on every move for communication and process scheduling
+const MOVE_OVERHEAD_MS: u64 = 30;
+
+/// Most of the remaining clock time a single move may use
+const MAX_CLOCK_PERCENT: u64 = 50;
+
+/// Tracks elapsed time against a soft and a hard limit.
+///
+/// The soft limit decides whether another iteration should be started, while the
+/// hard limit aborts the search mid-iteration.
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    start: Instant,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+}
+
+impl TimeManager {
+    /// A time manager without any limits
+    pub fn unlimited() -> Self {
+        Self {
+            start: Instant::now(),
+            soft_limit: None,
+            hard_limit: None,
+        }
+    }
+
+    /// Derive soft and hard limits from a fixed time budget
+    pub fn new(time_limit_ms: Option<u64>) -> Self {
+        match time_limit_ms {
+            Some(ms) => Self::with_limits(ms * SOFT_LIMIT_PERCENT / 100, ms),
+            None => Self::unlimited(),
+        }
+    }
+
+    /// Budget a move from the time left on the clock, the increment after each move
+    /// and the moves left until the next time control. The soft limit is an even
+    /// share of the clock plus most of the increment; the hard limit allows three
+    /// times that, but never more than half of what is left.
+    pub fn for_clock(remaining_ms: u64, increment_ms: u64, moves_to_go: Option<u32>) -> Self {
+        let available = remaining_ms.saturating_sub(MOVE_OVERHEAD_MS);
+        let moves = moves_to_go.map_or(DEFAULT_MOVES_TO_GO, |moves| u64::from(moves.max(1)));
+        let cap = if moves == 1 {
+            available
+        } else {
+            available * MAX_CLOCK_PERCENT / 100
+        };
+        let target = (available / moves + increment_ms * 3 / 4).min(cap);
+        Self::with_limits(target, (target * 3).min(cap))
+    }
+
+    pub fn with_limits(soft_ms: u64, hard_ms: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            soft_limit: Some(Duration::from_millis(soft_ms.min(hard_ms))),
+            hard_limit: Some(Duration::from_millis(hard_ms)),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        self.elapsed().as_millis() as u64
+    }
+
+    pub fn soft_limit_ms(&self) -> Option<u64> {
+        self.soft_limit.map(|limit| limit.as_millis() as u64)
+    }
+
+    pub fn hard_limit_ms(&self) -> Option<u64> {
+        self.hard_limit.map(|limit| limit.as_millis() as u64)
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.hard_limit.is_some()
+    }
+
+    /// Whether the search must be aborted immediately
+    pub fn hard_limit_reached(&self) -> bool {
+        self.hard_limit.is_some_and(|limit| self.elapsed() >= limit)
+    }
+
+    /// Whether no further iteration should be started
+    pub fn soft_limit_reached(&self) -> bool {
+        self.soft_limit.is_some_and(|limit| self.elapsed() >= limit)
+    }
+
+    /// Whether `percent` of the soft limit has elapsed; never true without limits
+    pub fn soft_limit_percent_reached(&self, percent: u64) -> bool {
+        self.soft_limit_ms()
+            .is_some_and(|limit| self.elapsed_ms() * 100 >= limit * percent)
+    }
+
+    /// Estimate whether the next iteration can finish before the hard limit.
+    ///
+    /// The next iteration is assumed to take as much longer than the last one
+    /// as the last one took compared to its predecessor.
+    pub fn can_finish_next_iteration(&self, last_ms: u64, previous_ms: Option<u64>) -> bool {
+        let Some(hard_ms) = self.hard_limit_ms() else {
+            return true;
+        };
+
+        let branching_factor = match previous_ms {
+            Some(previous) if previous > 0 => (last_ms / previous).clamp(2, 8),
+            _ => DEFAULT_BRANCHING_FACTOR,
+        };
+
+        self.elapsed_ms() + last_ms * branching_factor <= hard_ms
+    }
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_from_budget() {
+        let time = TimeManager::new(Some(1000));
+        assert_eq!(time.soft_limit_ms(), Some(600));
+        assert_eq!(time.hard_limit_ms(), Some(1000));
+        assert!(!time.hard_limit_reached());
+
+        let time = TimeManager::new(None);
+        assert!(!time.is_limited());
+        assert!(time.can_finish_next_iteration(u64::MAX / 16, None));
+    }
+
+    #[test]
+    fn test_limits_from_clock() {
+        let time = TimeManager::for_clock(60_030, 0, None);
+        assert_eq!(time.soft_limit_ms(), Some(2_000));
+        assert_eq!(time.hard_limit_ms(), Some(6_000));
+
+        let time = TimeManager::for_clock(10_030, 1_000, Some(10));
+        assert_eq!(time.soft_limit_ms(), Some(1_750));
+        assert_eq!(time.hard_limit_ms(), Some(5_000));
+
+        // The last move before the time control may use the whole clock
+        let time = TimeManager::for_clock(1_030, 0, Some(1));
+        assert_eq!(time.hard_limit_ms(), Some(1_000));
+
+        let time = TimeManager::for_clock(10, 0, None);
+        assert_eq!(time.hard_limit_ms(), Some(0));
+    }
+
+    #[test]
+    fn test_expired_limits() {
+        let time = TimeManager::with_limits(0, 0);
+        assert!(time.soft_limit_reached());
+        assert!(time.hard_limit_reached());
+        assert!(time.soft_limit_percent_reached(50));
+        assert!(!TimeManager::unlimited().soft_limit_percent_reached(0));
+        assert!(!TimeManager::with_limits(60_000, 100_000).soft_limit_percent_reached(50));
+    }
+
+    #[test]
+    fn test_next_iteration_estimate() {
+        let time = TimeManager::with_limits(5_000, 10_000);
+        assert!(time.can_finish_next_iteration(100, Some(25)));
+        assert!(!time.can_finish_next_iteration(4_000, Some(1_000)));
+        assert!(!time.can_finish_next_iteration(3_000, None));
+    }
+}
@@ -0,0 +1,383 @@
+//! Transposition table that can be shared between search threads without locking.
+//!
+//! Every slot holds two atomic words: the packed entry data and the position key
+//! XORed with that data. Concurrent writers may interleave their two stores, but a
+//! torn slot then fails the XOR check on probe and simply reads as a miss.
+//!
+//! Slots are grouped into buckets. Each entry records the generation of the search
+//! that stored it, so entries left over from earlier searches in the same game stay
+//! available but are the first to be replaced.
+
+use crate::moves::Move;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// Kind of score stored in an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The score is a lower bound (the node failed high)
+    Lower,
+    /// The score is an upper bound (no move raised alpha)
+    Upper,
+}
+
+/// Decoded transposition table entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtEntry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+const MOVE_BITS: u64 = 0xFF_FFFF;
+const DEPTH_SHIFT: u32 = 24;
+const BOUND_SHIFT: u32 = 32;
+const GENERATION_SHIFT: u32 = 34;
+const SCORE_SHIFT: u32 = 40;
+
+/// Generations wrap around after this many searches
+const GENERATION_CYCLE: u8 = 64;
+
+/// Slots sharing one index; a bucket fills a 64-byte cache line
+const BUCKET_SIZE: usize = 4;
+
+/// Weight of one generation of age against one ply of depth when replacing
+const AGE_WEIGHT: i32 = 8;
+
+fn generation_of(data: u64) -> u8 {
+    ((data >> GENERATION_SHIFT) & 0x3F) as u8
+}
+
+impl TtEntry {
+    /// Pack into a single word: move (24 bits), depth (8), bound (2), generation (6),
+    /// score (24, signed)
+    fn pack(self, generation: u8) -> u64 {
+        let mv = self.best_move.map_or(0, |mv| mv.to_bits() as u64 + 1) & MOVE_BITS;
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let score = (self.score as u64) & 0xFF_FFFF;
+        mv | (self.depth as u64) << DEPTH_SHIFT
+            | bound << BOUND_SHIFT
+            | (generation as u64) << GENERATION_SHIFT
+            | score << SCORE_SHIFT
+    }
+
+    fn unpack(data: u64) -> Self {
+        let mv = data & MOVE_BITS;
+        let bound = match (data >> BOUND_SHIFT) & 0x3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        // Sign-extend the 24-bit score
+        let score = ((data >> SCORE_SHIFT) as i64) << 40 >> 40;
+        Self {
+            best_move: (mv != 0).then(|| Move::from_bits(mv as u32 - 1)),
+            score: score as i32,
+            depth: (data >> DEPTH_SHIFT) as u8,
+            bound,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    /// Position key XOR data
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    /// Packed data if the slot holds a consistent entry for `key`
+    fn load(&self, key: u64) -> Option<u64> {
+        let data = self.data.load(Ordering::Relaxed);
+        let check = self.check.load(Ordering::Relaxed);
+        (data != 0 && check ^ data == key).then_some(data)
+    }
+
+    fn write(&self, key: u64, data: u64) {
+        self.data.store(data, Ordering::Relaxed);
+        self.check.store(key ^ data, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct Bucket {
+    slots: [Slot; BUCKET_SIZE],
+}
+
+/// Fixed-size, always-shared transposition table
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    mask: usize,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// Default table size in megabytes
+    pub const DEFAULT_SIZE_MB: usize = 16;
+
+    /// Largest table size in megabytes the engine offers
+    pub const MAX_SIZE_MB: usize = 65536;
+
+    /// Allocate a table of roughly `size_mb` megabytes
+    pub fn new(size_mb: usize) -> Self {
+        let entries = size_mb.max(1) * 1024 * 1024 / std::mem::size_of::<Slot>();
+        Self::with_entries(entries)
+    }
+
+    /// Allocate a table with about `entries` slots: a power of two of buckets, rounded
+    /// down, and at least one
+    pub fn with_entries(entries: usize) -> Self {
+        let buckets = (entries / BUCKET_SIZE).max(1);
+        let buckets = if buckets.is_power_of_two() {
+            buckets
+        } else {
+            buckets.next_power_of_two() / 2
+        };
+        Self {
+            buckets: (0..buckets).map(|_| Bucket::default()).collect(),
+            mask: buckets - 1,
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// Number of slots in the table
+    pub fn len(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        &self.buckets[key as usize & self.mask]
+    }
+
+    /// Generation stamped on entries stored by the current search
+    pub fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Start a new search, ageing every entry stored so far
+    pub fn new_search(&self) {
+        let next = (self.generation() + 1) % GENERATION_CYCLE;
+        self.generation.store(next, Ordering::Relaxed);
+    }
+
+    /// Searches since the entry in `data` was stored
+    fn age(&self, data: u64) -> u8 {
+        (self.generation() + GENERATION_CYCLE - generation_of(data)) % GENERATION_CYCLE
+    }
+
+    /// Look up a position, returning its entry if a consistent one is stored
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        self.bucket(key)
+            .slots
+            .iter()
+            .find_map(|slot| slot.load(key))
+            .map(TtEntry::unpack)
+    }
+
+    /// Store an entry.
+    ///
+    /// An existing entry for the same position is kept only when it is deeper, was
+    /// stored by the current search and the new entry is not exact. Otherwise the
+    /// slot to overwrite is an empty one or the one with the least depth, where
+    /// every search of age counts as `AGE_WEIGHT` plies lost.
+    pub fn store(&self, key: u64, entry: TtEntry) {
+        let bucket = self.bucket(key);
+        let data = entry.pack(self.generation());
+
+        let same = bucket
+            .slots
+            .iter()
+            .find_map(|slot| slot.load(key).map(|existing| (slot, existing)));
+        if let Some((slot, existing)) = same {
+            let keep = self.age(existing) == 0
+                && TtEntry::unpack(existing).depth > entry.depth
+                && entry.bound != Bound::Exact;
+            if !keep {
+                slot.write(key, data);
+            }
+            return;
+        }
+
+        let worth = |slot: &Slot| {
+            let existing = slot.data.load(Ordering::Relaxed);
+            if existing == 0 {
+                return i32::MIN;
+            }
+            TtEntry::unpack(existing).depth as i32 - AGE_WEIGHT * self.age(existing) as i32
+        };
+        let victim = bucket
+            .slots
+            .iter()
+            .min_by_key(|slot| worth(slot))
+            .expect("buckets are never empty");
+        victim.write(key, data);
+    }
+
+    /// Permille of sampled slots holding an entry from the current search
+    pub fn hashfull(&self) -> u32 {
+        let sample = self.buckets.iter().take(1000 / BUCKET_SIZE);
+        let mut total = 0;
+        let mut used = 0;
+        for bucket in sample {
+            for slot in &bucket.slots {
+                let data = slot.data.load(Ordering::Relaxed);
+                total += 1;
+                if data != 0 && self.age(data) == 0 {
+                    used += 1;
+                }
+            }
+        }
+        used * 1000 / total.max(1)
+    }
+
+    /// Forget every stored entry
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flat_map(|bucket| &bucket.slots) {
+            slot.data.store(0, Ordering::Relaxed);
+            slot.check.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SIZE_MB)
+    }
+}
+
+impl std::fmt::Debug for TranspositionTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TranspositionTable")
+            .field("entries", &self.len())
+            .field("generation", &self.generation())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{PieceType, Square};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_pack_roundtrip() {
+        let entry = TtEntry {
+            best_move: Some(Move::new_promotion(
+                Square::from_algebraic("e7").unwrap(),
+                Square::from_algebraic("e8").unwrap(),
+                PieceType::Pawn,
+                PieceType::Queen,
+            )),
+            score: -19_990,
+            depth: 42,
+            bound: Bound::Upper,
+        };
+
+        assert_eq!(TtEntry::unpack(entry.pack(37)), entry);
+        assert_eq!(generation_of(entry.pack(37)), 37);
+    }
+
+    #[test]
+    fn test_store_and_probe() {
+        let tt = TranspositionTable::with_entries(1024);
+        let entry = TtEntry {
+            best_move: None,
+            score: 35,
+            depth: 3,
+            bound: Bound::Exact,
+        };
+
+        tt.store(12345, entry);
+        assert_eq!(tt.probe(12345), Some(entry));
+        assert_eq!(tt.probe(12345 + 1024), None);
+
+        tt.clear();
+        assert_eq!(tt.probe(12345), None);
+    }
+
+    #[test]
+    fn test_replacement_prefers_current_generation() {
+        // A single bucket so every key competes for the same slots
+        let tt = TranspositionTable::with_entries(BUCKET_SIZE);
+        let entry = |depth| TtEntry {
+            best_move: None,
+            score: 0,
+            depth,
+            bound: Bound::Lower,
+        };
+
+        // Deep entries from an old search lose to shallow current ones
+        for key in 1..=BUCKET_SIZE as u64 {
+            tt.store(key, entry(20));
+        }
+        tt.new_search();
+        tt.new_search();
+        tt.store(100, entry(2));
+        assert_eq!(tt.probe(100), Some(entry(2)));
+        assert_eq!(tt.hashfull(), 250);
+
+        // Within a generation the shallowest entry is replaced
+        tt.store(101, entry(30));
+        tt.store(102, entry(30));
+        tt.store(103, entry(30));
+        tt.store(104, entry(30));
+        assert_eq!(tt.probe(100), None);
+        assert!((101..=104).all(|key| tt.probe(key).is_some()));
+
+        // A deeper current entry for the same position is kept
+        tt.store(101, entry(3));
+        assert_eq!(tt.probe(101), Some(entry(30)));
+    }
+
+    #[test]
+    fn test_concurrent_writes_never_tear() {
+        // A single bucket makes every thread race on the same four slots, replacing
+        // entries in it all the time
+        let tt = Arc::new(TranspositionTable::with_entries(1));
+        assert_eq!(tt.len(), BUCKET_SIZE);
+
+        // Each key's entry is derived from the key, so any mismatch is a torn read
+        let entry_for = |key: u64| TtEntry {
+            best_move: None,
+            score: (key % 1000) as i32,
+            depth: (key % 200) as u8,
+            bound: Bound::Exact,
+        };
+
+        let handles: Vec<_> = (0..4u64)
+            .map(|thread| {
+                let tt = Arc::clone(&tt);
+                std::thread::spawn(move || {
+                    let mut mismatches = 0;
+                    for i in 0..20_000u64 {
+                        let key = (thread * 1_000_003 + i * 7919) | 1;
+                        tt.store(key, entry_for(key));
+
+                        let probe_key = ((thread + 1) % 4 * 1_000_003 + i * 7919) | 1;
+                        if let Some(found) = tt.probe(probe_key) {
+                            if found != entry_for(probe_key) {
+                                mismatches += 1;
+                            }
+                        }
+                    }
+                    mismatches
+                })
+            })
+            .collect();
+
+        let mismatches: u32 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(mismatches, 0);
+    }
+}
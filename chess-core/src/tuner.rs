@@ -0,0 +1,422 @@
+//! Texel tuning of the classical evaluation constants.
+//!
+//! Every classical term is linear in its constant, so a position reduces to a
+//! sparse vector of feature coefficients whose dot product with the weights is the
+//! White-relative evaluation. The weights are fitted to game results by gradient
+//! descent on the squared error of a logistic win probability.
+
+use crate::{
+    board::{Color, PieceType},
+    coordination, endgame, evaluate, imbalance, initiative, king_activity, king_safety, majority,
+    params::EvalParams,
+    pawns, pgn, pieces,
+    position::Position,
+    space, threats, weak_squares, Error, Result,
+};
+
+const MATERIAL: usize = 0;
+const PST: usize = MATERIAL + 5;
+const DOUBLED: usize = PST + 6 * 64;
+const ISOLATED: usize = DOUBLED + 1;
+const BACKWARD: usize = ISOLATED + 1;
+const BISHOP_PAIR: usize = BACKWARD + 1;
+const KNIGHT_CLOSED: usize = BISHOP_PAIR + 1;
+const BISHOP_CLOSED: usize = KNIGHT_CLOSED + 1;
+const ROOK_OPEN: usize = BISHOP_CLOSED + 1;
+const ROOK_SEMI_OPEN: usize = ROOK_OPEN + 1;
+const ROOK_SEVENTH: usize = ROOK_SEMI_OPEN + 1;
+const ROOK_BEHIND_PASSER: usize = ROOK_SEVENTH + 1;
+const BAD_BISHOP: usize = ROOK_BEHIND_PASSER + 1;
+const BAD_BISHOP_CENTRE: usize = BAD_BISHOP + 1;
+
+/// Number of tuned weights
+pub const NUM_PARAMS: usize = BAD_BISHOP_CENTRE + 1;
+
+const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+/// Adam moment decay rates
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+
+/// A labelled training position
+#[derive(Debug, Clone)]
+pub struct TuningPosition {
+    /// Non-zero feature coefficients as `(weight index, coefficient)`
+    features: Vec<(usize, f64)>,
+    /// Contribution of the terms that are not linear in a weight and stay fixed
+    fixed: f64,
+    /// Game result from White's point of view: 1, 0.5 or 0
+    result: f64,
+}
+
+impl TuningPosition {
+    pub fn new(position: &Position, result: f64) -> Self {
+        Self {
+            features: features(position),
+            fixed: fixed_terms(position) as f64,
+            result,
+        }
+    }
+
+    /// White-relative evaluation under `weights`
+    pub fn evaluate(&self, weights: &[f64]) -> f64 {
+        self.fixed
+            + self
+                .features
+                .iter()
+                .map(|&(index, coefficient)| weights[index] * coefficient)
+                .sum::<f64>()
+    }
+}
+
+/// White-relative sum of the terms the tuner leaves alone
+fn fixed_terms(position: &Position) -> i32 {
+    let board = &position.board;
+    let params = position.params();
+    let phase = evaluate::game_phase(board);
+    let side = |color| {
+        pieces::tropism(board, color, params).taper(phase)
+            + pieces::trapped(board, color, params)
+            + pawns::passer_score(board, color, params)
+            + imbalance::quadratic(board, color, params)
+    };
+    space::evaluate(board, params)
+        + majority::evaluate(board, params)
+        + coordination::evaluate(board, params)
+        + threats::evaluate(board, params)
+        + weak_squares::evaluate(board, params)
+        + king_safety::evaluate(board, params)
+        + initiative::evaluate(board, params)
+        + king_activity::evaluate(board, position.side_to_move, params)
+        + endgame::evaluate_mop_up(board)
+        + side(Color::White)
+        - side(Color::Black)
+}
+
+/// Feature coefficients of the classical evaluation, from White's point of view
+fn features(position: &Position) -> Vec<(usize, f64)> {
+    let board = &position.board;
+    let mut dense = vec![0.0; NUM_PARAMS];
+
+    for (index, &piece_type) in PIECE_TYPES.iter().enumerate() {
+        let white = board.piece_bitboard(Color::White, piece_type);
+        let black = board.piece_bitboard(Color::Black, piece_type);
+
+        // Both kings are always present, so their material cancels out
+        if piece_type != PieceType::King {
+            dense[MATERIAL + index] = white.count() as f64 - black.count() as f64;
+        }
+        for square in white.squares() {
+            dense[PST + index * 64 + square as usize] += 1.0;
+        }
+        for square in black.squares() {
+            dense[PST + index * 64 + 63 - square as usize] -= 1.0;
+        }
+    }
+
+    let count_diff =
+        |count: &dyn Fn(Color) -> u32| count(Color::White) as f64 - count(Color::Black) as f64;
+
+    // Pawn penalties are subtracted, so their coefficients are negated
+    dense[DOUBLED] = -count_diff(&|c| pawns::doubled_pawns(board, c).count());
+    dense[ISOLATED] = -count_diff(&|c| pawns::isolated_pawns(board, c).count());
+    dense[BACKWARD] = -count_diff(&|c| pawns::backward_pawns(board, c).count());
+
+    let closed = (imbalance::closedness(board) - imbalance::NEUTRAL_CLOSEDNESS) as f64;
+    dense[BISHOP_PAIR] = count_diff(&|c| imbalance::has_bishop_pair(board, c) as u32);
+    dense[KNIGHT_CLOSED] =
+        count_diff(&|c| board.piece_bitboard(c, PieceType::Knight).count()) * closed;
+    dense[BISHOP_CLOSED] =
+        -count_diff(&|c| board.piece_bitboard(c, PieceType::Bishop).count()) * closed;
+
+    let white = pieces::rook_terms(board, Color::White);
+    let black = pieces::rook_terms(board, Color::Black);
+    dense[ROOK_OPEN] = (white.open_file - black.open_file) as f64;
+    dense[ROOK_SEMI_OPEN] = (white.semi_open_file - black.semi_open_file) as f64;
+    dense[ROOK_SEVENTH] = (white.seventh_rank - black.seventh_rank) as f64;
+    dense[ROOK_BEHIND_PASSER] = (white.behind_passer - black.behind_passer) as f64;
+
+    let white = pieces::bad_bishop_terms(board, Color::White);
+    let black = pieces::bad_bishop_terms(board, Color::Black);
+    dense[BAD_BISHOP] = -(white.blocking - black.blocking) as f64;
+    dense[BAD_BISHOP_CENTRE] = -(white.central - black.central) as f64;
+
+    dense
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, coefficient)| coefficient != 0.0)
+        .collect()
+}
+
+/// The weights `params` give the linear terms
+pub fn initial_weights(params: &EvalParams) -> Vec<f64> {
+    let mut weights = vec![0.0; NUM_PARAMS];
+    for index in 0..5 {
+        weights[MATERIAL + index] = params.piece_values[index] as f64;
+    }
+    for index in 0..6 {
+        for (square, &value) in params.table(index).iter().enumerate() {
+            weights[PST + index * 64 + square] = value as f64;
+        }
+    }
+    weights[DOUBLED] = params.doubled_pawn_penalty as f64;
+    weights[ISOLATED] = params.isolated_pawn_penalty as f64;
+    weights[BACKWARD] = params.backward_pawn_penalty as f64;
+    weights[BISHOP_PAIR] = params.bishop_pair_bonus as f64;
+    weights[KNIGHT_CLOSED] = params.knight_closed_bonus as f64;
+    weights[BISHOP_CLOSED] = params.bishop_closed_penalty as f64;
+    weights[ROOK_OPEN] = params.rook_open_file_bonus as f64;
+    weights[ROOK_SEMI_OPEN] = params.rook_semi_open_file_bonus as f64;
+    weights[ROOK_SEVENTH] = params.rook_seventh_rank_bonus as f64;
+    weights[ROOK_BEHIND_PASSER] = params.rook_behind_passer_bonus as f64;
+    weights[BAD_BISHOP] = params.bad_bishop_penalty as f64;
+    weights[BAD_BISHOP_CENTRE] = params.bad_bishop_centre_penalty as f64;
+    weights
+}
+
+/// Game result recorded on an EPD line, as `[1.0]`-style or `"1-0"`-style labels
+fn parse_result(line: &str) -> Option<f64> {
+    if let Some(start) = line.find('[') {
+        let end = line[start..].find(']')? + start;
+        return line[start + 1..end].trim().parse().ok();
+    }
+    if line.contains("1/2-1/2") {
+        Some(0.5)
+    } else if line.contains("1-0") {
+        Some(1.0)
+    } else if line.contains("0-1") {
+        Some(0.0)
+    } else {
+        None
+    }
+}
+
+/// Read labelled positions, one per line: the four EPD board fields followed by a
+/// result such as `[0.5]`, `"1-0"` or `c9 "1/2-1/2";`
+pub fn load_epd(text: &str) -> Result<Vec<TuningPosition>> {
+    let mut positions = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+        let position = Position::from_fen(&fields.join(" "))?;
+        let result = parse_result(line)
+            .ok_or_else(|| Error::InvalidPosition(format!("No game result in: {}", line)))?;
+        positions.push(TuningPosition::new(&position, result));
+    }
+    Ok(positions)
+}
+
+/// Read labelled positions from PGN games: every position of a game, from its start
+/// to the final one, is labelled with the game's `Result` tag, or the result
+/// closing its movetext. Unfinished games (`*`) are skipped.
+pub fn load_pgn(text: &str) -> Result<Vec<TuningPosition>> {
+    let mut positions = Vec::new();
+    for game in pgn::parse_pgn(text)? {
+        let label = game.tag("Result").or(game.result.as_deref());
+        let Some(result) = label.and_then(parse_result) else {
+            continue;
+        };
+        let mut position = game.start.clone();
+        positions.push(TuningPosition::new(&position, result));
+        for mv in &game.moves {
+            position.make_move(mv)?;
+            positions.push(TuningPosition::new(&position, result));
+        }
+    }
+    Ok(positions)
+}
+
+/// Expected score for a White-relative evaluation
+pub fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+/// Mean squared difference between results and predicted scores
+pub fn mean_error(positions: &[TuningPosition], weights: &[f64], k: f64) -> f64 {
+    let total: f64 = positions
+        .iter()
+        .map(|position| {
+            let error = position.result - sigmoid(position.evaluate(weights), k);
+            error * error
+        })
+        .sum();
+    total / positions.len().max(1) as f64
+}
+
+/// Scaling constant minimising the error of the given weights
+pub fn find_k(positions: &[TuningPosition], weights: &[f64]) -> f64 {
+    // The error is unimodal in k, so a ternary search converges
+    let (mut low, mut high) = (0.0, 10.0);
+    for _ in 0..100 {
+        let a = low + (high - low) / 3.0;
+        let b = high - (high - low) / 3.0;
+        if mean_error(positions, weights, a) < mean_error(positions, weights, b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Gradient of `mean_error` with respect to every weight
+fn gradient(positions: &[TuningPosition], weights: &[f64], k: f64) -> Vec<f64> {
+    let mut gradient = vec![0.0; weights.len()];
+    let scale = 2.0 * k * std::f64::consts::LN_10 / 400.0 / positions.len().max(1) as f64;
+
+    for position in positions {
+        let predicted = sigmoid(position.evaluate(weights), k);
+        let factor = (predicted - position.result) * predicted * (1.0 - predicted) * scale;
+        for &(index, coefficient) in &position.features {
+            gradient[index] += factor * coefficient;
+        }
+    }
+    gradient
+}
+
+/// Run `epochs` full-batch Adam steps on `weights`
+pub fn tune(
+    positions: &[TuningPosition],
+    weights: &mut [f64],
+    k: f64,
+    epochs: usize,
+    learning_rate: f64,
+) {
+    let mut m = vec![0.0; weights.len()];
+    let mut v = vec![0.0; weights.len()];
+
+    for epoch in 1..=epochs {
+        let gradient = gradient(positions, weights, k);
+        let m_correction = 1.0 - BETA1.powi(epoch as i32);
+        let v_correction = 1.0 - BETA2.powi(epoch as i32);
+
+        for i in 0..weights.len() {
+            m[i] = BETA1 * m[i] + (1.0 - BETA1) * gradient[i];
+            v[i] = BETA2 * v[i] + (1.0 - BETA2) * gradient[i] * gradient[i];
+            let step = m[i] / m_correction / ((v[i] / v_correction).sqrt() + 1e-8);
+            weights[i] -= learning_rate * step;
+        }
+    }
+}
+
+/// Copy of `base` with its linear terms replaced by the rounded `weights`
+pub fn to_params(weights: &[f64], base: &EvalParams) -> EvalParams {
+    let round = |index: usize| weights[index].round() as i32;
+    let mut params = base.clone();
+
+    for index in 0..5 {
+        params.piece_values[index] = round(MATERIAL + index);
+    }
+    for index in 0..6 {
+        for (square, value) in params.table_mut(index).iter_mut().enumerate() {
+            *value = round(PST + index * 64 + square);
+        }
+    }
+    params.doubled_pawn_penalty = round(DOUBLED);
+    params.isolated_pawn_penalty = round(ISOLATED);
+    params.backward_pawn_penalty = round(BACKWARD);
+    params.bishop_pair_bonus = round(BISHOP_PAIR);
+    params.knight_closed_bonus = round(KNIGHT_CLOSED);
+    params.bishop_closed_penalty = round(BISHOP_CLOSED);
+    params.rook_open_file_bonus = round(ROOK_OPEN);
+    params.rook_semi_open_file_bonus = round(ROOK_SEMI_OPEN);
+    params.rook_seventh_rank_bonus = round(ROOK_SEVENTH);
+    params.rook_behind_passer_bonus = round(ROOK_BEHIND_PASSER);
+    params.bad_bishop_penalty = round(BAD_BISHOP);
+    params.bad_bishop_centre_penalty = round(BAD_BISHOP_CENTRE);
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_features_reproduce_evaluation() {
+        let weights = initial_weights(&EvalParams::default());
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "6k1/1R6/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1",
+        ] {
+            let position = Position::from_fen(fen).unwrap();
+            let white_relative = match position.side_to_move {
+                Color::White => evaluate::evaluate(&position),
+                Color::Black => -evaluate::evaluate(&position),
+            };
+            let tuning = TuningPosition::new(&position, 0.5);
+            assert_eq!(
+                tuning.evaluate(&weights).round() as i32,
+                white_relative,
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn test_load_epd_results() {
+        let text = "\
+            4k3/8/8/8/8/8/8/3QK3 w - - [1.0]\n\
+            4k3/8/8/8/8/8/8/3QK3 w - - c9 \"1/2-1/2\";\n\
+            3qk3/8/8/8/8/8/8/4K3 b - - \"0-1\"\n";
+        let positions = load_epd(text).unwrap();
+        let results: Vec<f64> = positions.iter().map(|p| p.result).collect();
+        assert_eq!(results, vec![1.0, 0.5, 0.0]);
+
+        assert!(load_epd("4k3/8/8/8/8/8/8/3QK3 w - -").is_err());
+    }
+
+    #[test]
+    fn test_load_pgn_results() {
+        let text = "\
+            [Event \"a\"]\n\
+            [Result \"1-0\"]\n\
+            \n\
+            1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0\n\
+            \n\
+            [Event \"b\"]\n\
+            [Result \"*\"]\n\
+            \n\
+            1. d4 *\n\
+            \n\
+            [Event \"c\"]\n\
+            [Result \"1/2-1/2\"]\n\
+            \n\
+            1. Nf3 Nf6 1/2-1/2\n";
+        let positions = load_pgn(text).unwrap();
+        let results: Vec<f64> = positions.iter().map(|p| p.result).collect();
+        // The start and each of the seven moves, none of the unfinished game, then
+        // the start and both moves of the draw
+        assert_eq!(results, [vec![1.0; 8], vec![0.5; 3]].concat());
+
+        assert!(load_pgn("1. e4 e5 2. Ke3 1-0\n").is_err());
+    }
+
+    #[test]
+    fn test_tuning_reduces_error() {
+        // Up a knight always wins and level material always draws
+        let text = "\
+            4k3/pppp4/8/8/8/5N2/PPPP4/4K3 w - - [1.0]\n\
+            4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - [0.5]\n\
+            4k3/pppp4/8/2n5/8/8/PPPP4/4K3 w - - [0.0]\n";
+        let positions = load_epd(text).unwrap();
+        let mut weights = initial_weights(&EvalParams::default());
+        let k = 1.0;
+
+        let before = mean_error(&positions, &weights, k);
+        tune(&positions, &mut weights, k, 50, 5.0);
+        assert!(mean_error(&positions, &weights, k) < before);
+
+        // Untouched terms keep their values
+        let params = to_params(&weights, &EvalParams::default());
+        assert_ne!(params.knight_table, EvalParams::default().knight_table);
+        assert_eq!(params.storm_bonus, EvalParams::default().storm_bonus);
+    }
+}
@@ -0,0 +1,101 @@
+//! Weak squares: holes in the pawn camp and colour complexes left without their bishop
+
+use crate::{
+    bitboard::{DARK_SQUARES, LIGHT_SQUARES},
+    board::{Board, Color, PieceType},
+    params::EvalParams,
+    pawns,
+};
+
+/// Penalty for an enemy piece sitting on one of our holes, pawn to king
+pub(crate) const HOLE_OCCUPIED_PENALTY: [i32; 6] = [0, 25, 15, 10, 5, 0];
+
+/// Penalty per hole of a colour we have no bishop for, while the enemy still has one
+pub(crate) const WEAK_COMPLEX_PENALTY: i32 = 6;
+
+/// Penalty, as a non-positive score, for enemy pieces on the holes of `color`
+pub(crate) fn occupied_holes(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let holes = pawns::holes(board, color);
+    let mut score = 0;
+    for piece_type in [
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+    ] {
+        let occupiers = board.piece_bitboard(color.opposite(), piece_type) & holes;
+        score -= occupiers.count() as i32 * params.hole_occupied_penalty[piece_type as usize];
+    }
+    score
+}
+
+/// Penalty, as a non-positive score, for holes of `color` on a square colour only
+/// the enemy bishops can reach
+pub(crate) fn weak_complexes(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let holes = pawns::holes(board, color);
+    let ours = board.piece_bitboard(color, PieceType::Bishop);
+    let theirs = board.piece_bitboard(color.opposite(), PieceType::Bishop);
+
+    let mut score = 0;
+    for complex in [LIGHT_SQUARES, DARK_SQUARES] {
+        if (ours & complex).is_empty() && !(theirs & complex).is_empty() {
+            score -= (holes & complex).count() as i32 * params.weak_complex_penalty;
+        }
+    }
+    score
+}
+
+/// Weak square score of one side
+pub(crate) fn side_score(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    occupied_holes(board, color, params) + weak_complexes(board, color, params)
+}
+
+/// Weak square score from White's point of view
+pub fn evaluate(board: &Board, params: &EvalParams) -> i32 {
+    side_score(board, Color::White, params) - side_score(board, Color::Black, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_occupied_hole() {
+        // The black knight sits on d3, which no white pawn can ever attack
+        let pos = Position::from_fen("4k3/pppppppp/8/8/2P1P3/3n4/PP3PPP/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            occupied_holes(&pos.board, Color::White, &EvalParams::default()),
+            -HOLE_OCCUPIED_PENALTY[PieceType::Knight as usize]
+        );
+        assert_eq!(
+            occupied_holes(&pos.board, Color::Black, &EvalParams::default()),
+            0
+        );
+    }
+
+    #[test]
+    fn test_weak_colour_complex() {
+        // d3 and d4 are holes; only Black has a bishop for the light one
+        let pos = Position::from_fen("2b1k3/pppppppp/8/8/2P1P3/8/PP3PPP/4K3 w - - 0 1").unwrap();
+        let holes = pawns::holes(&pos.board, Color::White);
+        assert_eq!((holes & LIGHT_SQUARES).count(), 1);
+        assert_eq!(
+            weak_complexes(&pos.board, Color::White, &EvalParams::default()),
+            -WEAK_COMPLEX_PENALTY
+        );
+
+        // A white light-squared bishop covers the complex
+        let covered =
+            Position::from_fen("2b1k3/pppppppp/8/8/2P1P3/8/PP3PPP/4KB2 w - - 0 1").unwrap();
+        assert_eq!(
+            weak_complexes(&covered.board, Color::White, &EvalParams::default()),
+            0
+        );
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        assert_eq!(evaluate(&Position::new().board, &EvalParams::default()), 0);
+    }
+}
@@ -0,0 +1,211 @@
+//! Zobrist hashing of positions
+
+use crate::{
+    board::{Board, Color, Piece, PieceType, Square},
+    position::{CastlingRights, Position},
+};
+
+pub struct ZobristKeys {
+    pub pieces: [[[u64; 64]; 6]; 2],
+    pub side_to_move: u64,
+    pub castling: [u64; 16],
+    pub en_passant_file: [u64; 8],
+}
+
+lazy_static::lazy_static! {
+    pub static ref KEYS: ZobristKeys = {
+        // Fixed seed so hashes are reproducible between runs
+        let mut state = 0x9E3779B97F4A7C15u64;
+        let mut next = || {
+            state = state.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            z ^ (z >> 31)
+        };
+
+        let mut keys = ZobristKeys {
+            pieces: [[[0; 64]; 6]; 2],
+            side_to_move: 0,
+            castling: [0; 16],
+            en_passant_file: [0; 8],
+        };
+        for color in keys.pieces.iter_mut() {
+            for piece in color.iter_mut() {
+                for square in piece.iter_mut() {
+                    *square = next();
+                }
+            }
+        }
+        keys.side_to_move = next();
+        for key in keys.castling.iter_mut() {
+            *key = next();
+        }
+        for key in keys.en_passant_file.iter_mut() {
+            *key = next();
+        }
+        keys
+    };
+}
+
+/// Pack castling rights into a 4-bit index
+pub fn castling_index(rights: &CastlingRights) -> usize {
+    (rights.white_kingside as usize)
+        | (rights.white_queenside as usize) << 1
+        | (rights.black_kingside as usize) << 2
+        | (rights.black_queenside as usize) << 3
+}
+
+/// Key of `piece` standing on `square`
+pub fn piece_key(piece: Piece, square: Square) -> u64 {
+    KEYS.pieces[piece.color as usize][piece.piece_type as usize][square.index() as usize]
+}
+
+/// Key of the castling rights and en passant square, the parts of a position besides
+/// the pieces and side to move
+pub fn rights_key(rights: &CastlingRights, en_passant: Option<Square>) -> u64 {
+    let en_passant = en_passant.map_or(0, |square| KEYS.en_passant_file[square.file() as usize]);
+    KEYS.castling[castling_index(rights)] ^ en_passant
+}
+
+/// Key of the pawn structure alone, for the pawn hash table
+pub fn pawn_hash(board: &Board) -> u64 {
+    let keys = &*KEYS;
+    let mut key = 0;
+    for color in [Color::White, Color::Black] {
+        for square in board.piece_bitboard(color, PieceType::Pawn).squares() {
+            key ^= keys.pieces[color as usize][PieceType::Pawn as usize][square as usize];
+        }
+    }
+    key
+}
+
+/// Compute the Zobrist key of a position from scratch
+pub fn hash(position: &Position) -> u64 {
+    let keys = &*KEYS;
+    let mut key = 0;
+
+    for color in [Color::White, Color::Black] {
+        for piece_type in [
+            PieceType::Pawn,
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Rook,
+            PieceType::Queen,
+            PieceType::King,
+        ] {
+            for square in position.board.piece_bitboard(color, piece_type).squares() {
+                key ^= keys.pieces[color as usize][piece_type as usize][square as usize];
+            }
+        }
+    }
+
+    if position.side_to_move == Color::Black {
+        key ^= keys.side_to_move;
+    }
+    key ^ rights_key(&position.castling_rights, position.en_passant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_distinguishes_positions() {
+        let start = Position::new();
+        let mut moved = start.clone();
+        let mv = moved
+            .generate_moves()
+            .into_iter()
+            .find(|mv| mv.to_algebraic() == "g1f3")
+            .unwrap();
+        moved.make_move(&mv).unwrap();
+
+        assert_eq!(hash(&start), hash(&Position::new()));
+        assert_ne!(hash(&start), hash(&moved));
+    }
+
+    #[test]
+    fn test_hash_transposition() {
+        let mut a = Position::new();
+        let mut b = Position::new();
+        for (pos, line) in [
+            (&mut a, ["g1f3", "g8f6", "b1c3"]),
+            (&mut b, ["b1c3", "g8f6", "g1f3"]),
+        ] {
+            for mv_str in line {
+                let mv = pos
+                    .generate_moves()
+                    .into_iter()
+                    .find(|mv| mv.to_algebraic() == mv_str)
+                    .unwrap();
+                pos.make_move(&mv).unwrap();
+            }
+        }
+
+        assert_eq!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn test_pawn_hash_ignores_pieces() {
+        let start = Position::new();
+        let mut moved = start.clone();
+        let knight = moved
+            .generate_moves()
+            .into_iter()
+            .find(|mv| mv.to_algebraic() == "g1f3")
+            .unwrap();
+        moved.make_move(&knight).unwrap();
+        assert_eq!(pawn_hash(&start.board), pawn_hash(&moved.board));
+
+        let pawn = moved
+            .generate_moves()
+            .into_iter()
+            .find(|mv| mv.to_algebraic() == "e7e5")
+            .unwrap();
+        moved.make_move(&pawn).unwrap();
+        assert_ne!(pawn_hash(&start.board), pawn_hash(&moved.board));
+    }
+
+    #[test]
+    fn test_incremental_key_matches_hash() {
+        // Castling, en passant and promotion all change the key
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut pos = Position::from_fen(fen).unwrap();
+        for mv in pos.generate_moves() {
+            pos.make_move(&mv).unwrap();
+            assert_eq!(pos.key(), hash(&pos), "after {}", mv.to_algebraic());
+            for reply in pos.generate_moves() {
+                pos.make_move(&reply).unwrap();
+                assert_eq!(pos.key(), hash(&pos));
+                pos.undo_move().unwrap();
+            }
+            pos.undo_move().unwrap();
+        }
+        assert_eq!(pos.key(), hash(&pos));
+    }
+
+    #[test]
+    fn test_incremental_pawn_key_matches_hash() {
+        // En passant, promotions and pawn captures all change the pawn key
+        let fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        let mut pos = Position::from_fen(fen).unwrap();
+        let initial = pos.pawn_key();
+        for mv in pos.generate_moves() {
+            pos.make_move(&mv).unwrap();
+            assert_eq!(
+                pos.pawn_key(),
+                pawn_hash(&pos.board),
+                "after {}",
+                mv.to_algebraic()
+            );
+            for reply in pos.generate_moves() {
+                pos.make_move(&reply).unwrap();
+                assert_eq!(pos.pawn_key(), pawn_hash(&pos.board));
+                pos.undo_move().unwrap();
+            }
+            pos.undo_move().unwrap();
+        }
+        assert_eq!(pos.pawn_key(), initial);
+    }
+}
@@ -7,6 +7,51 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize magic bitboards
     magic::init();
 
+    // `clockwork bench [depth]` prints the node signature and speed, then exits
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("bench") {
+        let depth = match args.get(2) {
+            Some(depth) => depth.parse()?,
+            None => chess_core::search::BENCH_DEPTH,
+        };
+        let result = chess_core::search::bench_to_depth(depth)?;
+        println!("{} nodes {} nps", result.nodes, result.nps);
+        return Ok(());
+    }
+
+    // `clockwork tune <positions.epd|games.pgn> [epochs]` fits the evaluation weights
+    // to game results and prints them as an evaluation parameter file
+    if args.get(1).map(String::as_str) == Some("tune") {
+        use chess_core::{params::EvalParams, tuner};
+
+        let path = args
+            .get(2)
+            .ok_or("usage: clockwork tune <positions.epd|games.pgn> [epochs]")?;
+        let epochs = match args.get(3) {
+            Some(epochs) => epochs.parse()?,
+            None => 1000,
+        };
+        let text = std::fs::read_to_string(path)?;
+        let positions = if path.to_ascii_lowercase().ends_with(".pgn") {
+            tuner::load_pgn(&text)?
+        } else {
+            tuner::load_epd(&text)?
+        };
+        let base = EvalParams::default();
+        let mut weights = tuner::initial_weights(&base);
+        let k = tuner::find_k(&positions, &weights);
+        eprintln!(
+            "{} positions, K = {:.3}, error {:.6}",
+            positions.len(),
+            k,
+            tuner::mean_error(&positions, &weights, k)
+        );
+        tuner::tune(&positions, &mut weights, k, epochs, 1.0);
+        eprintln!("final error {:.6}", tuner::mean_error(&positions, &weights, k));
+        print!("{}", tuner::to_params(&weights, &base).to_toml());
+        return Ok(());
+    }
+
     // Create and run UCI engine
     let mut engine = UciEngine::new();
     if let Err(e) = engine.run() {
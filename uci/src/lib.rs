@@ -1,38 +1,221 @@
 
 
-use chess_core::{moves::Move, position::Position, search};
+use chess_core::{
+    moves::Move,
+    params::{EvalParams, Tunable},
+    position::Position,
+    rules::{self, Rules, Standard},
+    search,
+    smp::{self, WorkerPool},
+    tt::TranspositionTable,
+};
 use std::io::{self, BufRead, Write};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub mod options;
+
+use options::{OptionRegistry, OptionValue, UciOption};
 
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
+/// Tokens that start a new parameter in a `go` command
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves", "ponder", "wtime", "btime", "winc", "binc", "movestogo", "depth", "nodes",
+    "mate", "movetime", "infinite",
+];
+
+/// Where responses and search output are written, shared with the search thread
+type Output = Arc<Mutex<dyn Write + Send>>;
+
+/// Write `line` to `output` and flush it so the GUI sees it at once
+fn write_line(output: &Output, line: &str) -> io::Result<()> {
+    let mut output = output.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    writeln!(output, "{}", line)?;
+    output.flush()
+}
+
+/// `UCI_Variant` value for Chess960: the standard rules with castling written as
+/// the king taking its rook
+const CHESS960_VARIANT: &str = "chess960";
+
+/// How often a search that ended early, pondering or infinite, checks for `ponderhit` or `stop`
+const PONDER_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Search time after which the root move being searched is reported, so short
+/// searches are not flooded with `currmove` lines
+const CURRMOVE_DELAY: Duration = Duration::from_secs(1);
+
+/// `moves` played in turn from `position`, in UCI notation
+fn line_to_uci(position: &Position, moves: &[Move], chess960: bool) -> Vec<String> {
+    let mut position = position.clone();
+    moves
+        .iter()
+        .map(|mv| {
+            let uci = position.move_to_uci(mv, chess960);
+            let _ = position.make_move(mv);
+            uci
+        })
+        .collect()
+}
+
+/// Writes an `info` line for every completed search iteration
+struct InfoWriter {
+    output: Output,
+    /// Root of the search, to write castling in the PV in Chess960 notation
+    position: Position,
+    chess960: bool,
+    /// Principal variation of the last iteration, for the ponder move
+    pv: Vec<Move>,
+    started: Instant,
+}
+
+impl InfoWriter {
+    /// The reply expected to `best_move`, when the last PV starts with it
+    fn ponder_move(&self, best_move: Move) -> Option<Move> {
+        match self.pv.as_slice() {
+            [first, reply, ..] if *first == best_move => Some(*reply),
+            _ => None,
+        }
+    }
+}
+
+impl search::SearchObserver for InfoWriter {
+    fn on_iteration(&mut self, info: &search::IterationInfo) {
+        let score = match search::mate_in(info.score) {
+            Some(moves) => format!("mate {}", moves),
+            None => format!("cp {}", info.score),
+        };
+        let pv = line_to_uci(&self.position, &info.pv, self.chess960);
+        let mut line = format!(
+            "info depth {} seldepth {} score {} nodes {} time {}",
+            info.depth, info.seldepth, score, info.nodes, info.elapsed_ms
+        );
+        if !pv.is_empty() {
+            line.push_str(" pv ");
+            line.push_str(&pv.join(" "));
+        }
+        let _ = write_line(&self.output, &line);
+        self.pv = info.pv.clone();
+    }
+
+    fn on_current_move(&mut self, mv: Move, number: usize, depth: u32) {
+        if self.started.elapsed() >= CURRMOVE_DELAY {
+            let line = format!(
+                "info depth {} currmove {} currmovenumber {}",
+                depth,
+                self.position.move_to_uci(&mv, self.chess960),
+                number
+            );
+            let _ = write_line(&self.output, &line);
+        }
+    }
+}
+
+/// A `go` running on its own thread. The thread writes `bestmove` when it ends and
+/// hands the worker pool back.
+struct SearchThread {
+    stop: Arc<AtomicBool>,
+    ponder: Arc<AtomicBool>,
+    handle: thread::JoinHandle<WorkerPool>,
+}
 
 pub struct UciEngine {
     position: Position,
     search_params: search::SearchParams,
+    skill_level: u8,
+    limit_strength: bool,
+    /// Castling is written as the king taking its rook (`UCI_Chess960`)
+    chess960: bool,
+    /// Rule set selected with `UCI_Variant`, given to every position set up
+    rules: Arc<dyn Rules>,
+    elo: u32,
+    /// Kept for the whole game so later searches reuse earlier results
+    tt: Arc<TranspositionTable>,
+    /// Allocation or clearing of the table running in the background, so that
+    /// `isready` is answered meanwhile. The next search waits for it.
+    table_job: Option<thread::JoinHandle<Arc<TranspositionTable>>>,
+    /// Evaluation weights, adjustable through hidden options for SPSA tuning
+    eval_params: Arc<EvalParams>,
+    /// Search constants, adjustable through hidden options for SPSA tuning
+    tuning: search::SearchTuning,
+    /// Options advertised by `uci` and accepted by `setoption`
+    options: OptionRegistry,
+    /// Helper threads for the `Threads` option, kept between searches. The running
+    /// search owns them until it ends.
+    pool: WorkerPool,
+    /// The search started by the last `go`, until it is stopped or waited for
+    search: Option<SearchThread>,
+    output: Output,
 }
 
 impl UciEngine {
     
     pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    /// An engine writing its responses and search output to `output`
+    pub fn with_output(output: impl Write + Send + 'static) -> Self {
         Self {
             position: Position::new(),
             search_params: search::SearchParams::default(),
+            skill_level: search::MAX_SKILL_LEVEL,
+            limit_strength: false,
+            chess960: false,
+            rules: Arc::new(Standard),
+            elo: search::MAX_ELO,
+            tt: Arc::new(TranspositionTable::default()),
+            table_job: None,
+            eval_params: EvalParams::shared_default(),
+            tuning: search::SearchTuning::default(),
+            options: Self::declare_options(),
+            pool: WorkerPool::default(),
+            search: None,
+            output: Arc::new(Mutex::new(output)),
         }
     }
 
+    /// Every option the engine declares, with its default and bounds
+    fn declare_options() -> OptionRegistry {
+        let mut options = OptionRegistry::new();
+        let max_skill = search::MAX_SKILL_LEVEL as i64;
+        options.add(UciOption::spin("Skill Level", max_skill, 0, max_skill));
+        options.add(UciOption::check("UCI_LimitStrength", false));
+        let (min_elo, max_elo) = (search::MIN_ELO as i64, search::MAX_ELO as i64);
+        options.add(UciOption::spin("UCI_Elo", max_elo, min_elo, max_elo));
+        options.add(UciOption::spin(
+            "Hash",
+            TranspositionTable::DEFAULT_SIZE_MB as i64,
+            1,
+            TranspositionTable::MAX_SIZE_MB as i64,
+        ));
+        options.add(UciOption::spin("Threads", 1, 1, smp::MAX_THREADS as i64));
+        // Pondering is driven by the GUI through `go ponder`; the option only tells
+        // the GUI that the engine supports it
+        options.add(UciOption::check("Ponder", false));
+        options.add(UciOption::check("UCI_Chess960", false));
+        let mut variants = rules::VARIANTS.to_vec();
+        variants.push(CHESS960_VARIANT);
+        options.add(UciOption::combo("UCI_Variant", variants[0], &variants));
+        options
+    }
+
     
     pub fn run(&mut self) -> Result<()> {
         let stdin = io::stdin();
-        let mut stdout = io::stdout();
 
         for line in stdin.lock().lines() {
             let line = line?;
             let response = self.handle_command(&line)?;
 
             if let Some(response) = response {
-                writeln!(stdout, "{}", response)?;
-                stdout.flush()?;
+                write_line(&self.output, &response)?;
             }
 
             if line == "quit" {
@@ -57,10 +240,12 @@ impl UciEngine {
             "position" => self.handle_position(&parts[1..]),
             "go" => self.handle_go(&parts[1..]),
             "stop" => self.handle_stop(),
-            "quit" => Ok(None), 
+            "ponderhit" => self.handle_ponderhit(),
+            "quit" => self.handle_stop(),
             "debug" => self.handle_debug(&parts[1..]),
             "setoption" => self.handle_setoption(&parts[1..]),
             "register" => self.handle_register(),
+            "spsa" => self.handle_spsa(),
             _ => Ok(Some("Unknown command".to_string())),
         }
     }
@@ -70,18 +255,31 @@ impl UciEngine {
         let mut response = String::new();
         response.push_str("id name Castono Chess Engine\n");
         response.push_str("id author Claude Code\n");
+        for option in self.options.iter() {
+            response.push_str(&format!("{}\n", option));
+        }
         response.push_str("uciok");
         Ok(Some(response))
     }
 
-    
+    /// Answered at once, also while a search runs or the table is being prepared
     fn handle_isready(&self) -> Result<Option<String>> {
         Ok(Some("readyok".to_string()))
     }
 
-    
+    /// Forget the previous game: end its search, pondering included, and reset the
+    /// position with its repetition history, the last search parameters and the
+    /// table. Move ordering keeps no killer or history tables between searches.
     fn handle_ucinewgame(&mut self) -> Result<Option<String>> {
-        self.position = Position::new();
+        self.stop_search();
+        self.search_params = search::SearchParams::default();
+        self.set_position(Position::new());
+        self.wait_for_table();
+        let tt = Arc::clone(&self.tt);
+        self.start_table_job(move || {
+            tt.clear();
+            tt
+        })?;
         Ok(None)
     }
 
@@ -93,7 +291,7 @@ impl UciEngine {
 
         match args[0] {
             "startpos" => {
-                self.position = Position::new();
+                self.set_position(Position::new());
                 if args.len() > 1 && args[1] == "moves" {
                     self.apply_moves(&args[2..])?;
                 }
@@ -104,8 +302,9 @@ impl UciEngine {
                 }
                 let fen_parts: Vec<&str> = args[1..].iter().take(6).copied().collect();
                 let fen = fen_parts.join(" ");
-                self.position = Position::from_fen(&fen)
+                let position = Position::from_fen(&fen)
                     .map_err(|e| format!("Invalid FEN: {}", e))?;
+                self.set_position(position);
 
                 
                 let moves_start = 1 + fen_parts.len();
@@ -120,64 +319,195 @@ impl UciEngine {
     }
 
     
+    /// Start a search on its own thread and return at once; `bestmove` is written
+    /// when the search finishes or is stopped
     fn handle_go(&mut self, args: &[&str]) -> Result<Option<String>> {
-        let mut params = search::SearchParams::default();
+        self.stop_search();
+        self.wait_for_table();
+        let mut params = search::SearchParams {
+            skill_level: self.effective_skill_level(),
+            tt: Some(Arc::clone(&self.tt)),
+            tuning: self.tuning,
+            ..Default::default()
+        };
+        let mut depth_given = false;
+        let mut infinite = false;
+        let mut ponder = false;
 
         let mut i = 0;
         while i < args.len() {
             match args[i] {
-                "depth" => {
-                    if i + 1 < args.len() {
-                        params.depth = args[i + 1].parse().unwrap_or(4);
-                        i += 1;
-                    }
+                "depth" if i + 1 < args.len() => {
+                    params.depth = args[i + 1].parse().unwrap_or(4);
+                    depth_given = true;
+                    i += 1;
                 }
-                "movetime" => {
-                    if i + 1 < args.len() {
-                        params.time_limit_ms = Some(args[i + 1].parse().unwrap_or(1000));
-                        i += 1;
-                    }
+                "movetime" if i + 1 < args.len() => {
+                    params.time_limit_ms = Some(args[i + 1].parse().unwrap_or(1000));
+                    i += 1;
+                }
+                "wtime" if i + 1 < args.len() => {
+                    params.wtime_ms = args[i + 1].parse().ok();
+                    i += 1;
+                }
+                "btime" if i + 1 < args.len() => {
+                    params.btime_ms = args[i + 1].parse().ok();
+                    i += 1;
                 }
-                "nodes" => {
-                    if i + 1 < args.len() {
-                        params.nodes_limit = Some(args[i + 1].parse().unwrap_or(1000000));
+                "winc" if i + 1 < args.len() => {
+                    params.winc_ms = args[i + 1].parse().unwrap_or(0);
+                    i += 1;
+                }
+                "binc" if i + 1 < args.len() => {
+                    params.binc_ms = args[i + 1].parse().unwrap_or(0);
+                    i += 1;
+                }
+                "movestogo" if i + 1 < args.len() => {
+                    params.moves_to_go = args[i + 1].parse().ok();
+                    i += 1;
+                }
+                "nodes" if i + 1 < args.len() => {
+                    params.nodes_limit = Some(args[i + 1].parse().unwrap_or(1000000));
+                    i += 1;
+                }
+                "mate" if i + 1 < args.len() => {
+                    params.mate = args[i + 1].parse().ok();
+                    i += 1;
+                }
+                "searchmoves" => {
+                    let mut moves = Vec::new();
+                    while i + 1 < args.len() && !GO_KEYWORDS.contains(&args[i + 1]) {
+                        let mv = self.position.parse_uci_move_with(args[i + 1], self.chess960)?;
+                        moves.push(mv);
                         i += 1;
                     }
+                    params.search_moves = Some(moves);
                 }
+                "ponder" => ponder = true,
                 "infinite" => {
                     params.time_limit_ms = None;
+                    params.wtime_ms = None;
+                    params.btime_ms = None;
                     params.nodes_limit = None;
+                    infinite = true;
                 }
                 _ => {}
             }
             i += 1;
         }
 
-        self.search_params = params;
+        // Limited searches deepen until their limit runs out, infinite ones until `stop`
+        let limited = params.has_time_limit() || params.nodes_limit.is_some();
+        if (limited || infinite || ponder) && !depth_given {
+            params.depth = search::MAX_PLY;
+        }
 
-        
-        let result = search::search(&self.position, &self.search_params)?;
-
-        if let Some(best_move) = result.best_move {
-            let response = format!(
-                "bestmove {}\ninfo depth {} score cp {} nodes {}",
-                best_move.to_algebraic(),
-                result.depth,
-                result.score,
-                result.stats.nodes_searched
-            );
-            Ok(Some(response))
-        } else {
-            Ok(Some("bestmove 0000".to_string())) 
+        params.ponder.store(ponder, Ordering::Relaxed);
+        self.search_params = params.clone();
+
+        let mut info = InfoWriter {
+            output: Arc::clone(&self.output),
+            position: self.position.clone(),
+            chess960: self.chess960,
+            pv: Vec::new(),
+            started: Instant::now(),
+        };
+        let output = Arc::clone(&self.output);
+        let position = self.position.clone();
+        let stop = Arc::clone(&params.stop);
+        let pondering = Arc::clone(&params.ponder);
+        let pool = std::mem::take(&mut self.pool);
+        let handle = thread::Builder::new()
+            .name("search".to_string())
+            .stack_size(smp::SEARCH_STACK_SIZE)
+            .spawn(move || {
+                let result = pool.think(&position, &params, &mut info);
+                // No `bestmove` may be sent while pondering or searching infinitely, even
+                // if the search is over
+                while (infinite || params.ponder.load(Ordering::Relaxed))
+                    && !params.stop.load(Ordering::Relaxed)
+                {
+                    thread::sleep(PONDER_POLL_INTERVAL);
+                }
+                let line = match result {
+                    Ok(result) => match result.best_move {
+                        Some(best) => {
+                            let mut line = vec![best];
+                            line.extend(info.ponder_move(best));
+                            let line = line_to_uci(&position, &line, info.chess960);
+                            match line.get(1) {
+                                Some(reply) => format!("bestmove {} ponder {}", line[0], reply),
+                                None => format!("bestmove {}", line[0]),
+                            }
+                        }
+                        None => "bestmove 0000".to_string(),
+                    },
+                    // A GUI waits for `bestmove` whatever happened
+                    Err(e) => format!("info string Search failed: {}\nbestmove 0000", e),
+                };
+                let _ = write_line(&output, &line);
+                pool
+            })?;
+        self.search = Some(SearchThread {
+            stop,
+            ponder: pondering,
+            handle,
+        });
+        Ok(None)
+    }
+
+    /// Stop the running search; its `bestmove` is written before this returns
+    fn handle_stop(&mut self) -> Result<Option<String>> {
+        self.stop_search();
+        Ok(None)
+    }
+
+    /// Prepare the table on a background thread; `wait_for_table` installs it
+    fn start_table_job(
+        &mut self,
+        job: impl FnOnce() -> Arc<TranspositionTable> + Send + 'static,
+    ) -> Result<()> {
+        let handle = thread::Builder::new()
+            .name("table".to_string())
+            .spawn(job)?;
+        self.table_job = Some(handle);
+        Ok(())
+    }
+
+    /// Block until the table being allocated or cleared is ready for use
+    fn wait_for_table(&mut self) {
+        if let Some(job) = self.table_job.take() {
+            self.tt = job
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
         }
     }
 
-    
-    fn handle_stop(&self) -> Result<Option<String>> {
-        
+    /// The opponent played the move pondered on: keep searching, now on the clock
+    fn handle_ponderhit(&mut self) -> Result<Option<String>> {
+        if let Some(search) = &self.search {
+            search.ponder.store(false, Ordering::Relaxed);
+        }
         Ok(None)
     }
 
+    fn stop_search(&mut self) {
+        if let Some(search) = &self.search {
+            search.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait_for_search();
+    }
+
+    /// Block until the running search ends by itself, taking back its worker pool
+    fn wait_for_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.pool = search
+                .handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        }
+    }
+
     
     fn handle_debug(&self, args: &[&str]) -> Result<Option<String>> {
         if !args.is_empty() && args[0] == "on" {
@@ -190,11 +520,112 @@ impl UciEngine {
     }
 
     
-    fn handle_setoption(&self, _args: &[&str]) -> Result<Option<String>> {
-        
+    fn handle_setoption(&mut self, args: &[&str]) -> Result<Option<String>> {
+        // Options configure the next search, and the table and pool must be idle
+        self.stop_search();
+        // Option names may contain spaces: setoption name <name...> [value <value...>]
+        let value_pos = args.iter().position(|&arg| arg == "value");
+        let name = args
+            .get(1..value_pos.unwrap_or(args.len()))
+            .unwrap_or_default()
+            .join(" ");
+        let value = value_pos.map(|pos| args[pos + 1..].join(" ")).unwrap_or_default();
+
+        // A bad value is reported like an unknown name, leaving the engine running
+        if let Some(option) = self.options.get(&name) {
+            match option.parse_value(&value) {
+                Ok(value) => self.apply_option(option.name, value)?,
+                Err(e) => return Ok(Some(format!("info string {}", e))),
+            }
+        } else if let Some(tunable) =
+            self.tunables().into_iter().find(|t| t.name.eq_ignore_ascii_case(&name))
+        {
+            // Tunables are hidden from `uci` but accepted like any spin option
+            let Ok(value) = value.parse::<i32>() else {
+                return Ok(Some(format!("info string Invalid value for {}: {}", name, value)));
+            };
+            self.set_tunable(&tunable.name, value.clamp(tunable.min, tunable.max));
+        } else {
+            return Ok(Some(format!("info string No such option: {}", name)));
+        }
+
         Ok(None)
     }
 
+    /// Hand a checked option value to the engine component it configures
+    fn apply_option(&mut self, name: &str, value: OptionValue) -> Result<()> {
+        match (name, value) {
+            ("Skill Level", OptionValue::Spin(skill)) => self.skill_level = skill as u8,
+            ("UCI_LimitStrength", OptionValue::Check(on)) => self.limit_strength = on,
+            ("UCI_Chess960", OptionValue::Check(on)) => self.chess960 = on,
+            ("UCI_Variant", OptionValue::String(variant)) => {
+                self.chess960 = variant == CHESS960_VARIANT;
+                self.rules = rules::by_name(&variant).unwrap_or_else(|| Arc::new(Standard));
+                self.position.set_rules(Arc::clone(&self.rules));
+            }
+            ("UCI_Elo", OptionValue::Spin(elo)) => self.elo = elo as u32,
+            ("Hash", OptionValue::Spin(size_mb)) => {
+                // Free the old table first so the two never take memory together
+                self.wait_for_table();
+                self.tt = Arc::new(TranspositionTable::with_entries(0));
+                self.start_table_job(move || Arc::new(TranspositionTable::new(size_mb as usize)))?;
+            }
+            ("Threads", OptionValue::Spin(threads)) if threads as usize != self.pool.threads() => {
+                // Join the old helpers before starting the new ones
+                self.pool = WorkerPool::default();
+                self.pool = WorkerPool::new(threads as usize - 1);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Every search and evaluation parameter exposed to external tuners
+    fn tunables(&self) -> Vec<Tunable> {
+        let mut tunables = self.tuning.tunables();
+        tunables.extend(self.eval_params.tunables());
+        tunables
+    }
+
+    fn set_tunable(&mut self, name: &str, value: i32) {
+        if !self.tuning.set_tunable(name, value) {
+            Arc::make_mut(&mut self.eval_params).set_tunable(name, value);
+            self.position.set_params(Arc::clone(&self.eval_params));
+        }
+    }
+
+    /// Print the tunables as SPSA inputs in the OpenBench format:
+    /// `name, int, value, min, max, c_end, r_end`
+    fn handle_spsa(&self) -> Result<Option<String>> {
+        let lines: Vec<String> = self
+            .tunables()
+            .iter()
+            .map(|t| {
+                let c_end = ((t.max - t.min) / 20).max(1);
+                format!("{}, int, {}, {}, {}, {}, 0.002", t.name, t.value, t.min, t.max, c_end)
+            })
+            .collect();
+        Ok(Some(lines.join("\n")))
+    }
+
+    /// Make `position` current, evaluated with the engine's parameters
+    fn set_position(&mut self, mut position: Position) {
+        position.set_params(Arc::clone(&self.eval_params));
+        position.set_rules(Arc::clone(&self.rules));
+        self.position = position;
+    }
+
+    /// Skill level to search with, if strength limiting is active
+    fn effective_skill_level(&self) -> Option<u8> {
+        if self.limit_strength {
+            Some(search::skill_from_elo(self.elo))
+        } else if self.skill_level < search::MAX_SKILL_LEVEL {
+            Some(self.skill_level)
+        } else {
+            None
+        }
+    }
+
     
     fn handle_register(&self) -> Result<Option<String>> {
         Ok(None) 
@@ -203,46 +634,11 @@ impl UciEngine {
     
     fn apply_moves(&mut self, moves: &[&str]) -> Result<()> {
         for move_str in moves {
-            let mv = self.parse_move(move_str)?;
+            let mv = self.position.parse_uci_move_with(move_str, self.chess960)?;
             self.position.make_move(&mv)?;
         }
         Ok(())
     }
-
-    
-    fn parse_move(&self, move_str: &str) -> Result<Move> {
-        
-        if move_str.len() < 4 {
-            return Err(format!("Invalid move format: {}", move_str).into());
-        }
-
-        let from_str = &move_str[0..2];
-        let to_str = &move_str[2..4];
-
-        let from_square = chess_core::board::Square::from_algebraic(from_str)
-            .ok_or_else(|| format!("Invalid from square: {}", from_str))?;
-        let to_square = chess_core::board::Square::from_algebraic(to_str)
-            .ok_or_else(|| format!("Invalid to square: {}", to_str))?;
-
-        
-        let piece = self.position.board.piece_at(from_square)
-            .ok_or_else(|| format!("No piece at {}", from_str))?;
-
-        
-        if move_str.len() == 5 {
-            let promotion_char = move_str.chars().nth(4).unwrap();
-            let promotion = match promotion_char {
-                'n' | 'N' => chess_core::board::PieceType::Knight,
-                'b' | 'B' => chess_core::board::PieceType::Bishop,
-                'r' | 'R' => chess_core::board::PieceType::Rook,
-                'q' | 'Q' => chess_core::board::PieceType::Queen,
-                _ => return Err(format!("Invalid promotion: {}", promotion_char).into()),
-            };
-            Ok(Move::new_promotion(from_square, to_square, piece.piece_type, promotion))
-        } else {
-            Ok(Move::new(from_square, to_square, piece.piece_type))
-        }
-    }
 }
 
 impl Default for UciEngine {
@@ -251,10 +647,50 @@ impl Default for UciEngine {
     }
 }
 
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        self.stop_search();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Output of an engine, shared with the test reading it
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        /// Everything written since the last call
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    fn captured_engine() -> (UciEngine, Captured) {
+        let output = Captured::default();
+        (UciEngine::with_output(output.clone()), output)
+    }
+
+    /// Run a `go` command until the search ends and return what it wrote
+    fn go(engine: &mut UciEngine, output: &Captured, command: &str) -> String {
+        assert_eq!(engine.handle_command(command).unwrap(), None);
+        engine.wait_for_search();
+        output.take()
+    }
+
     #[test]
     fn test_uci_commands() {
         let mut engine = UciEngine::new();
@@ -285,5 +721,378 @@ mod tests {
         
         let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
         engine.handle_command(&format!("position fen {}", fen)).unwrap();
+
+        // Castling is sent as a two-square king move
+        engine
+            .handle_command("position startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1g1")
+            .unwrap();
+        let castled = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq";
+        assert!(engine.position.to_fen().starts_with(castled));
+
+
+        // Illegal moves are rejected rather than played
+        assert!(engine.handle_command("position startpos moves e2e5").is_err());
+        let err = engine.handle_command("position startpos moves e7e5").unwrap_err();
+        assert_eq!(err.to_string(), "Illegal move e7e5: the piece on e7 is the opponent's");
+
+        // Moves sent with the position count towards repetitions
+        engine
+            .handle_command("position startpos moves g1f3 g8f6 f3g1 f6g8")
+            .unwrap();
+        assert!(engine.position.is_repetition());
+    }
+
+    #[test]
+    fn test_go_searchmoves() {
+        let (mut engine, output) = captured_engine();
+
+        engine.handle_command("position startpos").unwrap();
+        let response = go(&mut engine, &output, "go depth 2 searchmoves a2a3 h2h3");
+        assert!(response.contains("bestmove a2a3") || response.contains("bestmove h2h3"));
+    }
+
+    #[test]
+    fn test_go_movetime() {
+        let (mut engine, output) = captured_engine();
+
+        engine.handle_command("position startpos").unwrap();
+        let response = go(&mut engine, &output, "go movetime 50");
+        assert!(response.lines().last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn test_go_clock() {
+        let (mut engine, output) = captured_engine();
+
+        engine.handle_command("position startpos moves e2e4").unwrap();
+        let start = std::time::Instant::now();
+        let command = "go wtime 100000 btime 600 winc 0 binc 0 movestogo 20";
+        let response = go(&mut engine, &output, command);
+        assert!(response.lines().last().unwrap().starts_with("bestmove "));
+        // Black's clock decides the budget, not White's
+        assert!(start.elapsed().as_millis() < 2_000);
+        assert_eq!(engine.search_params.btime_ms, Some(600));
+        assert_eq!(engine.search_params.moves_to_go, Some(20));
+    }
+
+    #[test]
+    fn test_skill_options() {
+        let mut engine = UciEngine::new();
+
+        let response = engine.handle_command("uci").unwrap().unwrap();
+        assert!(response.contains("option name Skill Level type spin"));
+        assert_eq!(engine.effective_skill_level(), None);
+
+        engine.handle_command("setoption name Skill Level value 5").unwrap();
+        assert_eq!(engine.effective_skill_level(), Some(5));
+
+        engine.handle_command("setoption name UCI_LimitStrength value true").unwrap();
+        engine.handle_command("setoption name UCI_Elo value 1350").unwrap();
+        assert_eq!(engine.effective_skill_level(), Some(0));
+    }
+
+    #[test]
+    fn test_setoption() {
+        let mut engine = UciEngine::new();
+
+        let response = engine.handle_command("uci").unwrap().unwrap();
+        let declared: Vec<&str> = response.lines().filter(|l| l.starts_with("option ")).collect();
+        assert_eq!(declared.len(), engine.options.iter().count());
+        assert!(declared.contains(&"option name UCI_LimitStrength type check default false"));
+
+        // Names are matched ignoring case, spins are clamped to their bounds
+        engine.handle_command("setoption name skill level value 99").unwrap();
+        assert_eq!(engine.skill_level, search::MAX_SKILL_LEVEL);
+        let response = engine.handle_command("setoption name Skill Level value high").unwrap();
+        assert_eq!(response, Some("info string Invalid value for Skill Level: high".to_string()));
+        assert_eq!(engine.skill_level, search::MAX_SKILL_LEVEL);
+        let response = engine.handle_command("setoption name UCI_LimitStrength value maybe");
+        assert!(response.unwrap().unwrap().starts_with("info string Invalid value"));
+
+        let response = engine.handle_command("setoption name Contempt value 10").unwrap();
+        assert_eq!(response, Some("info string No such option: Contempt".to_string()));
+    }
+
+    #[test]
+    fn test_setoption_bad_value_keeps_running() {
+        let mut engine = UciEngine::new();
+
+        let response = engine.handle_command("setoption name Hash value abc").unwrap();
+        assert_eq!(response, Some("info string Invalid value for Hash: abc".to_string()));
+        let response = engine.handle_command("setoption name UCI_Variant value atomic").unwrap();
+        assert_eq!(response, Some("info string Invalid value for UCI_Variant: atomic".to_string()));
+        let tunable = engine.tunables()[0].name.clone();
+        let command = format!("setoption name {} value lots", tunable);
+        let response = engine.handle_command(&command).unwrap().unwrap();
+        assert!(response.starts_with("info string Invalid value for "));
+
+        let response = engine.handle_command("isready").unwrap();
+        assert_eq!(response, Some("readyok".to_string()));
+    }
+
+    #[test]
+    fn test_tuning_options() {
+        let (mut engine, output) = captured_engine();
+
+        let response = engine.handle_command("uci").unwrap().unwrap();
+        assert!(!response.contains("multi_cut_moves"));
+
+        engine.handle_command("setoption name multi_cut_moves value 4").unwrap();
+        engine.handle_command("setoption name multi_cut_reduction value 9").unwrap();
+        assert_eq!(engine.tuning.multi_cut_moves, 4);
+        assert_eq!(engine.tuning.multi_cut_reduction, 3);
+
+        engine.handle_command("setoption name bishop_pair_bonus value 80").unwrap();
+        engine.handle_command("position startpos moves e2e4").unwrap();
+        assert_eq!(engine.position.params().bishop_pair_bonus, 80);
+
+        let inputs = engine.handle_command("spsa").unwrap().unwrap();
+        assert!(inputs.lines().any(|line| line.starts_with("bishop_pair_bonus, int, 80, ")));
+        assert!(go(&mut engine, &output, "go depth 2").contains("bestmove"));
+    }
+
+    #[test]
+    fn test_go_reports_info_per_iteration() {
+        let (mut engine, output) = captured_engine();
+
+        engine.handle_command("position startpos").unwrap();
+        let response = go(&mut engine, &output, "go depth 3");
+        let lines: Vec<&str> = response.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("info depth 1 "));
+        assert!(lines[2].starts_with("info depth 3 "));
+        assert!(lines[2].contains(" pv "));
+        assert!(lines[3].starts_with("bestmove "));
+    }
+
+    #[test]
+    fn test_tt_persists_between_searches() {
+        let (mut engine, output) = captured_engine();
+
+        engine.handle_command("position startpos").unwrap();
+        go(&mut engine, &output, "go depth 2");
+        let generation = engine.tt.generation();
+        assert!(engine.tt.probe(engine.position.key()).is_some());
+
+        go(&mut engine, &output, "go depth 2");
+        assert_eq!(engine.tt.generation(), generation + 1);
+
+        engine.handle_command("ucinewgame").unwrap();
+        engine.wait_for_table();
+        assert!(engine.tt.probe(engine.position.key()).is_none());
+    }
+
+    #[test]
+    fn test_ucinewgame_resets_state() {
+        let (mut engine, output) = captured_engine();
+
+        engine.handle_command("position startpos moves e2e4 e7e5").unwrap();
+        go(&mut engine, &output, "go depth 2");
+        engine.handle_command("go ponder searchmoves g1f3").unwrap();
+
+        engine.handle_command("ucinewgame").unwrap();
+        assert!(engine.search.is_none());
+        assert!(output.take().contains("bestmove g1f3"));
+        assert!(!engine.search_params.ponder.load(Ordering::Relaxed));
+        assert_eq!(engine.search_params.search_moves, None);
+        assert!(engine.position.history.is_empty());
+        assert_eq!(engine.position.to_fen(), Position::new().to_fen());
+        engine.wait_for_table();
+        assert_eq!(engine.tt.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_chess960_option() {
+        let (mut engine, output) = captured_engine();
+
+        let response = engine.handle_command("uci").unwrap().unwrap();
+        assert!(response.contains("option name UCI_Chess960 type check default false"));
+        engine.handle_command("setoption name UCI_Chess960 value true").unwrap();
+
+        // X-FEN castling field; the king on b1 castles by taking its rook
+        let fen = "1r4k1/8/8/8/8/8/PPPPPPPP/RK4R1 w GA - 0 1";
+        engine.handle_command(&format!("position fen {} moves b1a1", fen)).unwrap();
+        assert!(engine.position.to_fen().starts_with("1r4k1/8/8/8/8/8/PPPPPPPP/2KR2R1 b "));
+        // A king step to c1 is no longer read as castling
+        engine.handle_command(&format!("position fen {} moves b1c1", fen)).unwrap();
+        assert!(engine.position.to_fen().starts_with("1r4k1/8/8/8/8/8/PPPPPPPP/R1K3R1 b "));
+
+        // Castling is the only move allowed, and comes back in the same encoding
+        engine.handle_command(&format!("position fen {}", fen)).unwrap();
+        let response = go(&mut engine, &output, "go depth 1 searchmoves b1g1");
+        assert!(response.contains(" pv b1g1"));
+        assert!(response.lines().last().unwrap().starts_with("bestmove b1g1"));
+    }
+
+    #[test]
+    fn test_variant_option() {
+        let (mut engine, output) = captured_engine();
+
+        let response = engine.handle_command("uci").unwrap().unwrap();
+        let declared = "option name UCI_Variant type combo default chess var chess var chess960";
+        assert!(response.contains(declared));
+        let response = engine.handle_command("setoption name UCI_Variant value atomic").unwrap();
+        assert!(response.unwrap().starts_with("info string Invalid value"));
+        assert!(!engine.chess960);
+
+        // Chess960 keeps the standard rules but takes castling as king-takes-rook
+        engine.handle_command("setoption name UCI_Variant value Chess960").unwrap();
+        assert!(engine.chess960);
+        let fen = "1r4k1/8/8/8/8/8/PPPPPPPP/RK4R1 w GA - 0 1";
+        engine.handle_command(&format!("position fen {} moves b1a1", fen)).unwrap();
+        assert_eq!(engine.position.rules().name(), "chess");
+        let response = go(&mut engine, &output, "go depth 1");
+        assert!(response.contains("bestmove"));
+
+        engine.handle_command("setoption name UCI_Variant value chess").unwrap();
+        assert!(!engine.chess960);
+        engine.handle_command("position startpos moves e2e4").unwrap();
+        assert_eq!(engine.position.rules().name(), "chess");
+    }
+
+    #[test]
+    fn test_currmove_reported_in_long_searches() {
+        use search::SearchObserver;
+
+        let output = Captured::default();
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let castle = position.parse_uci_move("e1g1").unwrap();
+        let mut info = InfoWriter {
+            output: Arc::new(Mutex::new(output.clone())),
+            position,
+            chess960: true,
+            pv: Vec::new(),
+            started: Instant::now(),
+        };
+        info.on_current_move(castle, 1, 4);
+        assert_eq!(output.take(), "");
+
+        info.started -= CURRMOVE_DELAY;
+        info.on_current_move(castle, 3, 4);
+        assert_eq!(output.take(), "info depth 4 currmove e1h1 currmovenumber 3\n");
+    }
+
+    #[test]
+    fn test_threads_option() {
+        let (mut engine, output) = captured_engine();
+
+        let response = engine.handle_command("uci").unwrap().unwrap();
+        assert!(response.contains("option name Threads type spin default 1 min 1 max 256"));
+        assert_eq!(engine.pool.threads(), 1);
+
+        engine.handle_command("setoption name Threads value 4").unwrap();
+        assert_eq!(engine.pool.threads(), 4);
+        engine.handle_command("position startpos").unwrap();
+        let response = go(&mut engine, &output, "go depth 3");
+        assert!(response.lines().last().unwrap().starts_with("bestmove "));
+
+        engine.handle_command("setoption name Threads value 1").unwrap();
+        assert_eq!(engine.pool.threads(), 1);
+    }
+
+    #[test]
+    fn test_hash_option() {
+        let (mut engine, output) = captured_engine();
+
+        let response = engine.handle_command("uci").unwrap().unwrap();
+        assert!(response.contains("option name Hash type spin default 16 min 1 max 65536"));
+        assert_eq!(engine.tt.len(), TranspositionTable::default().len());
+
+        engine.handle_command("setoption name Hash value 1").unwrap();
+        engine.wait_for_table();
+        assert_eq!(engine.tt.len(), TranspositionTable::new(1).len());
+        engine.handle_command("setoption name Hash value 64").unwrap();
+        // The table is allocated in the background while commands are answered
+        let response = engine.handle_command("isready").unwrap();
+        assert_eq!(response, Some("readyok".to_string()));
+        engine.wait_for_table();
+        assert_eq!(engine.tt.len(), TranspositionTable::new(64).len());
+
+        // The resized table is the one searches use
+        engine.handle_command("position startpos").unwrap();
+        go(&mut engine, &output, "go depth 2");
+        assert!(engine.tt.probe(engine.position.key()).is_some());
+    }
+
+    #[test]
+    fn test_go_mate() {
+        let (mut engine, output) = captured_engine();
+
+        engine.handle_command("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let response = go(&mut engine, &output, "go mate 1");
+        assert!(response.contains("bestmove a1a8"));
+        assert!(response.contains("score mate 1"));
+
+        // Mates found by the regular search are reported in moves as well
+        let response = go(&mut engine, &output, "go depth 3");
+        assert!(response.contains("score mate 1 "));
+        assert!(!response.contains("score cp 19"));
+    }
+
+    #[test]
+    fn test_go_ponder() {
+        let (mut engine, output) = captured_engine();
+
+        let response = engine.handle_command("uci").unwrap().unwrap();
+        assert!(response.contains("option name Ponder type check default false"));
+
+        // The best move comes with the reply the engine expects
+        engine.handle_command("position startpos").unwrap();
+        let response = go(&mut engine, &output, "go depth 3");
+        let bestmove = response.lines().last().unwrap();
+        let words: Vec<&str> = bestmove.split_whitespace().collect();
+        assert_eq!((words.len(), words[0], words[2]), (4, "bestmove", "ponder"));
+
+        // Pondering outlasts the clock until the opponent plays the expected move
+        let moves = format!("position startpos moves {} {}", words[1], words[3]);
+        engine.handle_command(&moves).unwrap();
+        engine.handle_command("go ponder wtime 50 btime 50").unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!output.take().contains("bestmove"));
+        engine.handle_command("ponderhit").unwrap();
+        engine.wait_for_search();
+        assert!(output.take().contains("bestmove"));
+
+        // Stopping a ponder search sends its best move as well
+        engine.handle_command("go ponder").unwrap();
+        engine.handle_command("stop").unwrap();
+        assert!(output.take().contains("bestmove"));
+    }
+
+    #[test]
+    fn test_go_returns_while_searching() {
+        let (mut engine, output) = captured_engine();
+
+        engine.handle_command("position startpos").unwrap();
+        assert_eq!(engine.handle_command("go infinite").unwrap(), None);
+        let response = engine.handle_command("isready").unwrap();
+        assert_eq!(response, Some("readyok".to_string()));
+        assert!(!output.take().contains("bestmove"));
+        assert_eq!(engine.search_params.depth, search::MAX_PLY);
+
+        // The best move is written by the time `stop` returns
+        assert_eq!(engine.handle_command("stop").unwrap(), None);
+        assert!(output.take().lines().last().unwrap().starts_with("bestmove "));
+        assert_eq!(engine.handle_command("stop").unwrap(), None);
+        assert_eq!(output.take(), "");
+
+        // A new `go` ends the search before it
+        engine.handle_command("go infinite").unwrap();
+        let response = go(&mut engine, &output, "go depth 1");
+        assert_eq!(response.matches("bestmove").count(), 2);
+    }
+
+    #[test]
+    fn test_go_infinite_waits_for_stop() {
+        let (mut engine, output) = captured_engine();
+
+        // The mate is found at once, yet the best move waits for `stop`
+        engine.handle_command("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        engine.handle_command("go infinite").unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!output.take().contains("bestmove"));
+        engine.handle_command("stop").unwrap();
+        assert!(output.take().contains("bestmove a1a8"));
     }
 }
\ No newline at end of file
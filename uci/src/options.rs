@@ -0,0 +1,211 @@
+//! UCI options: the `option name ...` lines printed for `uci`, and the checking of
+//! `setoption` values against each option's type
+
+use std::fmt;
+
+use crate::Result;
+
+/// Type of an option with its default and bounds, as declared to the GUI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionKind {
+    Spin { default: i64, min: i64, max: i64 },
+    Check { default: bool },
+    String { default: String },
+    Combo { default: String, vars: Vec<String> },
+    Button,
+}
+
+/// A value sent with `setoption`, checked against the option's type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionValue {
+    Spin(i64),
+    Check(bool),
+    String(String),
+    Button,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UciOption {
+    pub name: &'static str,
+    pub kind: OptionKind,
+}
+
+impl UciOption {
+    pub fn spin(name: &'static str, default: i64, min: i64, max: i64) -> Self {
+        Self {
+            name,
+            kind: OptionKind::Spin { default, min, max },
+        }
+    }
+
+    pub fn check(name: &'static str, default: bool) -> Self {
+        Self {
+            name,
+            kind: OptionKind::Check { default },
+        }
+    }
+
+    pub fn string(name: &'static str, default: &str) -> Self {
+        Self {
+            name,
+            kind: OptionKind::String {
+                default: default.to_string(),
+            },
+        }
+    }
+
+    pub fn combo(name: &'static str, default: &str, vars: &[&str]) -> Self {
+        Self {
+            name,
+            kind: OptionKind::Combo {
+                default: default.to_string(),
+                vars: vars.iter().map(|var| var.to_string()).collect(),
+            },
+        }
+    }
+
+    pub fn button(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: OptionKind::Button,
+        }
+    }
+
+    /// Read a `setoption` value for this option. Spins are clamped to their bounds,
+    /// combo values are matched ignoring case, and `<empty>` clears a string.
+    pub fn parse_value(&self, value: &str) -> Result<OptionValue> {
+        let invalid = || format!("Invalid value for {}: {}", self.name, value);
+        match &self.kind {
+            OptionKind::Spin { min, max, .. } => {
+                let value: i64 = value.parse().map_err(|_| invalid())?;
+                Ok(OptionValue::Spin(value.clamp(*min, *max)))
+            }
+            OptionKind::Check { .. } => match value.to_ascii_lowercase().as_str() {
+                "true" => Ok(OptionValue::Check(true)),
+                "false" => Ok(OptionValue::Check(false)),
+                _ => Err(invalid().into()),
+            },
+            OptionKind::String { .. } if value == "<empty>" => {
+                Ok(OptionValue::String(String::new()))
+            }
+            OptionKind::String { .. } => Ok(OptionValue::String(value.to_string())),
+            OptionKind::Combo { vars, .. } => vars
+                .iter()
+                .find(|var| var.eq_ignore_ascii_case(value))
+                .map(|var| OptionValue::String(var.clone()))
+                .ok_or_else(|| invalid().into()),
+            OptionKind::Button => Ok(OptionValue::Button),
+        }
+    }
+}
+
+impl fmt::Display for UciOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match &self.kind {
+            OptionKind::Spin { default, min, max } => {
+                write!(f, "spin default {default} min {min} max {max}")
+            }
+            OptionKind::Check { default } => write!(f, "check default {default}"),
+            OptionKind::String { default } if default.is_empty() => {
+                write!(f, "string default <empty>")
+            }
+            OptionKind::String { default } => write!(f, "string default {default}"),
+            OptionKind::Combo { default, vars } => {
+                write!(f, "combo default {default}")?;
+                for var in vars {
+                    write!(f, " var {var}")?;
+                }
+                Ok(())
+            }
+            OptionKind::Button => write!(f, "button"),
+        }
+    }
+}
+
+/// The options an engine declares, in the order `uci` prints them
+#[derive(Debug, Clone, Default)]
+pub struct OptionRegistry {
+    options: Vec<UciOption>,
+}
+
+impl OptionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, option: UciOption) {
+        self.options.push(option);
+    }
+
+    /// The option called `name`, which GUIs may send in any case
+    pub fn get(&self, name: &str) -> Option<&UciOption> {
+        self.options
+            .iter()
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &UciOption> {
+        self.options.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_option_lines() {
+        assert_eq!(
+            UciOption::spin("Hash", 16, 1, 1024).to_string(),
+            "option name Hash type spin default 16 min 1 max 1024"
+        );
+        assert_eq!(
+            UciOption::check("Ponder", false).to_string(),
+            "option name Ponder type check default false"
+        );
+        assert_eq!(
+            UciOption::string("Debug Log File", "").to_string(),
+            "option name Debug Log File type string default <empty>"
+        );
+        assert_eq!(
+            UciOption::combo("Style", "Normal", &["Solid", "Normal"]).to_string(),
+            "option name Style type combo default Normal var Solid var Normal"
+        );
+        assert_eq!(
+            UciOption::button("Clear Hash").to_string(),
+            "option name Clear Hash type button"
+        );
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let mut registry = OptionRegistry::new();
+        registry.add(UciOption::spin("Hash", 16, 1, 1024));
+        registry.add(UciOption::check("Ponder", false));
+        registry.add(UciOption::combo("Style", "Normal", &["Solid", "Normal"]));
+        registry.add(UciOption::string("Debug Log File", ""));
+
+        let hash = registry.get("hash").unwrap();
+        assert_eq!(hash.parse_value("64").unwrap(), OptionValue::Spin(64));
+        assert_eq!(hash.parse_value("99999").unwrap(), OptionValue::Spin(1024));
+        assert!(hash.parse_value("lots").is_err());
+
+        let ponder = registry.get("Ponder").unwrap();
+        assert_eq!(
+            ponder.parse_value("TRUE").unwrap(),
+            OptionValue::Check(true)
+        );
+        assert!(ponder.parse_value("yes").is_err());
+
+        let style = registry.get("Style").unwrap();
+        let solid = OptionValue::String("Solid".to_string());
+        assert_eq!(style.parse_value("solid").unwrap(), solid);
+        assert!(style.parse_value("Risky").is_err());
+
+        let path = registry.get("Debug Log File").unwrap();
+        let empty = OptionValue::String(String::new());
+        assert_eq!(path.parse_value("<empty>").unwrap(), empty);
+        assert!(registry.get("Threads").is_none());
+    }
+}